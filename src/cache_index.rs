@@ -0,0 +1,280 @@
+//! 下载缓存的索引, 记录每个安装包对应的环境、版本及平台,
+//! `--offline`、`--force` 按这些信息查找缓存的安装包, 不依赖下载地址推断文件名;
+//! 同时记录安装包的 sha256 及计算时的文件大小、修改时间, 文件未变化时不重新计算
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::download::get_download_cache_dir;
+use crate::error::EnvError;
+
+/// 索引在下载缓存目录中的文件名
+const CACHE_INDEX_FILE: &str = "cache_index.json";
+
+/// 多个环境并行下载时串行读写索引
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// 缓存的安装包
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CacheEntry {
+    pub name: String,
+    pub version: String,
+    pub platform: String,
+    /// 缓存目录中的文件名
+    pub filename: String,
+    pub sha256: String,
+    /// 计算 sha256 时的文件大小
+    #[serde(default)]
+    pub size: u64,
+    /// 计算 sha256 时文件的修改时间, 单位为纳秒
+    #[serde(default)]
+    pub modified: u128,
+}
+
+impl CacheEntry {
+    fn matches(&self, name: &str, version: &str, platform: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) && self.version == version && self.platform == platform
+    }
+}
+
+/// 文件的大小及修改时间, 两者都未变化时认为内容未变化
+fn file_stamp(path: &Path) -> Result<(u64, u128)> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    Ok((metadata.len(), modified))
+}
+
+fn read_index(cache_dir: &Path) -> Vec<CacheEntry> {
+    let path = cache_dir.join(CACHE_INDEX_FILE);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return vec![];
+    };
+    serde_json::from_str(&content)
+        .inspect_err(|e| log::warn!("缓存索引 {} 无法解析, 将重新生成: {}", path.display(), e))
+        .unwrap_or_default()
+}
+
+fn write_index(cache_dir: &Path, entries: &[CacheEntry]) -> Result<()> {
+    let path = cache_dir.join(CACHE_INDEX_FILE);
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_string_pretty(entries)?)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// 计算文件的 sha256
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 在下载缓存目录中记录下载完成的安装包, 同一环境、版本及平台只保留最新的记录,
+/// 文件已不存在的记录一并清理; 不在该目录中的文件不记录
+pub fn record_in(
+    cache_dir: &Path,
+    name: &str,
+    version: &str,
+    platform: &str,
+    file: &Path,
+) -> Result<()> {
+    if file.parent() != Some(cache_dir) {
+        return Ok(());
+    }
+    let (size, modified) = file_stamp(file)?;
+    let entry = CacheEntry {
+        name: name.to_string(),
+        version: version.to_string(),
+        platform: platform.to_string(),
+        filename: file
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        sha256: sha256_file(file)?,
+        size,
+        modified,
+    };
+
+    let _lock = INDEX_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries = read_index(cache_dir);
+    entries
+        .retain(|e| !e.matches(name, version, platform) && cache_dir.join(&e.filename).is_file());
+    entries.push(entry);
+    write_index(cache_dir, &entries)
+}
+
+/// 按环境、版本及平台在下载缓存目录中查找安装包
+pub fn find_in(cache_dir: &Path, name: &str, version: &str, platform: &str) -> Option<PathBuf> {
+    read_index(cache_dir)
+        .into_iter()
+        .filter(|e| e.matches(name, version, platform))
+        .map(|e| cache_dir.join(e.filename))
+        .find(|path| path.is_file())
+}
+
+/// 重新计算缓存的安装包的 sha256 并与记录比较, 用于 `env verify`
+pub fn verify_cached(name: &str, version: &str, platform: &str) -> Result<Option<CacheEntry>> {
+    check_in(&get_download_cache_dir()?, name, version, platform, true)
+}
+
+/// 校验下载缓存目录中的安装包, 文件大小及修改时间与记录一致时直接使用记录的 sha256,
+/// 否则重新计算并与记录比较, force 为 true 时总是重新计算; 索引中没有记录时返回 None
+pub fn check_in(
+    cache_dir: &Path,
+    name: &str,
+    version: &str,
+    platform: &str,
+    force: bool,
+) -> Result<Option<CacheEntry>> {
+    let _lock = INDEX_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries = read_index(cache_dir);
+    let Some(entry) = entries
+        .iter_mut()
+        .find(|e| e.matches(name, version, platform) && cache_dir.join(&e.filename).is_file())
+    else {
+        return Ok(None);
+    };
+
+    let path = cache_dir.join(&entry.filename);
+    let stamp = file_stamp(&path)?;
+    if !force && stamp == (entry.size, entry.modified) {
+        return Ok(Some(entry.clone()));
+    }
+
+    let actual = sha256_file(&path)?;
+    if actual != entry.sha256 {
+        return Err(EnvError::ChecksumMismatch {
+            file: path.display().to_string(),
+            expected: entry.sha256.clone(),
+            actual,
+        }
+        .into());
+    }
+    // 内容未变化, 更新文件大小及修改时间, 下次不再重新计算
+    let checked = CacheEntry {
+        size: stamp.0,
+        modified: stamp.1,
+        ..entry.clone()
+    };
+    if checked != *entry {
+        *entry = checked.clone();
+        write_index(cache_dir, &entries)?;
+    }
+    Ok(Some(checked))
+}
+
+/// 删除下载缓存目录索引中环境、版本及平台对应的记录, 文件由调用方删除
+pub fn forget_in(cache_dir: &Path, name: &str, version: &str, platform: &str) -> Result<()> {
+    let _lock = INDEX_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries = read_index(cache_dir);
+    let len = entries.len();
+    entries.retain(|e| !e.matches(name, version, platform));
+    if entries.len() != len {
+        write_index(cache_dir, &entries)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_cached() {
+        let cache_dir = std::env::temp_dir().join("env_check_cached_test");
+        let _ = fs::remove_dir_all(&cache_dir);
+        fs::create_dir_all(&cache_dir).unwrap();
+        let file = cache_dir.join("tool-1.0.tar.gz");
+        fs::write(&file, b"abc").unwrap();
+        record_in(&cache_dir, "tool", "1.0", "linux-x64", &file).unwrap();
+        let check = |force| check_in(&cache_dir, "tool", "1.0", "linux-x64", force);
+
+        // 旧版本索引中没有文件大小及修改时间, 重新计算后补上
+        let mut entries = read_index(&cache_dir);
+        entries[0].size = 0;
+        write_index(&cache_dir, &entries).unwrap();
+        assert_eq!(check(false).unwrap().unwrap().size, 3);
+        assert_eq!(read_index(&cache_dir)[0].size, 3);
+
+        // 文件未变化时使用记录的 sha256, 不读取文件内容
+        let mut entries = read_index(&cache_dir);
+        entries[0].sha256 = "recorded".to_string();
+        write_index(&cache_dir, &entries).unwrap();
+        assert_eq!(check(false).unwrap().unwrap().sha256, "recorded");
+
+        // 强制重新计算时发现不一致
+        let err = check(true).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(EnvError::ChecksumMismatch { expected, .. }) if expected == "recorded"
+        ));
+
+        // 文件内容变化后即使不强制也会重新计算
+        record_in(&cache_dir, "tool", "1.0", "linux-x64", &file).unwrap();
+        assert!(check(true).is_ok());
+        fs::write(&file, b"abcd").unwrap();
+        assert!(check(false).is_err());
+
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_index() {
+        let cache_dir = std::env::temp_dir().join("env_cache_index_test");
+        let _ = fs::remove_dir_all(&cache_dir);
+        fs::create_dir_all(&cache_dir).unwrap();
+
+        // 文件名中没有版本号, 只能通过索引找到
+        let file = cache_dir.join("0123456789abcdef-tool");
+        fs::write(&file, b"abc").unwrap();
+        record_in(&cache_dir, "tool", "1.0", "linux-x64", &file).unwrap();
+        assert_eq!(
+            find_in(&cache_dir, "Tool", "1.0", "linux-x64"),
+            Some(file.clone())
+        );
+        assert_eq!(find_in(&cache_dir, "tool", "1.0", "macos-arm64"), None);
+        assert_eq!(
+            read_index(&cache_dir)[0].sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        // 文件被删除后不再返回, 下次记录时清理
+        let other = cache_dir.join("tool-2.0.tar.gz");
+        fs::write(&other, b"2.0").unwrap();
+        fs::remove_file(&file).unwrap();
+        assert_eq!(find_in(&cache_dir, "tool", "1.0", "linux-x64"), None);
+        record_in(&cache_dir, "tool", "2.0", "linux-x64", &other).unwrap();
+        assert_eq!(read_index(&cache_dir).len(), 1);
+
+        forget_in(&cache_dir, "tool", "2.0", "linux-x64").unwrap();
+        assert!(read_index(&cache_dir).is_empty());
+        assert!(check_in(&cache_dir, "tool", "2.0", "linux-x64", false)
+            .unwrap()
+            .is_none());
+
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+}
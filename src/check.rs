@@ -1,8 +1,20 @@
+use std::cmp::Reverse;
 use std::io::ErrorKind;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::{env, fs};
 
-use crate::env_config::EnvConfig;
+use anyhow::{anyhow, Result};
+
+use crate::download::cache_file_path;
+use crate::env_config::Environment;
+use crate::environment::resolve_bin_dir;
+use crate::search::LATEST_VERSION;
+use crate::t;
+use crate::version::{self, Version};
+
+/// 安装成功后写入版本目录的标记文件
+pub const INSTALL_MARKER: &str = ".env-installed";
 
 pub fn validate_version(version: &str) -> bool {
     let parts: Vec<&str> = version.split('.').collect();
@@ -14,7 +26,7 @@ pub fn validate_version(version: &str) -> bool {
             return false;
         }
         for c in part.chars() {
-            if !c.is_digit(10) {
+            if !c.is_ascii_digit() {
                 return false;
             }
         }
@@ -22,15 +34,116 @@ pub fn validate_version(version: &str) -> bool {
     true
 }
 
-/// 命令行获取Java版本
-pub fn get_java_version_from(output: &str) -> Option<String> {
-    let version = output.split(" ").nth(1).unwrap_or("unknown");
+/// 规范化用户输入的版本号, 去掉首尾空白及前缀 `v`
+pub fn normalize_version(version: &str) -> String {
+    let version = version.trim();
+    version
+        .strip_prefix(['v', 'V'])
+        .filter(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+        .unwrap_or(version)
+        .to_string()
+}
 
-    if version == "unknown" || !validate_version(version) {
-        return None;
+/// 校验用户指定的版本号, 只接受已知的版本(见 `search::known_versions`),
+/// 没有已知版本时只校验格式; 无效时提示最接近的可用版本.
+/// 版本范围(如 `^17`、`>=1.20, <1.22`)解析为已知版本中满足范围的最新版本
+pub fn check_version(env: &Environment, version: &str, known: &[String]) -> Result<String> {
+    let version = normalize_version(version);
+    if is_version_range(&version) {
+        let matched = known
+            .iter()
+            .filter(|v| Version::parse(v).is_some() && version::satisfies(v, &version));
+        return version::max(matched)
+            .cloned()
+            .ok_or_else(|| anyhow!(t!("error.no_matching_version", env.name, version)));
+    }
+    if version == LATEST_VERSION
+        || known.contains(&version)
+        || (known.is_empty() && validate_version(&version))
+    {
+        return Ok(version);
+    }
+
+    match closest_version(&version, known) {
+        Some(closest) => Err(anyhow!(t!(
+            "error.unknown_version",
+            version,
+            env.name,
+            closest
+        ))),
+        None => Err(anyhow!(t!("error.invalid_version", version))),
     }
+}
+
+/// 以比较符开头的输入是版本范围, 见 `version::satisfies`
+fn is_version_range(version: &str) -> bool {
+    version.starts_with(['^', '~', '>', '<', '=', '*'])
+}
+
+/// 在可选版本中查找与输入最接近的版本
+pub fn closest_version<'a>(version: &str, options: &'a [String]) -> Option<&'a str> {
+    options
+        .iter()
+        .min_by_key(|option| version_distance(version, option))
+        .map(|option| option.as_str())
+}
+
+/// 版本距离: 优先比较相同的数字前缀长度, 其次比较第一个不同数字的差值, 最后比较编辑距离
+fn version_distance(a: &str, b: &str) -> (Reverse<usize>, u64, usize) {
+    let (common, diff) = match (numeric_parts(a), numeric_parts(b)) {
+        (Some(a), Some(b)) => {
+            let common = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+            let diff = match (a.get(common), b.get(common)) {
+                (Some(x), Some(y)) => x.abs_diff(*y),
+                _ => 0,
+            };
+            (common, diff)
+        }
+        _ => (0, 0),
+    };
+    (Reverse(common), diff, edit_distance(a, b))
+}
 
-    Some(version.to_string())
+fn numeric_parts(version: &str) -> Option<Vec<u64>> {
+    version.split('.').map(|part| part.parse().ok()).collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// 从 `java --version`/`java -version` 的输出中解析版本号, 支持各发行版的格式:
+/// Oracle JDK 及 GraalVM 的 `java 17.0.12 2024-07-16 LTS`,
+/// OpenJDK 及 Temurin 的 `openjdk 21.0.2 2024-01-16` 或 `openjdk version "17.0.9" 2023-10-17`
+pub fn get_java_version_from(output: &str) -> Option<String> {
+    let first_line = output.lines().find(|line| !line.trim().is_empty())?;
+    let tokens: Vec<&str> = first_line.split_whitespace().collect();
+
+    // `-version` 的输出为 `<vendor> version "X"`, `--version` 的输出为 `<vendor> X`
+    let version = match tokens.iter().position(|token| *token == "version") {
+        Some(index) => tokens.get(index + 1)?,
+        None => tokens.get(1)?,
+    };
+    let version = version.trim_matches('"');
+
+    is_java_version(version).then(|| version.to_string())
+}
+
+/// Java 版本号由数字及点组成, 首个正式版本可能只有主版本号(如 `21`), Java 8 使用 `1.8.0_381`
+fn is_java_version(version: &str) -> bool {
+    version
+        .split(['.', '_'])
+        .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
 }
 
 /// 命令行获取Java版本
@@ -38,27 +151,260 @@ pub fn get_java_version() -> Option<String> {
     let output = Command::new("java").arg("--version").output();
 
     let output = match output {
-        Ok(output) => String::from_utf8(output.stdout).unwrap(),
+        Ok(output) => String::from_utf8_lossy(&output.stdout).to_string(),
         Err(e) => {
             eprintln!("获取Java版本失败: {}", e);
             return None;
         }
     };
 
-    let version = output.split(" ").nth(1).unwrap_or("unknown");
+    get_java_version_from(&output)
+}
+
+/// 系统中已安装的环境
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedInstall {
+    pub version: String,
+    pub home_dir: PathBuf,
+}
+
+/// 获取环境的可执行程序及查询版本的参数
+fn version_command(name: &str) -> Option<(&'static str, &'static [&'static str])> {
+    match name.to_lowercase().as_str() {
+        "java" => Some(("java", &["-version"])),
+        "node" | "node.js" => Some(("node", &["--version"])),
+        "go" => Some(("go", &["version"])),
+        "maven" => Some(("mvn", &["--version"])),
+        "gradle" => Some(("gradle", &["--version"])),
+        "python" => Some(("python3", &["--version"])),
+        _ => None,
+    }
+}
+
+/// 在 PATH 中查找可执行程序, 会解析符号链接
+pub fn find_executable(program: &str) -> Option<PathBuf> {
+    let extensions: &[&str] = if cfg!(windows) {
+        &["exe", "cmd", "bat"]
+    } else {
+        &[""]
+    };
+
+    let path = env::var_os("PATH")?;
+    for dir in env::split_paths(&path) {
+        for ext in extensions {
+            let candidate = dir.join(program).with_extension(ext);
+            if candidate.is_file() {
+                return Some(fs::canonicalize(&candidate).unwrap_or(candidate));
+            }
+        }
+    }
+    None
+}
+
+/// 从版本命令的输出中解析版本号, 如 `openjdk version "17.0.12"`、`v22.12.0`、`go1.23.4`
+pub fn parse_version_output(output: &str) -> Option<String> {
+    output.split_whitespace().find_map(|token| {
+        let token = token.trim_matches('"');
+        let token = token
+            .strip_prefix("go")
+            .or_else(|| token.strip_prefix('v'))
+            .unwrap_or(token);
+        let end = token
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(token.len());
+        let version = token[..end].trim_end_matches('.');
+
+        (version.contains('.') && version.starts_with(|c: char| c.is_ascii_digit()))
+            .then(|| version.to_string())
+    })
+}
+
+/// 检测系统 PATH 中已安装的环境版本及其主目录
+pub fn detect_installed_version(name: &str) -> Option<DetectedInstall> {
+    let (program, args) = version_command(name)?;
+    let executable = find_executable(program)?;
+
+    let output = Command::new(&executable).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // java -version 输出在标准错误中
+    let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+
+    let mut version = parse_version_output(&text)?;
+    // Java 8 及更早版本的版本号为 1.8.0
+    if program == "java" {
+        if let Some(legacy) = version.strip_prefix("1.") {
+            version = legacy.to_string();
+        }
+    }
+
+    let bin_dir = executable.parent()?;
+    let home_dir = if bin_dir.file_name().is_some_and(|n| n == "bin") {
+        bin_dir.parent()?
+    } else {
+        bin_dir
+    };
+
+    Some(DetectedInstall {
+        version,
+        home_dir: home_dir.to_path_buf(),
+    })
+}
+
+/// PATH 中实际使用的程序与当前版本的对应情况
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathCheck {
+    /// PATH 中使用的就是当前版本
+    Active,
+    /// PATH 中先找到了其他位置的程序
+    Shadowed(PathBuf),
+    /// PATH 中没有找到
+    Missing,
+}
+
+/// 检查 PATH 中使用的是否为当前版本, 已知版本命令的环境按程序位置判断,
+/// 其他环境只检查 bin 目录是否在 PATH 中
+pub fn check_active_on_path(name: &str, bin_dir: &Path, home_dir: &Path) -> PathCheck {
+    let Some((program, _)) = version_command(name) else {
+        let on_path = env::var_os("PATH")
+            .is_some_and(|path| env::split_paths(&path).any(|dir| dir == bin_dir));
+        return if on_path {
+            PathCheck::Active
+        } else {
+            PathCheck::Missing
+        };
+    };
+
+    let Some(executable) = find_executable(program) else {
+        return PathCheck::Missing;
+    };
+    // find_executable 会解析符号链接, 比较前同样解析版本目录
+    let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if executable.starts_with(canonical(bin_dir)) || executable.starts_with(canonical(home_dir)) {
+        PathCheck::Active
+    } else {
+        PathCheck::Shadowed(executable)
+    }
+}
+
+/// 已安装的版本是否满足要求, 如要求 `17` 时 `17.0.12` 满足
+pub fn version_matches(required: &str, installed: &str) -> bool {
+    installed == required || installed.starts_with(&format!("{}.", required))
+}
+
+/// 版本目录是否为完整的安装, 以安装完成时写入的标记文件为准;
+/// 没有标记文件的旧安装检查可执行文件目录中是否有可执行程序, 解压中断留下的目录视为未安装
+pub fn is_install_complete(env: &Environment, version: &str, version_dir: &Path) -> bool {
+    if version_dir.join(INSTALL_MARKER).is_file() {
+        return true;
+    }
+    if !version_dir.is_dir() {
+        return false;
+    }
+
+    // 可执行文件目录以版本目录作为安装目录解析, Node.js 等环境的可执行文件目录就是版本目录,
+    // 只检查目录是否存在会把解压中断的目录当作完整安装
+    let Ok(bin_dir) = resolve_bin_dir(env, version, version_dir.to_str()) else {
+        return false;
+    };
+    bin_dir.starts_with(version_dir) && has_executable(env, &bin_dir)
+}
+
+/// 目录中是否有环境的可执行程序, 未知的环境检查是否有任意可执行文件
+fn has_executable(env: &Environment, bin_dir: &Path) -> bool {
+    if let Some((program, _)) = version_command(&env.name) {
+        return executable_names(program)
+            .iter()
+            .any(|name| is_executable_file(&bin_dir.join(name)));
+    }
+
+    fs::read_dir(bin_dir).is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|entry| is_executable_file(&entry.path()))
+    })
+}
+
+/// 程序在当前系统中可能的文件名
+fn executable_names(program: &str) -> Vec<String> {
+    if cfg!(windows) {
+        ["exe", "cmd", "bat"]
+            .iter()
+            .map(|ext| format!("{}.{}", program, ext))
+            .collect()
+    } else {
+        vec![program.to_string()]
+    }
+}
+
+#[cfg(unix)]
+fn is_executable_file(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ["exe", "cmd", "bat"].contains(&ext.to_lowercase().as_str()))
+}
 
-    if version == "unknown" || !validate_version(version) {
+/// 安装完成后写入标记文件
+pub fn write_install_marker(version_dir: &Path, version: &str) -> Result<()> {
+    fs::create_dir_all(version_dir)?;
+    fs::write(version_dir.join(INSTALL_MARKER), version)?;
+    Ok(())
+}
+
+/// 查询路径所在磁盘当前用户可用的空间, 查询失败时返回 None
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
         return None;
     }
+    // 各平台的字段类型不同, macOS 上 f_bavail 为 u32
+    #[allow(clippy::useless_conversion)]
+    Some(u64::from(stat.f_bavail) * u64::from(stat.f_frsize))
+}
+
+/// 查询路径所在磁盘当前用户可用的空间, 查询失败时返回 None
+#[cfg(windows)]
+pub fn available_space(path: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
 
-    Some(version.to_string())
+    let path: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut available = 0u64;
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            path.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    (ok != 0).then_some(available)
 }
 
-pub fn is_downloaded(name: &str, version: &str) -> bool {
-    let install_dir = PathBuf::from(&EnvConfig::load_deserialize().unwrap().install_path);
-    let install_dir = install_dir.join(name);
-    let download_dir = install_dir.join(format!("{}-{}", name, version));
-    download_dir.exists()
+/// 获取下载缓存中已完整下载的安装包
+pub fn get_cached_package(cache_dir: &Path, url: &str) -> Option<String> {
+    let path = cache_file_path(cache_dir, url);
+    path.is_file().then(|| path.to_str().unwrap().to_string())
 }
 
 /// 检查Java环境
@@ -69,7 +415,7 @@ pub fn check_java_environment(version: &str) -> bool {
         Ok(output) => {
             if output.status.success() {
                 // 可以打印 Java 版本信息
-                if let Some(stdout) = String::from_utf8(output.stdout).ok() {
+                if let Ok(stdout) = String::from_utf8(output.stdout) {
                     let current_version = get_java_version_from(&stdout);
                     if let Some(current_version) = current_version {
                         if current_version == version {
@@ -101,7 +447,13 @@ pub fn check_java_environment(version: &str) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use crate::check::{check_java_environment, get_java_version};
+    use crate::check::{
+        available_space, check_active_on_path, check_java_environment, check_version,
+        closest_version, get_java_version, get_java_version_from, is_install_complete,
+        normalize_version, parse_version_output, version_matches, write_install_marker, PathCheck,
+    };
+    use crate::env_config::EnvConfig;
+    use crate::t;
 
     #[test]
     fn test_get_java_version() {
@@ -109,9 +461,196 @@ mod tests {
         assert_eq!(version, Some("17.0.12".to_string()));
     }
 
+    #[test]
+    fn test_get_java_version_from() {
+        let oracle = "java 17.0.12 2024-07-16 LTS\n\
+            Java(TM) SE Runtime Environment (build 17.0.12+8-LTS-286)\n\
+            Java HotSpot(TM) 64-Bit Server VM (build 17.0.12+8-LTS-286, mixed mode, sharing)";
+        assert_eq!(get_java_version_from(oracle), Some("17.0.12".to_string()));
+
+        let openjdk = "openjdk version \"17.0.9\" 2023-10-17\n\
+            OpenJDK Runtime Environment (build 17.0.9+9-Ubuntu-122.04)\n\
+            OpenJDK 64-Bit Server VM (build 17.0.9+9-Ubuntu-122.04, mixed mode, sharing)";
+        assert_eq!(get_java_version_from(openjdk), Some("17.0.9".to_string()));
+
+        let temurin = "openjdk 21.0.2 2024-01-16 LTS\n\
+            OpenJDK Runtime Environment Temurin-21.0.2+13 (build 21.0.2+13-LTS)\n\
+            OpenJDK 64-Bit Server VM Temurin-21.0.2+13 (build 21.0.2+13-LTS, mixed mode, sharing)";
+        assert_eq!(get_java_version_from(temurin), Some("21.0.2".to_string()));
+
+        let graalvm = "java 21.0.1 2023-10-17\n\
+            Java(TM) SE Runtime Environment Oracle GraalVM 21.0.1+12.1 (build 21.0.1+12-jvmci-23.1-b19)\n\
+            Java HotSpot(TM) 64-Bit Server VM Oracle GraalVM 21.0.1+12.1 (build 21.0.1+12-jvmci-23.1-b19, mixed mode, sharing)";
+        assert_eq!(get_java_version_from(graalvm), Some("21.0.1".to_string()));
+
+        let graalvm_ce = "openjdk version \"17.0.5\" 2022-10-18\n\
+            OpenJDK Runtime Environment GraalVM CE 22.3.0 (build 17.0.5+8-jvmci-22.3-b08)";
+        assert_eq!(
+            get_java_version_from(graalvm_ce),
+            Some("17.0.5".to_string())
+        );
+
+        // 首个正式版本只有主版本号, Java 8 使用旧的版本格式
+        assert_eq!(
+            get_java_version_from("openjdk version \"21\" 2023-09-19"),
+            Some("21".to_string())
+        );
+        assert_eq!(
+            get_java_version_from("java version \"1.8.0_381\""),
+            Some("1.8.0_381".to_string())
+        );
+        assert_eq!(get_java_version_from("command not found: java"), None);
+        assert_eq!(get_java_version_from(""), None);
+    }
+
     #[test]
     fn test_check_java_environment() {
         let version = "17.0.12";
-        assert!(check_java_environment(&version));
+        assert!(check_java_environment(version));
+    }
+
+    #[test]
+    fn test_closest_version() {
+        let java: Vec<String> = ["21", "17", "11", "8"].map(String::from).to_vec();
+        assert_eq!(closest_version("17.09", &java), Some("17"));
+        assert_eq!(closest_version("20", &java), Some("21"));
+
+        let rust: Vec<String> = ["stable", "beta", "nightly"].map(String::from).to_vec();
+        assert_eq!(closest_version("stabel", &rust), Some("stable"));
+        assert_eq!(closest_version("1.2.3", &[]), None);
+    }
+
+    #[test]
+    fn test_check_version() {
+        let config: EnvConfig =
+            serde_json::from_str(include_str!("../.env.config.default.json")).unwrap();
+        let java = config.get_enviroment("java").unwrap();
+        let known: Vec<String> = ["21", "17", "11", "8"].map(String::from).to_vec();
+
+        assert_eq!(check_version(&java, " v17 ", &known).unwrap(), "17");
+        assert_eq!(check_version(&java, "latest", &known).unwrap(), "latest");
+        // 格式正确但不在已知版本中时同样提示最接近的版本
+        for version in ["17.0.9", "17.09"] {
+            let err = check_version(&java, version, &known).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                t!("error.unknown_version", version, "java", "17")
+            );
+        }
+
+        // 版本范围解析为满足范围的最新已知版本
+        let known: Vec<String> = ["21.0.5", "17.0.13", "17.0.9", "11.0.25", "stable"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(check_version(&java, "^17", &known).unwrap(), "17.0.13");
+        assert_eq!(
+            check_version(&java, ">=11, <21", &known).unwrap(),
+            "17.0.13"
+        );
+        assert_eq!(check_version(&java, "*", &known).unwrap(), "21.0.5");
+        let err = check_version(&java, "^8", &known).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            t!("error.no_matching_version", "java", "^8")
+        );
+
+        // 没有已知版本时只校验格式
+        assert_eq!(check_version(&java, "1.2.3", &[]).unwrap(), "1.2.3");
+        assert!(check_version(&java, "1.2", &[]).is_err());
+    }
+
+    #[test]
+    fn test_normalize_version() {
+        assert_eq!(normalize_version(" v22.12.0 "), "22.12.0");
+        assert_eq!(normalize_version("17"), "17");
+        assert_eq!(normalize_version("very"), "very");
+    }
+
+    #[test]
+    fn test_check_active_on_path() {
+        // 没有版本命令的环境只检查 bin 目录是否在 PATH 中
+        let home = std::env::temp_dir().join("env-status-test/cmake-3.31.3");
+        assert_eq!(
+            check_active_on_path("cmake", &home.join("bin"), &home),
+            PathCheck::Missing
+        );
+    }
+
+    #[test]
+    fn test_parse_version_output() {
+        let java = "openjdk version \"17.0.12\" 2024-07-16\nOpenJDK Runtime Environment";
+        assert_eq!(parse_version_output(java), Some("17.0.12".to_string()));
+        assert_eq!(
+            parse_version_output("v22.12.0\n"),
+            Some("22.12.0".to_string())
+        );
+        assert_eq!(
+            parse_version_output("go version go1.23.4 linux/amd64"),
+            Some("1.23.4".to_string())
+        );
+        assert_eq!(
+            parse_version_output("Apache Maven 3.9.9 (8e8579a9e76f7d015ee5ec7bfcdc97d260186937)"),
+            Some("3.9.9".to_string())
+        );
+        assert_eq!(parse_version_output("command not found"), None);
+
+        assert!(version_matches("17", "17.0.12"));
+        assert!(!version_matches("1", "17.0.12"));
+    }
+
+    #[test]
+    fn test_available_space() {
+        assert!(available_space(&std::env::temp_dir()).is_some_and(|size| size > 0));
+        assert_eq!(
+            available_space(std::path::Path::new("/env/not/exists")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_install_complete() {
+        let config: EnvConfig =
+            serde_json::from_str(include_str!("../.env.config.default.json")).unwrap();
+        let env = config.get_enviroment("java").unwrap();
+
+        let version_dir = std::env::temp_dir().join("env_install_complete_test");
+        let _ = std::fs::remove_dir_all(&version_dir);
+        assert!(!is_install_complete(&env, "17", &version_dir));
+
+        // 解压中断只留下了部分文件
+        std::fs::create_dir_all(version_dir.join("lib")).unwrap();
+        assert!(!is_install_complete(&env, "17", &version_dir));
+
+        // 没有标记文件的旧安装检查可执行文件目录中的程序
+        std::fs::create_dir_all(version_dir.join("bin")).unwrap();
+        assert!(!is_install_complete(&env, "17", &version_dir));
+        write_executable(&version_dir.join("bin"), "java");
+        assert!(is_install_complete(&env, "17", &version_dir));
+
+        std::fs::remove_dir_all(version_dir.join("bin")).unwrap();
+        write_install_marker(&version_dir, "17").unwrap();
+        assert!(is_install_complete(&env, "17", &version_dir));
+
+        // Node.js 的可执行文件目录就是版本目录, 只解压出部分文件时不算完整安装
+        let node = config.get_enviroment("node").unwrap();
+        let node_dir = version_dir.join("node-v20.10.0");
+        std::fs::create_dir_all(node_dir.join("lib")).unwrap();
+        std::fs::write(node_dir.join("README.md"), "").unwrap();
+        assert!(!is_install_complete(&node, "20.10.0", &node_dir));
+        write_executable(&node_dir, "node");
+        assert!(is_install_complete(&node, "20.10.0", &node_dir));
+
+        std::fs::remove_dir_all(&version_dir).unwrap();
+    }
+
+    /// 在目录中创建当前系统可执行的空程序
+    fn write_executable(dir: &std::path::Path, program: &str) {
+        let path = dir.join(&super::executable_names(program)[0]);
+        std::fs::write(&path, "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
     }
 }
@@ -0,0 +1,464 @@
+//! 数据库环境安装后的数据目录初始化, 只解压的数据库没有数据目录无法启动
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use serde_json::Value;
+use tempfile::NamedTempFile;
+
+use crate::env_config::{DatabaseConfig, EnvConfig, Environment};
+use crate::environment::resolve_environment;
+use crate::options::is_dry_run;
+use crate::{get_temp_dir, status, t};
+
+/// 需要初始化数据目录的数据库
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Database {
+    MySql,
+    PostgreSql,
+    Redis,
+    MongoDb,
+}
+
+impl Database {
+    fn from_name(name: &str) -> Option<Database> {
+        match name.to_lowercase().as_str() {
+            "mysql" => Some(Database::MySql),
+            "postgresql" | "postgres" => Some(Database::PostgreSql),
+            "redis" => Some(Database::Redis),
+            "mongodb" | "mongo" => Some(Database::MongoDb),
+            _ => None,
+        }
+    }
+
+    fn default_port(&self) -> &'static str {
+        match self {
+            Database::MySql => "3306",
+            Database::PostgreSql => "5432",
+            Database::Redis => "6379",
+            Database::MongoDb => "27017",
+        }
+    }
+
+    /// 数据目录中记录端口的配置文件
+    fn config_file(&self) -> &'static str {
+        match self {
+            Database::MySql => "my.cnf",
+            Database::PostgreSql => "postgresql.conf",
+            Database::Redis => "redis.conf",
+            Database::MongoDb => "mongod.conf",
+        }
+    }
+}
+
+/// 数据目录, 放在安装目录之外, 重新安装或切换版本时不会被删除
+pub fn get_data_dir(name: &str) -> Result<PathBuf> {
+    Ok(PathBuf::from(&EnvConfig::current()?.install_path)
+        .join("data")
+        .join(name.to_lowercase()))
+}
+
+/// 读取安装参数, 未填写时密码从环境变量读取, 其他使用配置中的默认值
+fn get_arg(env: &Environment, args: &Value, name: &str) -> Option<String> {
+    if let Some(value) = args.get(name).and_then(Value::as_str) {
+        return Some(value.to_string());
+    }
+    let arg = env.args.iter().find(|arg| arg.name == name)?;
+    Some(
+        arg.get_env_password(&env.name)
+            .map(|(_, password)| password)
+            .unwrap_or_else(|| arg.default.clone()),
+    )
+}
+
+/// 安装后初始化数据库的数据目录并记录到配置, 非数据库环境直接跳过;
+/// 数据目录已存在时沿用已有数据, 不重新初始化
+pub fn init_database(env: &Environment, version: &str, args: &Value) -> Result<()> {
+    let Some(database) = Database::from_name(&env.name) else {
+        return Ok(());
+    };
+
+    let data_dir = get_data_dir(&env.name)?;
+    let port = get_arg(env, args, "port").unwrap_or_else(|| database.default_port().to_string());
+    let password = get_arg(env, args, "password").unwrap_or_default();
+
+    if is_dry_run() {
+        status!(
+            "{}",
+            t!("dry_run.init_database", env.name, data_dir.display(), port)
+        );
+        return Ok(());
+    }
+
+    let mut password_set = !password.is_empty();
+    if data_dir.exists() {
+        status!("{}", t!("database.existing_dir", data_dir.display()));
+        // 已有数据目录沿用初始化时的密码, 新填写的密码不会生效
+        password_set = EnvConfig::current()?
+            .get_database_configs(&env.name)
+            .values()
+            .any(|config| config.data_dir == data_dir.to_string_lossy() && config.password_set);
+        if !password.is_empty() {
+            status!("{}", t!("database.password_unchanged", env.name).yellow());
+        }
+    } else {
+        status!("{}", t!("database.init", env.name, data_dir.display()));
+        let (_, bin_dir) = resolve_environment(env, version)?;
+        match database {
+            Database::MySql => init_mysql(&bin_dir, &data_dir, &port, &password)?,
+            Database::PostgreSql => init_postgresql(&bin_dir, &data_dir, &port, &password)?,
+            Database::Redis => init_redis(&data_dir, &port, &password)?,
+            Database::MongoDb => init_mongodb(&data_dir, &port, &password)?,
+        }
+    }
+
+    EnvConfig::set_database_config(
+        &env.name,
+        version,
+        DatabaseConfig {
+            port,
+            data_dir: data_dir.to_string_lossy().into_owned(),
+            password_set,
+        },
+    )
+}
+
+/// 切换版本后重新应用安装时记录的端口及数据目录, 发现问题只提示不中断切换:
+/// MySQL 的 my.cnf 改为使用新版本的 basedir, 其他数据库检查配置文件中的端口是否一致
+pub fn apply_database_config(env: &Environment, version: &str) -> Result<()> {
+    let Some(database) = Database::from_name(&env.name) else {
+        return Ok(());
+    };
+    if is_dry_run() {
+        return Ok(());
+    }
+
+    let configs = EnvConfig::current()?.get_database_configs(&env.name);
+    let Some(config) = configs.get(version) else {
+        log::debug!("{} {} 没有记录数据库配置", env.name, version);
+        return Ok(());
+    };
+
+    let warn = |message: String| status!("{}", message.yellow());
+    let data_dir = Path::new(&config.data_dir);
+    if !data_dir.is_dir() {
+        warn(t!(
+            "database.missing_dir",
+            env.name,
+            version,
+            config.data_dir
+        ));
+        return Ok(());
+    }
+
+    let shared = shared_versions(&configs, version);
+    if !shared.is_empty() {
+        warn(t!(
+            "database.shared_dir",
+            env.name,
+            version,
+            shared.join(", "),
+            config.data_dir
+        ));
+    }
+
+    let config_file = data_dir.join(database.config_file());
+    match database {
+        Database::MySql => {
+            let (_, bin_dir) = resolve_environment(env, version)?;
+            let base_dir = bin_dir.parent().unwrap_or(&bin_dir);
+            fs::write(&config_file, mysql_config(&config.port, base_dir, data_dir))?;
+        }
+        _ => {
+            let content = fs::read_to_string(&config_file).unwrap_or_default();
+            if !has_port(&content, &config.port) {
+                warn(t!(
+                    "database.port_mismatch",
+                    config_file.display(),
+                    config.port
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 与指定版本使用同一个数据目录的其他版本
+fn shared_versions(configs: &BTreeMap<String, DatabaseConfig>, version: &str) -> Vec<String> {
+    let Some(config) = configs.get(version) else {
+        return vec![];
+    };
+    configs
+        .iter()
+        .filter(|(v, c)| v.as_str() != version && c.data_dir == config.data_dir)
+        .map(|(v, _)| v.clone())
+        .collect()
+}
+
+/// 配置文件中是否有设置为指定端口的 port 配置, 兼容 `port=`、`port = `、`port ` 及 `port: ` 写法
+fn has_port(content: &str, port: &str) -> bool {
+    content.lines().any(|line| {
+        line.trim()
+            .strip_prefix("port")
+            .map(|rest| rest.trim_start_matches([' ', '=', ':']).trim() == port)
+            .unwrap_or(false)
+    })
+}
+
+fn program(bin_dir: &Path, name: &str) -> PathBuf {
+    bin_dir.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX))
+}
+
+fn run_command(command: &mut Command, program: &str) -> Result<()> {
+    let status = command
+        .status()
+        .map_err(|e| anyhow!(t!("database.run_failed", program, e)))?;
+
+    if !status.success() {
+        return Err(anyhow!(t!("database.command_failed", program, status)));
+    }
+    Ok(())
+}
+
+/// 将密码写入临时文件, 避免出现在命令行参数中;
+/// 文件名随机且只有当前用户可读写(0600), 返回值被丢弃时删除文件, 出错时也不会留下密码
+fn write_secret_file(name: &str, content: &str) -> Result<NamedTempFile> {
+    fs::create_dir_all(get_temp_dir())?;
+    let mut file = tempfile::Builder::new()
+        .prefix(name)
+        .tempfile_in(get_temp_dir())?;
+    file.write_all(content.as_bytes())?;
+    file.flush()?;
+    Ok(file)
+}
+
+/// mysqld --initialize-insecure 创建数据目录, 有密码时通过 init-file 设置 root 密码
+fn init_mysql(bin_dir: &Path, data_dir: &Path, port: &str, password: &str) -> Result<()> {
+    let base_dir = bin_dir.parent().unwrap_or(bin_dir);
+    let mut command = Command::new(program(bin_dir, "mysqld"));
+    command
+        .arg("--initialize-insecure")
+        .arg(format!("--basedir={}", base_dir.display()))
+        .arg(format!("--datadir={}", data_dir.display()));
+
+    let init_file = if password.is_empty() {
+        None
+    } else {
+        let sql = format!(
+            "ALTER USER 'root'@'localhost' IDENTIFIED BY '{}';\n",
+            password.replace('\'', "''")
+        );
+        let file = write_secret_file("env_mysql_init", &sql)?;
+        command.arg(format!("--init-file={}", file.path().display()));
+        Some(file)
+    };
+
+    run_command(&mut command, "mysqld")?;
+    drop(init_file);
+
+    // 初始化要求数据目录为空, 配置文件在初始化完成后写入
+    fs::write(
+        data_dir.join("my.cnf"),
+        mysql_config(port, base_dir, data_dir),
+    )?;
+    Ok(())
+}
+
+fn mysql_config(port: &str, base_dir: &Path, data_dir: &Path) -> String {
+    format!(
+        "[mysqld]\nport={}\nbasedir={}\ndatadir={}\n",
+        port,
+        base_dir.display(),
+        data_dir.display()
+    )
+}
+
+/// initdb 创建数据目录, 有密码时使用密码认证, 端口写入 postgresql.conf
+fn init_postgresql(bin_dir: &Path, data_dir: &Path, port: &str, password: &str) -> Result<()> {
+    let mut command = Command::new(program(bin_dir, "initdb"));
+    command
+        .arg("-D")
+        .arg(data_dir)
+        .args(["-U", "postgres", "-E", "UTF8"]);
+
+    let pwfile = if password.is_empty() {
+        command.args(["-A", "trust"]);
+        None
+    } else {
+        let file = write_secret_file("env_postgresql_pwfile", password)?;
+        command
+            .arg(format!("--pwfile={}", file.path().display()))
+            .args(["-A", "scram-sha-256"]);
+        Some(file)
+    };
+
+    run_command(&mut command, "initdb")?;
+    drop(pwfile);
+
+    let config_file = data_dir.join("postgresql.conf");
+    let mut config = fs::read_to_string(&config_file)?;
+    config.push_str(&format!("\nport = {}\n", port));
+    fs::write(config_file, config)?;
+    Ok(())
+}
+
+/// 写入使用所选端口及密码的 redis.conf
+fn init_redis(data_dir: &Path, port: &str, password: &str) -> Result<()> {
+    fs::create_dir_all(data_dir)?;
+    fs::write(
+        data_dir.join("redis.conf"),
+        redis_config(data_dir, port, password),
+    )?;
+    Ok(())
+}
+
+fn redis_config(data_dir: &Path, port: &str, password: &str) -> String {
+    let mut config = format!("port {}\ndir \"{}\"\n", port, data_dir.display());
+    if !password.is_empty() {
+        config.push_str(&format!("requirepass {}\n", password));
+    }
+    config
+}
+
+/// 创建数据库目录并写入 mongod.conf, MongoDB 的用户需要在启动后创建
+fn init_mongodb(data_dir: &Path, port: &str, password: &str) -> Result<()> {
+    let db_path = data_dir.join("db");
+    fs::create_dir_all(&db_path)?;
+    fs::write(
+        data_dir.join("mongod.conf"),
+        format!(
+            "storage:\n  dbPath: \"{}\"\nnet:\n  port: {}\n  bindIp: 127.0.0.1\n",
+            db_path.display(),
+            port
+        ),
+    )?;
+
+    if !password.is_empty() {
+        status!("{}", t!("database.mongodb_password").yellow());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_redis() {
+        assert_eq!(Database::from_name("Postgres"), Some(Database::PostgreSql));
+        assert_eq!(Database::from_name("java"), None);
+
+        let data_dir = Path::new("/opt/dev/data/redis");
+        assert_eq!(
+            redis_config(data_dir, "6380", ""),
+            "port 6380\ndir \"/opt/dev/data/redis\"\n"
+        );
+        assert_eq!(
+            redis_config(data_dir, "6379", "secret"),
+            "port 6379\ndir \"/opt/dev/data/redis\"\nrequirepass secret\n"
+        );
+    }
+
+    #[test]
+    fn test_init_data_dirs() {
+        let root = std::env::temp_dir().join("env_init_database_test");
+        let _ = fs::remove_dir_all(&root);
+
+        let redis_dir = root.join("redis");
+        init_redis(&redis_dir, "6380", "secret").unwrap();
+        assert_eq!(
+            fs::read_to_string(redis_dir.join("redis.conf")).unwrap(),
+            redis_config(&redis_dir, "6380", "secret")
+        );
+
+        let mongodb_dir = root.join("mongodb");
+        init_mongodb(&mongodb_dir, "27018", "").unwrap();
+        assert!(mongodb_dir.join("db").is_dir());
+        let config = fs::read_to_string(mongodb_dir.join("mongod.conf")).unwrap();
+        assert!(has_port(&config, "27018"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    /// 用脚本代替 initdb, 记录密码文件的权限及内容, 密码为 fail 时模拟初始化失败
+    #[cfg(unix)]
+    #[test]
+    fn test_init_postgresql_secret_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = std::env::temp_dir().join("env_init_postgresql_test");
+        let _ = fs::remove_dir_all(&root);
+        let bin_dir = root.join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        let initdb = bin_dir.join("initdb");
+        fs::write(
+            &initdb,
+            r#"#!/bin/sh
+for arg in "$@"; do
+  case "$arg" in --pwfile=*) pwfile="${arg#--pwfile=}" ;; esac
+done
+mkdir -p "$2"
+echo "$pwfile" > "$2/pwfile.path"
+ls -l "$pwfile" | cut -c1-10 > "$2/pwfile.mode"
+cat "$pwfile" > "$2/pwfile.content"
+[ "$(cat "$pwfile")" = fail ] && exit 1
+touch "$2/postgresql.conf"
+"#,
+        )
+        .unwrap();
+        fs::set_permissions(&initdb, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let data_dir = root.join("data");
+        init_postgresql(&bin_dir, &data_dir, "5433", "secret").unwrap();
+        let read = |name: &str| fs::read_to_string(data_dir.join(name)).unwrap();
+        assert_eq!(read("pwfile.mode").trim(), "-rw-------");
+        assert_eq!(read("pwfile.content"), "secret");
+        assert!(!Path::new(read("pwfile.path").trim()).exists());
+        assert!(has_port(&read("postgresql.conf"), "5433"));
+
+        // 初始化失败时同样删除密码文件
+        let data_dir = root.join("failed");
+        assert!(init_postgresql(&bin_dir, &data_dir, "5433", "fail").is_err());
+        let path = fs::read_to_string(data_dir.join("pwfile.path")).unwrap();
+        assert!(!Path::new(path.trim()).exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_apply_database_config() {
+        let config = |data_dir: &str| DatabaseConfig {
+            port: "3306".to_string(),
+            data_dir: data_dir.to_string(),
+            password_set: false,
+        };
+        let configs = BTreeMap::from([
+            ("5.7.44".to_string(), config("/opt/dev/data/mysql")),
+            ("8.0.39".to_string(), config("/opt/dev/data/mysql")),
+            ("8.4.2".to_string(), config("/opt/dev/data/mysql-8.4")),
+        ]);
+        assert_eq!(shared_versions(&configs, "8.0.39"), vec!["5.7.44"]);
+        assert!(shared_versions(&configs, "8.4.2").is_empty());
+        assert!(shared_versions(&configs, "9.0.0").is_empty());
+
+        assert_eq!(
+            mysql_config(
+                "3307",
+                Path::new("/opt/dev/mysql/8.4.2"),
+                Path::new("/opt/dev/data/mysql")
+            ),
+            "[mysqld]\nport=3307\nbasedir=/opt/dev/mysql/8.4.2\ndatadir=/opt/dev/data/mysql\n"
+        );
+
+        assert!(has_port("port 6380\ndir \"/data\"\n", "6380"));
+        assert!(has_port("\nport = 5433\n", "5433"));
+        assert!(has_port("net:\n  port: 27017\n", "27017"));
+        assert!(!has_port("port 6379\n", "6380"));
+        assert!(!has_port("#port 6380\n", "6380"));
+    }
+}
@@ -1,96 +1,790 @@
-use crate::{get_temp_dir, Result};
+use crate::env_config::EnvConfig;
+use crate::environment::{cancelled, prompt_error};
+use crate::error::EnvError;
+use crate::interrupt::check_cancelled;
+use crate::options::{get_cache_dir, is_assume_yes, is_dry_run, is_offline};
+use crate::output::{is_progress_terminal, plain_progress_bar, show_progress, show_status};
+use crate::{get_temp_dir, status, Result};
 use anyhow::anyhow;
-use indicatif::{ProgressBar, ProgressStyle};
-use reqwest::{header, Client};
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use lazy_static::lazy_static;
+use log::debug;
+use reqwest::{header, redirect, Client, StatusCode};
 use std::fs::OpenOptions;
-use std::io::Write;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
 use std::{fs, io};
 
-pub fn create_pbr(size: usize) -> ProgressBar {
-    let pb = ProgressBar::new(size as u64);
+/// 获取下载缓存目录, 依次使用 `--cache-dir`、配置中的 `cache_dir`, 默认位于系统临时目录
+pub fn get_download_cache_dir() -> Result<PathBuf> {
+    Ok(download_cache_dir(&EnvConfig::current()?))
+}
+
+/// 按指定的配置获取下载缓存目录
+pub fn download_cache_dir(config: &EnvConfig) -> PathBuf {
+    get_cache_dir()
+        .or_else(|| config.cache_dir.as_ref().map(PathBuf::from))
+        .unwrap_or_else(|| get_temp_dir().join("env_download_cache"))
+}
+
+/// 创建下载缓存目录并确认可写, 避免下载开始后才失败
+pub fn ensure_cache_dir_writable(cache_dir: &Path) -> Result<()> {
+    let probe = cache_dir.join(format!(".write-test.{}", std::process::id()));
+    fs::create_dir_all(cache_dir)
+        .and_then(|_| fs::write(&probe, b""))
+        .and_then(|_| fs::remove_file(&probe))
+        .map_err(|e| anyhow!("下载缓存目录不可写 {}: {}", cache_dir.display(), e))
+}
+
+/// 下载缓存占用的空间, 包含未下载完成的 `.part` 文件
+pub fn download_cache_size() -> Result<u64> {
+    fn dir_size(dir: &Path) -> u64 {
+        fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| match entry.metadata() {
+                Ok(metadata) if metadata.is_dir() => dir_size(&entry.path()),
+                Ok(metadata) => metadata.len(),
+                Err(_) => 0,
+            })
+            .sum()
+    }
+    Ok(dir_size(&get_download_cache_dir()?))
+}
+
+/// 清理下载缓存, older_than 为天数, 只清理修改时间早于该天数的文件
+pub fn clean_download_cache(older_than: Option<u64>) -> Result<()> {
+    let dry_run = is_dry_run();
+    let cache_dir = get_download_cache_dir()?;
+    if !cache_dir.exists() {
+        status!("下载缓存为空: {}", cache_dir.display());
+        return Ok(());
+    }
+
+    let now = SystemTime::now();
+    let mut total_size = 0;
+    let mut files = vec![];
+    for entry in fs::read_dir(&cache_dir)? {
+        let path = entry?.path();
+        let metadata = path.metadata()?;
+
+        if let Some(days) = older_than {
+            let age = now
+                .duration_since(metadata.modified()?)
+                .unwrap_or(Duration::ZERO);
+            if age < Duration::from_secs(days * 24 * 60 * 60) {
+                continue;
+            }
+        }
+
+        total_size += metadata.len();
+        files.push((path, metadata.len()));
+    }
+
+    status!(
+        "下载缓存目录: {}, 待清理 {} 个文件, 共 {}",
+        cache_dir.display(),
+        files.len(),
+        HumanBytes(total_size)
+    );
+
+    for (path, size) in &files {
+        if dry_run {
+            status!("将删除: {} ({})", path.display(), HumanBytes(*size));
+        } else if path.is_dir() {
+            fs::remove_dir_all(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+    }
+
+    if !dry_run {
+        if older_than.is_none() {
+            fs::remove_dir_all(&cache_dir)?;
+        }
+        status!("清理完成, 释放 {}", HumanBytes(total_size));
+    }
+    Ok(())
+}
+
+lazy_static! {
+    /// 所有下载进度条共用, 并发下载时每个环境各占一行
+    static ref MULTI_PROGRESS: MultiProgress =
+        MultiProgress::with_draw_target(ProgressDrawTarget::stderr());
+    /// 并发下载时串行显示确认提示
+    static ref PROMPT_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// 创建下载进度条, 断点续传时从已下载的大小开始, 百分比及剩余时间按整个文件计算
+pub fn create_pbr(size: u64, position: u64) -> ProgressBar {
+    if !show_progress() {
+        return ProgressBar::hidden();
+    }
+    if !is_progress_terminal() {
+        let pb = plain_progress_bar(size, "下载进度", true);
+        pb.set_position(position);
+        return pb;
+    }
+    let pb = MULTI_PROGRESS.add(ProgressBar::new(size));
 
     pb.set_style(ProgressStyle::default_bar()
-    .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
+    .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} {percent}% ({bytes_per_sec}, {eta})")
     .unwrap()
     .progress_chars("#>-"));
 
+    // 已下载的部分不计入下载速度
+    pb.set_position(position);
+    pb.reset_eta();
+
     pb
 }
 
-pub async fn download_packages(url: &str) -> Result<String> {
+/// 最多跟随的重定向次数
+const MAX_REDIRECTS: usize = 10;
+
+/// 创建带连接及读取超时的 HTTP 客户端, 超时时间由配置中的 `timeout_secs` 决定,
+/// 重定向的每一跳在 `--verbose` 下输出
+pub fn http_client() -> Result<Client> {
+    http_client_for(&EnvConfig::current()?)
+}
+
+/// 按指定配置的超时时间创建 HTTP 客户端
+pub fn http_client_for(config: &EnvConfig) -> Result<Client> {
+    if is_offline() {
+        return Err(anyhow!("离线模式下不能访问网络, 去掉 --offline 后重试"));
+    }
+
+    let timeout = Duration::from_secs(config.timeout_secs);
+    let redirect = redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() > MAX_REDIRECTS {
+            return attempt.error(format!("重定向次数超过 {} 次", MAX_REDIRECTS));
+        }
+        if let Some(from) = attempt.previous().last() {
+            debug!("重定向: {} -> {}", from, attempt.url());
+        }
+        attempt.follow()
+    });
+
+    Client::builder()
+        .connect_timeout(timeout)
+        .read_timeout(timeout)
+        .redirect(redirect)
+        .build()
+        .map_err(|e| anyhow!("无法创建 HTTP 客户端: {}", e))
+}
+
+/// 转换请求错误, 超时时在错误信息中指明地址
+pub fn request_error(url: &str, e: reqwest::Error) -> anyhow::Error {
+    let url = url.to_string();
+    let reason = e.to_string();
+    if e.is_timeout() {
+        EnvError::Timeout { url, reason }.into()
+    } else {
+        EnvError::DownloadFailed { url, reason }.into()
+    }
+}
+
+/// 通过 HEAD 请求检查安装包地址是否存在, 离线模式下只检查下载缓存
+pub async fn check_package_url(url: &str) -> Result<bool> {
+    if is_file_url(url) {
+        return Ok(local_package(url).is_ok());
+    }
+    if is_offline() {
+        return Ok(get_cache_file_path(url)?.is_file());
+    }
+
+    let resp = http_client()?
+        .head(url)
+        .send()
+        .await
+        .map_err(|e| request_error(url, e))?;
+    Ok(resp.status().is_success())
+}
+
+/// 离线模式下缓存中没有安装包时的错误, 指明需要复制到缓存目录的文件
+pub fn offline_cache_error(cache_dir: &Path, url: &str) -> anyhow::Error {
+    anyhow!(
+        "离线模式下下载缓存中没有安装包: {}, 可以在联网的机器上使用 `env dev --only-download` 下载后复制到 {}",
+        cache_file_path(cache_dir, url).display(),
+        cache_dir.display()
+    )
+}
+
+/// 安装包在下载缓存中的位置
+pub fn get_cache_file_path(url: &str) -> Result<PathBuf> {
+    Ok(cache_file_path(&get_download_cache_dir()?, url))
+}
+
+/// 安装包在指定下载缓存目录中的位置
+pub fn cache_file_path(cache_dir: &Path, url: &str) -> PathBuf {
+    cache_dir.join(get_cache_file_name(url))
+}
+
+/// 缓存文件名使用地址的最后一段, 不含版本号的文件名(如 kubectl)加上地址的哈希避免不同版本冲突
+fn get_cache_file_name(url: &str) -> String {
     let url_last = url.split("/").last().unwrap();
-    let base_dir = get_temp_dir().join("env_download_cache");
-
-    // 创建缓存目录如果不存在
-    if !base_dir.exists() {
-        fs::create_dir_all(&base_dir)?;
-    }
-
-    let filename = base_dir.join(url_last).to_str().unwrap().to_string();
-    let path = Path::new(&filename);
-    println!("下载包 {} 到 {}", url, filename);
-
-    let client = Client::new();
-    let total_size = {
-        let resp = client.head(url).send().await?;
-        if resp.status().is_success() {
-            resp.headers()
-                .get(header::CONTENT_LENGTH)
-                .and_then(|ct_len| ct_len.to_str().ok())
-                .and_then(|ct_len| ct_len.parse().ok())
-                .unwrap_or(0)
-        } else {
-            return Err(anyhow!(
-                "Couldn't download URL: {}. Error: {:?}",
-                url,
-                resp.status(),
-            ));
+
+    // rustup-init 各平台的地址只有目标三元组不同, 文件名带上三元组且仍以 rustup-init 开头,
+    // 以便按文件名识别为安装程序
+    if let Some(ext) = url_last.strip_prefix("rustup-init") {
+        if let Some(target) = url.rsplit('/').nth(1).filter(|t| !t.is_empty()) {
+            return format!("rustup-init-{}{}", target, ext);
         }
+    }
+    if url_last.chars().any(|c| c.is_ascii_digit()) {
+        return url_last.to_string();
+    }
+
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    format!("{:016x}-{}", hasher.finish(), url_last)
+}
+
+/// 是否为 `file://` 地址
+pub fn is_file_url(url: &str) -> bool {
+    url.starts_with("file://")
+}
+
+/// 解析本地安装包, 支持文件路径及 `file://` 地址, 文件不存在时报错, 返回绝对路径
+pub fn local_package(source: &str) -> Result<String> {
+    let path = if is_file_url(source) {
+        reqwest::Url::parse(source)
+            .ok()
+            .and_then(|url| url.to_file_path().ok())
+            .ok_or_else(|| anyhow!("无效的文件地址: {}", source))?
+    } else if source.contains("://") {
+        return Err(anyhow!("只支持本地文件路径或 file:// 地址: {}", source));
+    } else {
+        PathBuf::from(source)
     };
-    let mut request = client.get(url);
 
-    let mut has_size = 0;
-    if path.exists() {
-        has_size = path.metadata()?.len().saturating_sub(1);
+    if !path.is_file() {
+        return Err(anyhow!("本地安装包不存在: {}", path.display()));
+    }
+    let path = std::path::absolute(&path)?;
+    path.to_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("本地安装包路径不是有效的 UTF-8: {}", path.display()))
+}
+
+/// 安装包是否位于下载缓存目录中, 只有缓存中的安装包在安装后会被删除
+pub fn is_cached_package(cache_dir: &Path, filename: &str) -> bool {
+    Path::new(filename).parent() == Some(cache_dir)
+}
+
+/// 下载安装包到缓存目录, 下载过程中写入 `.part` 文件, 完成后再重命名,
+/// 因此缓存目录中的安装包总是完整的; `file://` 地址直接使用本地文件
+pub async fn download_packages(url: &str) -> Result<String> {
+    download_packages_with(&EnvConfig::current()?, url).await
+}
+
+/// 按指定配置的缓存目录、超时时间及确认阈值下载安装包
+pub async fn download_packages_with(config: &EnvConfig, url: &str) -> Result<String> {
+    if is_file_url(url) {
+        return local_package(url);
+    }
+    let cache_dir = download_cache_dir(config);
+    ensure_cache_dir_writable(&cache_dir)?;
+
+    let filename = cache_file_path(&cache_dir, url)
+        .to_str()
+        .unwrap()
+        .to_string();
+    if is_offline() {
+        if Path::new(&filename).is_file() {
+            return Ok(filename);
+        }
+        return Err(offline_cache_error(&cache_dir, url));
+    }
+
+    let part_filename = format!("{}.part", filename);
+    let path = Path::new(&part_filename);
+    status!("下载包 {} 到 {}", url, filename);
+
+    let mut request = http_client_for(config)?.get(url);
+
+    // 从已下载的大小继续下载, 追加到未完成的文件末尾
+    let mut has_size = if path.exists() {
+        path.metadata()?.len()
+    } else {
+        0
+    };
+    if has_size > 0 {
         request = request.header(header::RANGE, format!("bytes={}-", has_size));
     }
-    let pb = create_pbr(total_size as usize - has_size as usize);
 
-    let mut source = request.send().await?;
-    let mut dest = OpenOptions::new().create(true).append(true).open(&path)?;
-    while let Some(chunk) = source.chunk().await? {
+    // 大小以 GET 响应为准, 避免 HEAD 请求被重定向到不同的镜像导致进度条总量不一致
+    let mut source = request.send().await.map_err(|e| request_error(url, e))?;
+
+    // 已下载的部分不小于服务器上的文件, 大小一致时说明已下载完成, 否则重新下载
+    if source.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        let total = source
+            .headers()
+            .get(header::CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_content_range_total);
+        if total == Some(has_size) {
+            status!("安装包已下载完成: {}", filename);
+            fs::rename(path, &filename)?;
+            return Ok(filename);
+        }
+
+        debug!("已下载的部分与服务器上的文件不一致, 重新下载: {}", url);
+        fs::remove_file(path)?;
+        has_size = 0;
+        source = http_client_for(config)?
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| request_error(url, e))?;
+    }
+    if !source.status().is_success() {
+        return Err(EnvError::DownloadFailed {
+            url: url.to_string(),
+            reason: source.status().to_string(),
+        }
+        .into());
+    }
+    if source.url().as_str() != url {
+        debug!("下载地址重定向到: {}", source.url());
+    }
+    // 服务器不支持断点续传时返回完整文件, 需要重新下载
+    if has_size > 0 && source.status() != StatusCode::PARTIAL_CONTENT {
+        debug!("服务器不支持断点续传, 重新下载: {}", url);
+        has_size = 0;
+    }
+    if has_size > 0 {
+        status!("继续下载, 已下载 {}", HumanBytes(has_size));
+    }
+    // 续传时响应只包含剩余部分的大小
+    let size = source
+        .content_length()
+        .map(|len| len + has_size)
+        .unwrap_or(0);
+    if size > 0 {
+        status!("安装包大小: {}", HumanBytes(size));
+    }
+    confirm_download(config, url, size).await?;
+    let pb = create_pbr(size, has_size);
+
+    let mut dest = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(has_size > 0)
+        .truncate(has_size == 0)
+        .open(path)?;
+    while let Some(chunk) = source.chunk().await.map_err(|e| request_error(url, e))? {
+        check_cancelled()?;
         dest.write_all(&chunk)?;
         pb.inc(chunk.len() as u64);
     }
     pb.finish_with_message("Download complete");
+    fs::rename(path, &filename)?;
 
     Ok(filename)
 }
 
-// 复制文件到指定目录, 并返回复制后的文件位置
-pub fn copy_file_to_dir(source_file_path: &str, destination_dir_path: &str) -> io::Result<String> {
-    let source_file = Path::new(source_file_path);
-    let destination_dir = Path::new(destination_dir_path);
+/// 读取 `Content-Range: bytes */1234` 中的文件总大小
+fn parse_content_range_total(value: &str) -> Option<u64> {
+    value.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+/// 边下载边解压时在下载与解压线程之间缓冲的数据块数量
+const STREAM_BUFFER_CHUNKS: usize = 32;
+
+/// 是否可以边下载边解压, 只支持顺序读取的 tar 及 tar.gz, zip 等格式需要随机访问
+pub fn is_streamable(url: &str) -> bool {
+    let name = url.rsplit('/').next().unwrap_or(url).to_lowercase();
+    name.ends_with(".tar.gz") || name.ends_with(".tgz") || name.ends_with(".tar")
+}
+
+/// 边下载边解压 tar/tar.gz 安装包到指定目录, 不写入下载缓存, 因此也不支持断点续传
+pub async fn stream_extract(url: &str, output_dir: &Path) -> Result<()> {
+    stream_extract_with(&EnvConfig::current()?, url, output_dir).await
+}
+
+/// 按指定配置的超时时间及确认阈值边下载边解压
+pub async fn stream_extract_with(config: &EnvConfig, url: &str, output_dir: &Path) -> Result<()> {
+    status!("边下载边解压 {} 到 {}", url, output_dir.display());
+
+    let mut source = http_client_for(config)?
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| request_error(url, e))?;
+    if !source.status().is_success() {
+        return Err(EnvError::DownloadFailed {
+            url: url.to_string(),
+            reason: source.status().to_string(),
+        }
+        .into());
+    }
+    let size = source.content_length().unwrap_or(0);
+    if size > 0 {
+        status!("安装包大小: {}", HumanBytes(size));
+    }
+    confirm_download(config, url, size).await?;
+    let pb = create_pbr(size, 0);
+
+    // 解压在阻塞线程中进行, 通过有界队列接收下载的数据
+    let (tx, rx) = tokio::sync::mpsc::channel(STREAM_BUFFER_CHUNKS);
+    let gzip = !url.to_lowercase().ends_with(".tar");
+    let output_dir = output_dir.to_path_buf();
+    let file = url.to_string();
+    let extract = tokio::task::spawn_blocking(move || {
+        let reader = ChannelReader::new(rx);
+        let unpacked = if gzip {
+            tar::Archive::new(flate2::read::GzDecoder::new(reader)).unpack(&output_dir)
+        } else {
+            tar::Archive::new(reader).unpack(&output_dir)
+        };
+        unpacked.map_err(|e| EnvError::ExtractFailed {
+            file,
+            reason: e.to_string(),
+        })
+    });
+
+    while let Some(chunk) = source.chunk().await.map_err(|e| request_error(url, e))? {
+        check_cancelled()?;
+        pb.inc(chunk.len() as u64);
+        // 解压结束(完成或出错)后不再需要剩余的数据
+        if tx.send(chunk).await.is_err() {
+            break;
+        }
+    }
+    drop(tx);
+    pb.finish_and_clear();
+
+    extract
+        .await
+        .map_err(|e| anyhow!("解压线程异常退出: {}", e))??;
+    Ok(())
+}
+
+/// 从异步队列中按顺序读取数据块, 队列关闭后返回 EOF
+struct ChannelReader<B> {
+    rx: tokio::sync::mpsc::Receiver<B>,
+    chunk: Option<B>,
+    pos: usize,
+}
 
-    // 确保源文件存在
-    if !source_file.exists() {
-        return Err(io::Error::new(io::ErrorKind::NotFound, "源文件不存在"));
+impl<B> ChannelReader<B> {
+    fn new(rx: tokio::sync::mpsc::Receiver<B>) -> ChannelReader<B> {
+        ChannelReader {
+            rx,
+            chunk: None,
+            pos: 0,
+        }
     }
+}
 
-    // 确保目标目录存在，如果不存在则创建
-    if !destination_dir.exists() {
-        fs::create_dir_all(destination_dir)?;
+impl<B: AsRef<[u8]>> Read for ChannelReader<B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        check_cancelled().map_err(io::Error::other)?;
+        loop {
+            if let Some(chunk) = &self.chunk {
+                let remaining = &chunk.as_ref()[self.pos..];
+                if !remaining.is_empty() {
+                    let n = remaining.len().min(buf.len());
+                    buf[..n].copy_from_slice(&remaining[..n]);
+                    self.pos += n;
+                    return Ok(n);
+                }
+            }
+            match self.rx.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = Some(chunk);
+                    self.pos = 0;
+                }
+                None => return Ok(0),
+            }
+        }
     }
+}
 
-    // 构建目标文件的完整路径
-    let destination_file = PathBuf::from(destination_dir).join(source_file.file_name().unwrap());
+/// 安装包超过配置的 `confirm_size_mb` 时询问是否下载, `--yes`、`--quiet` 及 JSON 模式下不询问
+///
+/// 提示在阻塞线程中显示, 不占用异步运行时的工作线程, 显示期间暂停所有下载进度条的绘制
+async fn confirm_download(config: &EnvConfig, url: &str, size: u64) -> Result<()> {
+    if !needs_confirm(size, config.confirm_size_mb) || is_assume_yes() || !show_status() {
+        return Ok(());
+    }
+
+    let prompt = format!("下载 {} ({})?", url, HumanBytes(size));
+    let confirmed = tokio::task::spawn_blocking(move || {
+        // 并发下载时逐个询问, 避免提示交错
+        let _guard = PROMPT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        MULTI_PROGRESS.suspend(|| {
+            Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(prompt)
+                .default(true)
+                .interact_opt()
+        })
+    })
+    .await
+    .map_err(|e| anyhow!("确认提示异常退出: {}", e))?
+    .map_err(prompt_error)?
+    .ok_or_else(cancelled)?;
+    if !confirmed {
+        return Err(anyhow!("已取消下载: {}", url));
+    }
+    Ok(())
+}
+
+/// 阈值为 0 时不询问
+fn needs_confirm(size: u64, threshold_mb: u64) -> bool {
+    threshold_mb > 0 && size > threshold_mb * 1024 * 1024
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_cache_file_name() {
+        assert_eq!(
+            get_cache_file_name("https://example.com/go1.23.4.linux-amd64.tar.gz"),
+            "go1.23.4.linux-amd64.tar.gz"
+        );
 
-    let ret = destination_file.to_str().unwrap().to_string();
+        let v1 = get_cache_file_name("https://dl.k8s.io/release/v1.32.0/bin/linux/amd64/kubectl");
+        let v2 = get_cache_file_name("https://dl.k8s.io/release/v1.31.4/bin/linux/amd64/kubectl");
+        assert!(v1.ends_with("-kubectl"));
+        assert_ne!(v1, v2);
 
-    fs::copy(source_file, &destination_file)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("复制文件失败: {}", e)))?;
-    // 删除临时文件
-    fs::remove_file(source_file_path).unwrap();
-    Ok(ret)
+        let linux = "https://static.rust-lang.org/rustup/dist/x86_64-unknown-linux-gnu/rustup-init";
+        let windows =
+            "https://static.rust-lang.org/rustup/dist/x86_64-pc-windows-msvc/rustup-init.exe";
+        assert_eq!(
+            get_cache_file_name(linux),
+            "rustup-init-x86_64-unknown-linux-gnu"
+        );
+        assert_eq!(
+            get_cache_file_name(windows),
+            "rustup-init-x86_64-pc-windows-msvc.exe"
+        );
+        for url in [linux, windows] {
+            assert_eq!(
+                crate::installer::get_installer_type(&get_cache_file_name(url)),
+                Some(crate::installer::InstallerType::Rustup)
+            );
+        }
+    }
+
+    #[test]
+    fn test_needs_confirm() {
+        assert!(needs_confirm(650 * 1024 * 1024, 500));
+        assert!(!needs_confirm(100 * 1024 * 1024, 500));
+        assert!(!needs_confirm(650 * 1024 * 1024, 0));
+    }
+
+    /// 使用独立下载缓存目录的默认配置, 测试不读取用户的全局配置
+    fn test_config(name: &str) -> EnvConfig {
+        let mut config: EnvConfig =
+            serde_json::from_str(include_str!("../.env.config.default.json")).unwrap();
+        let cache_dir = get_temp_dir().join(name);
+        config.cache_dir = Some(cache_dir.to_str().unwrap().to_string());
+        config
+    }
+
+    /// 处理指定次数请求的 HTTP 服务, 支持 `Range: bytes=N-`, 超出文件大小时返回 416
+    fn serve(content: &'static [u8], requests: usize, name: &str) -> String {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                respond(stream.unwrap(), content);
+            }
+        });
+        format!("http://{}/{}", addr, name)
+    }
+
+    fn respond(mut stream: std::net::TcpStream, content: &[u8]) {
+        use std::io::{BufRead, BufReader};
+
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut start = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some(range) = line.to_lowercase().strip_prefix("range: bytes=") {
+                start = range.trim().trim_end_matches('-').parse().unwrap();
+            }
+        }
+
+        if start >= content.len() {
+            let response = format!(
+                "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\n\
+                 Content-Length: 0\r\nConnection: close\r\n\r\n",
+                content.len()
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+            return;
+        }
+
+        let body = &content[start..];
+        let mut response = if start > 0 {
+            format!(
+                "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\n",
+                start,
+                content.len() - 1,
+                content.len()
+            )
+        } else {
+            "HTTP/1.1 200 OK\r\n".to_string()
+        };
+        response.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        ));
+        stream.write_all(response.as_bytes()).unwrap();
+        stream.write_all(body).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_download() {
+        static CONTENT: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+        let url = serve(CONTENT, 1, "env-resume-test-1.0.bin");
+        let config = test_config("env_resume_download_test");
+        let cache_dir = download_cache_dir(&config);
+        let _ = fs::remove_dir_all(&cache_dir);
+
+        // 模拟中断的下载, 已下载前 10 个字节
+        let filename = cache_file_path(&cache_dir, &url);
+        let part = PathBuf::from(format!("{}.part", filename.display()));
+        fs::create_dir_all(part.parent().unwrap()).unwrap();
+        let _ = fs::remove_file(&filename);
+        fs::write(&part, &CONTENT[..10]).unwrap();
+
+        let downloaded = download_packages_with(&config, &url).await.unwrap();
+        assert_eq!(fs::read(&downloaded).unwrap(), CONTENT);
+        assert!(!part.exists());
+
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_range_not_satisfiable() {
+        static CONTENT: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+        // 两次请求返回 416, 第二次之后重新下载
+        let url = serve(CONTENT, 3, "env-resume-test-2.0.bin");
+        let config = test_config("env_resume_416_test");
+        let cache_dir = download_cache_dir(&config);
+        let _ = fs::remove_dir_all(&cache_dir);
+        let filename = cache_file_path(&cache_dir, &url);
+        let part = PathBuf::from(format!("{}.part", filename.display()));
+        fs::create_dir_all(part.parent().unwrap()).unwrap();
+        let _ = fs::remove_file(&filename);
+
+        // 已下载完整的文件, 进程在解压前退出
+        fs::write(&part, CONTENT).unwrap();
+        let downloaded = download_packages_with(&config, &url).await.unwrap();
+        assert_eq!(fs::read(&downloaded).unwrap(), CONTENT);
+        assert!(!part.exists());
+        fs::remove_file(&downloaded).unwrap();
+
+        // 已下载的部分比服务器上的文件大, 重新下载
+        fs::write(&part, [CONTENT, b"extra"].concat()).unwrap();
+        let downloaded = download_packages_with(&config, &url).await.unwrap();
+        assert_eq!(fs::read(&downloaded).unwrap(), CONTENT);
+        fs::remove_dir_all(&cache_dir).unwrap();
+
+        assert_eq!(parse_content_range_total("bytes */1234"), Some(1234));
+        assert_eq!(parse_content_range_total("bytes */*"), None);
+    }
+
+    #[test]
+    fn test_local_package() {
+        let dir = std::env::temp_dir().join("env_local_package_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("jdk 21.tar.gz");
+        fs::write(&file, b"jdk").unwrap();
+        let expected = file.to_str().unwrap();
+
+        assert_eq!(local_package(expected).unwrap(), expected);
+        let url = reqwest::Url::from_file_path(&file).unwrap();
+        assert!(is_file_url(url.as_str()));
+        assert_eq!(local_package(url.as_str()).unwrap(), expected);
+
+        assert!(local_package(dir.join("missing.tar.gz").to_str().unwrap()).is_err());
+        assert!(local_package(dir.to_str().unwrap()).is_err());
+        assert!(local_package("https://example.com/jdk.tar.gz").is_err());
+
+        // 路径不是 UTF-8 时报错而不是 panic
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let invalid = dir.join(std::ffi::OsStr::from_bytes(b"jdk-\xff.tar.gz"));
+            fs::write(&invalid, b"jdk").unwrap();
+            let url = format!("file://{}/jdk-%FF.tar.gz", dir.display());
+            let err = local_package(&url).unwrap_err();
+            assert!(err.to_string().contains("UTF-8"));
+        }
+        assert!(!is_cached_package(
+            &download_cache_dir(&test_config("env_local_package_cache")),
+            expected
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stream_extract() {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        let content = b"stream";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "tool-1.0/bin/tool", &content[..])
+            .unwrap();
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+
+        let url = serve(Box::leak(archive.into_boxed_slice()), 1, "tool-1.0.tar.gz");
+        assert!(is_streamable(&url));
+        assert!(!is_streamable("https://example.com/tool-1.0.zip"));
+
+        let output_dir = get_temp_dir().join("env_stream_extract_test");
+        let _ = fs::remove_dir_all(&output_dir);
+        fs::create_dir_all(&output_dir).unwrap();
+        let config = test_config("env_stream_extract_cache");
+        stream_extract_with(&config, &url, &output_dir)
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read(output_dir.join("tool-1.0").join("bin").join("tool")).unwrap(),
+            content
+        );
+        // 不写入下载缓存
+        assert!(!cache_file_path(&download_cache_dir(&config), &url).exists());
+
+        fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn test_ensure_cache_dir_writable() {
+        let cache_dir = get_temp_dir().join("env_cache_dir_test").join("nested");
+        let _ = fs::remove_dir_all(cache_dir.parent().unwrap());
+
+        ensure_cache_dir_writable(&cache_dir).unwrap();
+        assert!(cache_dir.is_dir());
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 0);
+
+        fs::remove_dir_all(cache_dir.parent().unwrap()).unwrap();
+    }
 }
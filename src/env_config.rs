@@ -1,33 +1,117 @@
 use crate::deduplicate;
-use crate::get_env_home_dir;
+use crate::environment::{cancelled, prompt_error};
+use crate::error::EnvError;
+use crate::i18n::{locale_lang, Lang};
+use crate::options::{is_assume_yes, is_dry_run};
+use crate::output::show_status;
+use crate::zip::DEFAULT_FORMAT;
+use crate::ChooseEnvironment;
+use crate::{get_env_config_dir, get_env_data_dir, get_env_home_dir, status, t, version};
+use anyhow::anyhow;
+use anyhow::Context;
 use anyhow::Ok;
 use anyhow::Result;
+use clap::ValueEnum;
 use config::Config;
+use dialoguer::{theme::ColorfulTheme, Confirm};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs::{self, File},
     path::{Path, PathBuf},
+    sync::RwLock,
 };
 
-const DEFAULT_ENV_CONFIG: &'static str = include_str!("../.env.config.default.json");
+const DEFAULT_ENV_CONFIG: &str = include_str!("../.env.config.default.json");
+
+/// 全局配置文件名
+const CONFIG_FILE_NAME: &str = ".env.config.json";
+
+/// 当前版本快照文件名
+const ACTIVE_FILE_NAME: &str = "active.json";
+
+/// 不能通过 `env config set` 修改的字段, 由安装及导入命令维护
+const PROTECTED_KEYS: [&str; 2] = ["environments", "installed"];
+
+/// 交互参数支持的类型
+const ARG_TYPES: [&str; 4] = ["input", "select", "multi-select", "password"];
 
 lazy_static! {
-    pub static ref ENV_CONFIG: EnvConfig = EnvConfig::load_deserialize().expect("加载环境配置失败");
+    /// 进程内缓存的配置, 只从磁盘加载一次, 所有修改都经由 `EnvConfig::save`/`EnvConfig::update` 同步
+    static ref ENV_CONFIG: RwLock<Option<EnvConfig>> = RwLock::new(None);
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EnvConfig {
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub install_path: String,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub environments: Vec<Environment>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub installed: Option<Vec<InstalledEnvironment>>,
+
+    /// 安装完成后保留下载缓存中的安装包, 重复安装同一版本时无需再次下载
+    #[serde(default)]
+    pub keep_downloads: bool,
+
+    /// 下载缓存目录, 未配置时使用系统临时目录下的 env_download_cache
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<String>,
+
+    /// 安装包超过该大小(MB)时下载前需要确认, 为 0 时不确认
+    #[serde(default = "get_default_confirm_size_mb")]
+    pub confirm_size_mb: u64,
+
+    /// 下载时的连接及读取超时时间(秒)
+    #[serde(default = "get_default_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// `env update-index` 下载仓库索引的地址, 未配置时使用项目仓库中的索引
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_url: Option<String>,
+
+    /// 自定义解析下载地址的外部命令, 以环境名称、版本、系统、架构为参数调用,
+    /// 标准输出中的地址优先使用, 没有输出时使用内置的解析方式
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolver_command: Option<String>,
+
+    /// 输出消息的语言(zh/en), 未配置时根据 LANG 等环境变量判断
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<Lang>,
+
+    /// 切换版本时可执行文件目录加到 PATH 的最前面(prepend)还是最后面(append)
+    #[serde(default)]
+    pub path_strategy: PathStrategy,
+
+    /// 版本目录的布局, name-version 为 `java/java-17.0.9`, version 为 `java/17.0.9`,
+    /// 修改后按原布局安装的版本不再被识别, 需要重新安装
+    #[serde(default)]
+    pub layout: Layout,
+
+    /// `env prune` 时每个环境保留的已安装版本数量, 当前使用的版本始终保留
+    #[serde(default = "get_default_keep_versions")]
+    pub keep_versions: usize,
+
+    /// 各环境下载地址中使用的架构名称, 如 go 使用 amd64 表示 x64
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub arch_mapping: HashMap<String, HashMap<String, String>>,
+
+    /// 版本别名, 环境名称 -> 别名 -> 版本, 如 java 的 lts -> 17.0.9
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub aliases: HashMap<String, HashMap<String, String>>,
+
+    /// 交互安装时默认选中的版本, 环境名称 -> 版本或别名, 如 node -> 18.19.0,
+    /// 未配置时使用环境 version 参数的默认值
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub default_versions: HashMap<String, String>,
+
+    /// 项目固定的版本, 写在项目目录的 `.env.config.json` 中, 不会保存到全局配置
+    #[serde(default, skip_serializing)]
+    pub pinned: HashMap<String, String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -37,11 +121,27 @@ pub struct InstalledEnvironment {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub current_version: Option<String>,
 
+    /// 上一次切换前使用的版本, 用于 `env choose --previous`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_version: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub home_dir: Option<String>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    /// 登记的系统已有安装等位于安装目录之外的版本, 版本 -> 主目录
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub external_homes: BTreeMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub installed_versions: Vec<String>,
+
+    /// 数据库环境各版本初始化时的端口、数据目录及是否设置了 root 密码, 切换版本时重新应用
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub databases: BTreeMap<String, DatabaseConfig>,
+
+    /// 上一次安装时选择的参数, 作为下次交互的默认值, 不保存密码
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub last_args: HashMap<String, Value>,
 }
 
 impl InstalledEnvironment {
@@ -49,30 +149,180 @@ impl InstalledEnvironment {
         InstalledEnvironment {
             name: name.to_string(),
             current_version: Some(version.to_string()),
+            previous_version: None,
             home_dir: Some(home_dir.to_string()),
+            external_homes: BTreeMap::new(),
             installed_versions: vec![version.to_string()],
+            databases: BTreeMap::new(),
+            last_args: HashMap::new(),
         }
     }
+
+    /// 记录切换到的版本, 切换到不同版本时保留上一个版本;
+    /// 版本登记在安装目录之外时主目录使用登记的目录
+    pub fn switch_to(&mut self, version: &str, home_dir: &str) {
+        if self.current_version.as_deref() != Some(version) {
+            self.previous_version = self.current_version.take();
+        }
+        self.current_version = Some(version.to_string());
+        self.home_dir = Some(
+            self.external_homes
+                .get(version)
+                .map_or(home_dir, String::as_str)
+                .to_string(),
+        );
+        // 去重
+        self.installed_versions.push(version.to_string());
+        self.installed_versions = deduplicate(&self.installed_versions);
+    }
+
+    /// 版本登记的主目录: 安装目录之外的版本返回登记的目录, 当前版本返回记录的主目录
+    pub fn home_of(&self, version: &str) -> Option<&str> {
+        self.external_homes
+            .get(version)
+            .map(String::as_str)
+            .or_else(|| {
+                (self.current_version.as_deref() == Some(version))
+                    .then_some(self.home_dir.as_deref())
+                    .flatten()
+            })
+    }
+
+    /// 移除已卸载的版本, 上一个版本被卸载时不能再切换回去
+    pub fn remove_version(&mut self, version: &str) {
+        self.installed_versions.retain(|v| v != version);
+        self.databases.remove(version);
+        self.external_homes.remove(version);
+        if self.previous_version.as_deref() == Some(version) {
+            self.previous_version = None;
+        }
+    }
+}
+
+/// 数据库版本初始化时使用的配置
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseConfig {
+    pub port: String,
+
+    pub data_dir: String,
+
+    /// 初始化时是否设置了 root 密码, 配置中不保存密码本身及其摘要
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub password_set: bool,
+}
+
+/// 用于在机器之间迁移的配置, 只包含自定义环境及已安装的版本, 不包含本机路径
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PortableConfig {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub environments: Vec<Environment>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub installed: Vec<InstalledEnvironment>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Environment {
     pub name: String,
     pub description: String,
-    
-    #[serde(skip_serializing_if = "Option::is_none", default="get_default_support")]
+
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        default = "get_default_support"
+    )]
     pub support: Option<bool>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub args: Vec<EnvironmentInteractArgs>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub executable: Vec<String>,
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub environment: HashMap<String, String>,
-    pub repository: String,
+    pub repository: Repository,
+
+    /// 支持的操作系统(windows/linux/macos), 为空时不限制
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub platforms: Vec<String>,
+
+    /// 安装包本身就是可执行文件, 不需要解压, 安装时复制到版本目录的 bin 目录
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub binary: bool,
+
+    /// 解压完成后依次执行的命令, 支持 `%INSTALL_DIR%` 等变量
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_install: Vec<String>,
+
+    /// 各系统安装包的格式, 用于替换下载地址模板中的 `%format%`, 未配置的系统使用默认格式
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub format: HashMap<String, String>,
 }
 
+/// 下载地址模板, 可以是单个模板或按顺序尝试的多个镜像
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Repository {
+    Single(String),
+    Mirrors(Vec<String>),
+}
+
+impl Repository {
+    /// 按优先级排列的下载地址模板
+    pub fn templates(&self) -> Vec<&str> {
+        match self {
+            Repository::Single(template) => vec![template.as_str()],
+            Repository::Mirrors(templates) => templates.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+impl From<&str> for Repository {
+    fn from(template: &str) -> Repository {
+        Repository::Single(template.to_string())
+    }
+}
+
+impl Environment {
+    /// 获取指定系统的安装包格式
+    pub fn package_format(&self, os: &str) -> String {
+        self.format
+            .get(os)
+            .map(String::as_str)
+            .or_else(|| DEFAULT_FORMAT[os].as_str())
+            .unwrap_or("")
+            .to_string()
+    }
+
+    /// 获取配置中可选的版本列表
+    pub fn get_version_options(&self) -> Vec<String> {
+        self.args
+            .iter()
+            .find(|arg| arg.name == "version")
+            .map(|arg| arg.options.clone())
+            .unwrap_or_default()
+    }
+
+    /// 获取配置中的默认版本
+    pub fn get_default_version(&self) -> Option<String> {
+        self.args
+            .iter()
+            .find(|arg| arg.name == "version")
+            .map(|arg| arg.default.clone())
+            .filter(|version| !version.is_empty())
+    }
+}
+
+fn get_default_confirm_size_mb() -> u64 {
+    500
+}
+
+fn get_default_timeout_secs() -> u64 {
+    30
+}
+
+fn get_default_keep_versions() -> usize {
+    3
+}
 
 fn get_default_support() -> Option<bool> {
     Some(true)
@@ -93,6 +343,164 @@ pub struct EnvironmentInteractArgs {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub select_description: Option<Vec<String>>,
+
+    /// 可选值所属的发布通道, 未列出的为 stable
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub channels: HashMap<String, Channel>,
+
+    /// password 类型是否需要输入两次确认
+    #[serde(default = "default_confirm", skip_serializing_if = "is_confirm")]
+    pub confirm: bool,
+
+    /// password 类型读取密码的环境变量, 默认为 `ENV_<环境名称>_<参数名称>`, 如 `ENV_MYSQL_PASSWORD`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_var: Option<String>,
+}
+
+fn default_confirm() -> bool {
+    true
+}
+
+fn is_confirm(confirm: &bool) -> bool {
+    *confirm
+}
+
+impl EnvironmentInteractArgs {
+    pub fn get_channel(&self, option: &str) -> Channel {
+        self.channels.get(option).copied().unwrap_or_default()
+    }
+
+    /// 读取密码的环境变量名称
+    pub fn get_env_var(&self, env_name: &str) -> String {
+        self.env_var.clone().unwrap_or_else(|| {
+            format!("ENV_{}_{}", env_name, self.name)
+                .to_uppercase()
+                .replace(['-', '.', ' '], "_")
+        })
+    }
+
+    /// 从环境变量读取密码, 用于非交互安装, 未设置或为空时返回 None
+    pub fn get_env_password(&self, env_name: &str) -> Option<(String, String)> {
+        if self.type_ != "password" {
+            return None;
+        }
+        let key = self.get_env_var(env_name);
+        std::env::var(&key)
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(|value| (key, value))
+    }
+}
+
+/// 切换版本时可执行文件目录在 PATH 中的位置
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathStrategy {
+    /// 放在最前面, 优先于系统自带的版本
+    #[default]
+    Prepend,
+    /// 放在最后面, 系统自带的版本优先
+    Append,
+}
+
+/// 安装目录中版本目录的布局
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// `<安装目录>/<name>/<name>-<version>`
+    #[default]
+    NameVersion,
+    /// `<安装目录>/<name>/<version>`
+    Version,
+}
+
+impl Layout {
+    /// 版本目录, 安装、检测是否已安装及重新扫描都由此得到路径
+    pub fn version_dir(&self, install_dir: &Path, name: &str, version: &str) -> PathBuf {
+        install_dir.join(name).join(self.dir_name(name, version))
+    }
+
+    /// 版本目录的名称, 名称部分使用小写
+    pub fn dir_name(&self, name: &str, version: &str) -> String {
+        match self {
+            Layout::NameVersion => format!("{}-{}", name.to_lowercase(), version),
+            Layout::Version => version.to_string(),
+        }
+    }
+
+    /// 从版本目录的名称中解析版本号, 不是该布局的目录返回 None
+    pub fn parse_dir_name(&self, name: &str, dir_name: &str) -> Option<String> {
+        match self {
+            Layout::NameVersion => parse_version_dir_name(name, dir_name),
+            // 另一种布局的目录不是版本目录
+            Layout::Version => (!dir_name.is_empty()
+                && parse_version_dir_name(name, dir_name).is_none())
+            .then(|| dir_name.to_string()),
+        }
+    }
+
+    /// 安装过程使用的目录, 存放临时解压目录及安装锁, 不在任何环境目录下,
+    /// 因此重新扫描版本目录时不会遇到
+    pub fn work_dir(&self, install_dir: &Path) -> PathBuf {
+        install_dir.join(".env-work")
+    }
+
+    /// 版本的临时解压目录, 每个版本独立, 同时安装不同版本时互不影响
+    pub fn temp_dir(&self, install_dir: &Path, name: &str, version: &str) -> PathBuf {
+        self.work_dir(install_dir)
+            .join(format!("{}-{}.tmp", name.to_lowercase(), version))
+    }
+
+    /// 同一版本安装锁的文件位置
+    pub fn lock_path(&self, install_dir: &Path, name: &str, version: &str) -> PathBuf {
+        self.work_dir(install_dir)
+            .join(format!("{}-{}.lock", name.to_lowercase(), version))
+    }
+}
+
+/// 版本的发布通道
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize, ValueEnum, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// 正式版本
+    #[default]
+    Stable,
+    /// 长期支持版本
+    Lts,
+    /// 测试版本
+    Beta,
+}
+
+impl Channel {
+    /// 选择 `channel` 通道时是否列出该通道的版本: lts 只列出长期支持版本,
+    /// stable 列出除测试版以外的版本, beta 列出全部版本
+    pub fn matches(&self, channel: Channel) -> bool {
+        match channel {
+            Channel::Stable => *self != Channel::Beta,
+            Channel::Lts => *self == Channel::Lts,
+            Channel::Beta => true,
+        }
+    }
+
+    /// 版本列表中的标注
+    pub fn label(&self) -> &'static str {
+        match self {
+            Channel::Stable => "",
+            Channel::Lts => "LTS",
+            Channel::Beta => "测试版",
+        }
+    }
+}
+
+impl std::fmt::Display for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Channel::Stable => "stable",
+            Channel::Lts => "lts",
+            Channel::Beta => "beta",
+        };
+        f.write_str(name)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -110,67 +518,456 @@ impl EnvConfig {
             .cloned()
     }
 
-    pub fn switch_version(name: &str, version: &str, install_dir: &PathBuf) -> Result<()> {
+    /// 按名称查找环境, 找不到时尝试将名称解析为内置环境后再查找
+    pub fn find_environment(&self, name: &str) -> Option<Environment> {
+        self.get_enviroment(name).or_else(|| {
+            let env = ChooseEnvironment::from_str(name, true).ok()?;
+            self.get_enviroment(env.get_name())
+        })
+    }
+
+    pub fn switch_version(name: &str, version: &str, install_dir: &Path) -> Result<()> {
         let install_dir = install_dir.to_str().unwrap();
 
-        let mut config = ENV_CONFIG.clone();
-        let new_installed = InstalledEnvironment::new(name, version, install_dir);
+        EnvConfig::update(|config| {
+            config.record_switch(name, version, install_dir);
+            write_active_file(config)
+        })?;
 
-        if let Some(installed) = &mut config.installed {
-            if let Some(env) = installed
-                .iter_mut()
-                .find(|env| env.name.to_lowercase() == name.to_lowercase())
-            {
-                env.current_version = Some(version.to_string());
-                env.home_dir = Some(install_dir.to_string());
-                // 去重
+        Ok(())
+    }
+
+    /// 登记安装目录之外已有的安装(如系统中的 JDK)并切换到该版本
+    pub fn adopt_version(name: &str, version: &str, home_dir: &Path) -> Result<()> {
+        let home_dir = home_dir.to_str().unwrap();
+
+        EnvConfig::update(|config| {
+            config.record_adopt(name, version, home_dir);
+            write_active_file(config)
+        })?;
+
+        Ok(())
+    }
+
+    /// 在配置中记录登记的外部安装, 之后切换到该版本时使用登记的目录
+    pub fn record_adopt(&mut self, name: &str, version: &str, home_dir: &str) {
+        self.record_switch(name, version, home_dir);
+        if let Some(env) = self
+            .installed
+            .iter_mut()
+            .flatten()
+            .find(|env| env.name.to_lowercase() == name.to_lowercase())
+        {
+            env.external_homes
+                .insert(version.to_string(), home_dir.to_string());
+        }
+    }
+
+    /// 版本登记在安装目录之外时返回登记的主目录
+    pub fn external_home(&self, name: &str, version: &str) -> Option<PathBuf> {
+        self.get_installed(name)?
+            .external_homes
+            .get(version)
+            .map(PathBuf::from)
+    }
+
+    /// 在配置中记录切换到的版本, 环境尚未安装时新增一条记录
+    pub fn record_switch(&mut self, name: &str, version: &str, install_dir: &str) {
+        let installed = self.installed.get_or_insert_with(Vec::new);
+        match installed
+            .iter_mut()
+            .find(|env| env.name.to_lowercase() == name.to_lowercase())
+        {
+            Some(env) => env.switch_to(version, install_dir),
+            None => installed.push(InstalledEnvironment::new(name, version, install_dir)),
+        }
+    }
+
+    /// 配置的默认版本, 是别名时返回指向的版本
+    pub fn get_default_version(&self, name: &str) -> Option<String> {
+        self.default_versions
+            .iter()
+            .find(|(env, _)| env.to_lowercase() == name.to_lowercase())
+            .map(|(_, version)| self.resolve_alias(name, version))
+    }
+
+    /// 环境的版本别名
+    pub fn get_aliases(&self, name: &str) -> BTreeMap<String, String> {
+        self.aliases
+            .iter()
+            .filter(|(env, _)| env.to_lowercase() == name.to_lowercase())
+            .flat_map(|(_, aliases)| aliases.clone())
+            .collect()
+    }
+
+    /// 参数是别名时返回指向的版本, 否则原样返回
+    pub fn resolve_alias(&self, name: &str, version: &str) -> String {
+        self.get_aliases(name)
+            .remove(version)
+            .unwrap_or_else(|| version.to_string())
+    }
+
+    /// 设置版本别名, 别名不能是版本号, 避免与真实版本混淆
+    pub fn set_alias(&mut self, name: &str, alias: &str, version: &str) -> Result<()> {
+        if alias.trim().is_empty() || version::Version::parse(alias).is_some() || alias == "latest"
+        {
+            return Err(anyhow!("别名不能为空、版本号或 latest: {}", alias));
+        }
+        self.remove_alias(name, alias);
+        self.aliases
+            .entry(name.to_string())
+            .or_default()
+            .insert(alias.to_string(), version.to_string());
+        Ok(())
+    }
+
+    /// 删除版本别名, 返回删除前指向的版本
+    pub fn remove_alias(&mut self, name: &str, alias: &str) -> Option<String> {
+        let mut removed = None;
+        for (env, aliases) in self.aliases.iter_mut() {
+            if env.to_lowercase() == name.to_lowercase() {
+                removed = removed.or(aliases.remove(alias));
+            }
+        }
+        self.aliases.retain(|_, aliases| !aliases.is_empty());
+        removed
+    }
+
+    /// 在配置中登记已安装的版本, 不修改当前版本
+    pub fn add_installed_version(name: &str, version: &str) -> Result<()> {
+        EnvConfig::update(|config| {
+            config.record_installed(name, version);
+            Ok(())
+        })
+    }
+
+    pub fn record_installed(&mut self, name: &str, version: &str) {
+        let installed = self.installed.get_or_insert_with(Vec::new);
+        match installed
+            .iter_mut()
+            .find(|env| env.name.to_lowercase() == name.to_lowercase())
+        {
+            Some(env) => {
                 env.installed_versions.push(version.to_string());
                 env.installed_versions = deduplicate(&env.installed_versions);
-            } else {
-                installed.push(new_installed);
             }
-        } else {
-            config.installed = Some(vec![new_installed]);
+            None => installed.push(InstalledEnvironment {
+                name: name.to_string(),
+                current_version: None,
+                previous_version: None,
+                home_dir: None,
+                external_homes: BTreeMap::new(),
+                installed_versions: vec![version.to_string()],
+                databases: BTreeMap::new(),
+                last_args: HashMap::new(),
+            }),
         }
+    }
 
-        EnvConfig::save(&config)?;
+    /// 从配置中移除已卸载的版本
+    pub fn uninstall_version(name: &str, version: &str) -> Result<()> {
+        EnvConfig::update(|config| {
+            config.record_uninstall(name, version);
+            Ok(())
+        })
+    }
 
-        Ok(())
+    pub fn record_uninstall(&mut self, name: &str, version: &str) {
+        if let Some(env) = self
+            .installed
+            .iter_mut()
+            .flatten()
+            .find(|env| env.name.to_lowercase() == name.to_lowercase())
+        {
+            env.remove_version(version);
+        }
     }
 
-    pub fn get_current_version(&self, name: &str) -> Option<String> {
-        if let Some(installed) = &self.installed {
-            let env = installed
-                .iter()
+    /// 记录本次安装选择的参数, 环境未登记时忽略
+    pub fn save_last_args(env: &Environment, args: &Value) -> Result<()> {
+        EnvConfig::update(|config| {
+            config.record_last_args(env, args);
+            Ok(())
+        })
+    }
+
+    pub fn record_last_args(&mut self, env: &Environment, args: &Value) {
+        let Some(installed) = self
+            .installed
+            .iter_mut()
+            .flatten()
+            .find(|e| e.name.to_lowercase() == env.name.to_lowercase())
+        else {
+            return;
+        };
+
+        // 密码不写入配置文件
+        installed.last_args = args
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| {
+                env.args
+                    .iter()
+                    .any(|arg| &arg.name == *key && arg.type_ != "password")
+            })
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+    }
+
+    /// 获取上一次安装时选择的参数值
+    pub fn get_last_arg(&self, name: &str, arg: &str) -> Option<&Value> {
+        self.get_installed(name)?.last_args.get(arg)
+    }
+
+    /// 记录数据库版本初始化时使用的配置
+    pub fn set_database_config(name: &str, version: &str, database: DatabaseConfig) -> Result<()> {
+        EnvConfig::update(|config| {
+            let env = config
+                .installed
+                .iter_mut()
+                .flatten()
                 .find(|env| env.name.to_lowercase() == name.to_lowercase())
-                .unwrap();
-            return env.current_version.clone();
+                .ok_or_else(|| anyhow!("{} 尚未安装", name))?;
+            env.databases.insert(version.to_string(), database);
+            Ok(())
+        })
+    }
+
+    /// 数据库环境各版本记录的配置
+    pub fn get_database_configs(&self, name: &str) -> BTreeMap<String, DatabaseConfig> {
+        self.installed
+            .iter()
+            .flatten()
+            .find(|env| env.name.to_lowercase() == name.to_lowercase())
+            .map(|env| env.databases.clone())
+            .unwrap_or_default()
+    }
+
+    /// 导出自定义环境及已安装的版本
+    pub fn to_portable(&self) -> Result<PortableConfig> {
+        let default_config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG)?;
+
+        let environments = self
+            .environments
+            .iter()
+            .filter(|env| default_config.get_enviroment(&env.name).is_none())
+            .cloned()
+            .collect();
+
+        let installed = self
+            .installed
+            .iter()
+            .flatten()
+            .map(|env| InstalledEnvironment {
+                home_dir: None,
+                external_homes: BTreeMap::new(),
+                databases: BTreeMap::new(),
+                ..env.clone()
+            })
+            .collect();
+
+        Ok(PortableConfig {
+            environments,
+            installed,
+        })
+    }
+
+    /// 合并导入的自定义环境, 同名环境以导入的为准
+    pub fn merge_environments(&mut self, portable: &PortableConfig) {
+        for env in &portable.environments {
+            match self
+                .environments
+                .iter_mut()
+                .find(|e| e.name.to_lowercase() == env.name.to_lowercase())
+            {
+                Some(existing) => *existing = env.clone(),
+                None => self.environments.push(env.clone()),
+            }
         }
-        None
     }
 
-    pub fn get_install_versions(&self, name: &str) -> Vec<String> {
-        if let Some(installed) = &self.installed {
-            let env = installed
+    /// 获取导入配置中本机尚未安装的版本, 当前版本排在最后以便最终切换到它
+    pub fn get_missing_versions(&self, portable: &PortableConfig) -> Vec<(String, String)> {
+        let mut missing = vec![];
+        for env in &portable.installed {
+            let installed = self.get_install_versions(&env.name);
+            let mut versions: Vec<&String> = env
+                .installed_versions
                 .iter()
-                .find(|env| env.name.to_lowercase() == name.to_lowercase())
-                .unwrap();
-            return env.installed_versions.clone();
+                .filter(|v| !installed.contains(v))
+                .collect();
+            versions.sort_by_key(|v| Some(*v) == env.current_version.as_ref());
+
+            missing.extend(versions.into_iter().map(|v| (env.name.clone(), v.clone())));
         }
-        vec![]
+        missing
+    }
+
+    /// 获取项目固定的版本
+    pub fn get_pinned_version(&self, name: &str) -> Option<String> {
+        self.get_pinned_environments()
+            .into_iter()
+            .find(|(env, _)| env.name.to_lowercase() == name.to_lowercase())
+            .map(|(_, version)| version)
+    }
+
+    /// 获取项目固定了版本的所有环境, 按环境名称排序
+    pub fn get_pinned_environments(&self) -> Vec<(Environment, String)> {
+        let mut pinned: Vec<(Environment, String)> = self
+            .pinned
+            .iter()
+            .filter_map(|(name, version)| Some((self.find_environment(name)?, version.clone())))
+            .collect();
+        pinned.sort_by(|a, b| a.0.name.cmp(&b.0.name));
+        pinned
+    }
+
+    /// 获取环境使用的架构名称, 未配置映射时使用统一化后的名称
+    pub fn map_arch(&self, name: &str, arch: &str) -> String {
+        self.arch_mapping
+            .iter()
+            .find(|(env, _)| env.to_lowercase() == name.to_lowercase())
+            .and_then(|(_, mapping)| mapping.get(arch))
+            .cloned()
+            .unwrap_or_else(|| arch.to_string())
+    }
+
+    pub fn get_installed(&self, name: &str) -> Option<&InstalledEnvironment> {
+        self.installed
+            .as_ref()?
+            .iter()
+            .find(|env| env.name.to_lowercase() == name.to_lowercase())
+    }
+
+    pub fn get_current_version(&self, name: &str) -> Option<String> {
+        self.get_installed(name)?.current_version.clone()
+    }
+
+    pub fn get_previous_version(&self, name: &str) -> Option<String> {
+        self.get_installed(name)?.previous_version.clone()
+    }
+
+    /// 已安装的版本, 按版本号从新到旧排序
+    pub fn get_install_versions(&self, name: &str) -> Vec<String> {
+        let mut versions = self
+            .get_installed(name)
+            .map(|env| env.installed_versions.clone())
+            .unwrap_or_default();
+        version::sort_newest_first(&mut versions);
+        versions
     }
 
     pub fn save(config: &EnvConfig) -> Result<()> {
-        let config = serde_json::to_string_pretty(config)?;
+        let _lock = lock_config()?;
+
+        write_config_atomic(&get_home_config_path(), config)?;
+        *ENV_CONFIG.write().unwrap() = Some(config.clone());
+        Ok(())
+    }
+
+    /// 加锁后重新读取磁盘上的全局配置, 修改后原子写回,
+    /// 避免多个 env 进程同时运行时互相覆盖对方的修改
+    pub fn update(f: impl FnOnce(&mut EnvConfig) -> Result<()>) -> Result<()> {
+        let _lock = lock_config()?;
+
+        let path = get_home_config_path();
+        let mut config: EnvConfig = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        f(&mut config)?;
+        write_config_atomic(&path, &config)?;
 
-        fs::write(get_home_config_path(), config)?;
+        // 缓存中合并了项目配置, 下次使用时重新加载
+        *ENV_CONFIG.write().unwrap() = None;
         Ok(())
     }
 
+    /// 读取顶层配置项, 未设置或不存在时返回错误
+    pub fn get_value(&self, key: &str) -> Result<Value> {
+        serde_json::to_value(self)?
+            .get(key)
+            .cloned()
+            .ok_or_else(|| anyhow!("配置项 {} 不存在或未设置", key))
+    }
+
+    /// 修改顶层配置项, 值按 JSON 解析, 无法解析时作为字符串, 返回修改后的值.
+    /// 通过序列化后能否读回该字段判断配置项是否存在, 设置为 null 即取消可选的配置项
+    pub fn set_value(&mut self, key: &str, raw: &str) -> Result<Value> {
+        if PROTECTED_KEYS.contains(&key) {
+            return Err(anyhow!("{} 不能直接修改, 请使用对应的命令", key));
+        }
+
+        let mut object = serde_json::to_value(&*self)?;
+        let existed = object.get(key).is_some();
+        let fields = object
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("配置格式错误"))?;
+        let mut with_value = |value: &Value| {
+            fields.insert(key.to_string(), value.clone());
+            serde_json::from_value::<EnvConfig>(Value::Object(fields.clone()))
+        };
+
+        // 先按 JSON 解析, 字段类型不匹配时再作为字符串, 如 `install_path 2024`
+        let raw_string = Value::String(raw.to_string());
+        let parsed = serde_json::from_str::<Value>(raw).unwrap_or_else(|_| raw_string.clone());
+        let result = match with_value(&parsed) {
+            Err(_) if !parsed.is_string() && !parsed.is_null() => {
+                with_value(&raw_string).map(|updated| (raw_string, updated))
+            }
+            result => result.map(|updated| (parsed, updated)),
+        };
+        let (value, updated) =
+            result.map_err(|e| EnvError::ConfigInvalid(format!("{} 的值无效: {}", key, e)))?;
+        let written = serde_json::to_value(&updated)?.get(key).cloned();
+        match written {
+            Some(written) => {
+                updated.validate()?;
+                *self = updated;
+                Ok(written)
+            }
+            None if value.is_null() && existed => {
+                *self = updated;
+                Ok(Value::Null)
+            }
+            None => Err(anyhow!("未知的配置项: {}", key)),
+        }
+    }
+
+    /// 获取缓存的配置, 首次调用时从磁盘加载
+    pub fn cached() -> Result<EnvConfig> {
+        if let Some(config) = ENV_CONFIG.read().unwrap().as_ref() {
+            return Ok(config.clone());
+        }
+
+        let config = EnvConfig::load_deserialize()?;
+        *ENV_CONFIG.write().unwrap() = Some(config.clone());
+        Ok(config)
+    }
+
+    /// 获取缓存的配置, 加载失败时附带说明返回错误
+    pub fn current() -> Result<EnvConfig> {
+        EnvConfig::cached().context("加载环境配置失败")
+    }
+
     pub fn init() -> Result<()> {
         let home_config_path = get_home_config_path();
 
+        // 配置目录变化后, 迁移旧的 ~/.dev_env 配置
+        let legacy_dir = get_env_home_dir();
+        let legacy_config_path = legacy_dir.join(CONFIG_FILE_NAME);
+        if migrate_config(&legacy_config_path, &home_config_path)? {
+            status!(
+                "已将配置从 {} 迁移到 {}",
+                legacy_config_path.display(),
+                home_config_path.display()
+            );
+            pin_legacy_install_path(&home_config_path, &legacy_dir)?;
+        }
+
         if home_config_path.exists() {
+            let _lock = lock_config()?;
+            if pin_legacy_lang(&home_config_path, locale_lang())? {
+                status!("{}", t!("config.lang_kept"));
+            }
             return Ok(());
         }
 
@@ -178,12 +975,22 @@ impl EnvConfig {
 
         let mut default_env_config: EnvConfig = serde_json::from_str(default_env_config_str)?;
 
-        // 设置默认安装目录
-        if default_env_config.install_path.is_empty() {
-            default_env_config.install_path = get_env_home_dir().to_str().unwrap().to_string();
+        // 新配置记录当前 locale 对应的语言, 没有 lang 字段的配置都来自支持多语言之前的版本
+        default_env_config.lang = Some(locale_lang());
+
+        // 设置默认安装目录, 旧目录下已有安装时继续使用, 否则 Linux 下使用 XDG 数据目录
+        if let Some(install_path) = default_install_path(
+            &default_env_config.install_path,
+            &legacy_dir,
+            &get_env_data_dir(),
+        ) {
+            default_env_config.install_path = install_path.to_str().unwrap().to_string();
         }
 
         // 写入配置
+        if let Some(parent) = home_config_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
         serde_json::to_writer_pretty(File::create(home_config_path)?, &default_env_config)?;
         Ok(())
     }
@@ -194,29 +1001,90 @@ impl EnvConfig {
         // 初始化home配置文件
         EnvConfig::init()?;
 
-        let home_config = config::File::with_name(home_config.as_os_str().to_str().unwrap());
-
-        let local_config = ".env.config.json";
-
-        let local_exist = config_exist(local_config);
-
-        let local_config = config::File::with_name(&local_config);
-
-        let mut setting = Config::builder().add_source(home_config);
+        let local_config = Path::new(CONFIG_FILE_NAME);
+        let mut files = vec![home_config.as_path()];
+        if config_exist(CONFIG_FILE_NAME) {
+            files.push(local_config);
+        }
+        EnvConfig::load_files(&files)
+    }
 
-        if local_exist {
-            setting = setting.add_source(local_config);
+    /// 依次加载配置文件, 后面的文件覆盖前面的同名字段
+    fn load_files(files: &[&Path]) -> Result<Config> {
+        let mut setting = Config::builder();
+        for file in files {
+            setting = setting.add_source(config::File::from(*file));
         }
-        let setting = setting.build()?;
-        Ok(setting)
+        Ok(setting.build()?)
     }
     /// 加载配置
     pub fn load_deserialize() -> Result<EnvConfig> {
         let setting = EnvConfig::load()?;
 
         let setting = setting.try_deserialize::<EnvConfig>()?;
+        setting.validate()?;
         Ok(setting)
     }
+
+    /// 校验配置内容, 一次性报告所有问题
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = vec![];
+        let mut names = vec![];
+
+        for env in &self.environments {
+            let name = env.name.as_str();
+            if name.trim().is_empty() {
+                problems.push("存在名称为空的环境".to_string());
+            } else if names.contains(&name.to_lowercase()) {
+                problems.push(format!("{}: 环境名称重复", name));
+            }
+            names.push(name.to_lowercase());
+
+            let templates = env.repository.templates();
+            if templates.is_empty() || templates.iter().any(|t| t.trim().is_empty()) {
+                problems.push(format!("{}: repository 不能为空", name));
+            }
+
+            for arg in &env.args {
+                let arg_type = arg.type_.as_str();
+                if !ARG_TYPES.contains(&arg_type) {
+                    problems.push(format!(
+                        "{}: 参数 {} 的类型 {} 无效, 可选值: {}",
+                        name,
+                        arg.name,
+                        arg_type,
+                        ARG_TYPES.join(", ")
+                    ));
+                }
+                if matches!(arg_type, "select" | "multi-select") && arg.options.is_empty() {
+                    problems.push(format!("{}: 参数 {} 缺少 options", name, arg.name));
+                }
+            }
+        }
+
+        for name in self.pinned.keys() {
+            if self.find_environment(name).is_none() {
+                problems.push(format!("pinned: 未找到环境 {}", name));
+            }
+        }
+
+        for name in self.aliases.keys() {
+            if self.find_environment(name).is_none() {
+                problems.push(format!("aliases: 未找到环境 {}", name));
+            }
+        }
+
+        for name in self.default_versions.keys() {
+            if self.find_environment(name).is_none() {
+                problems.push(format!("default_versions: 未找到环境 {}", name));
+            }
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(EnvError::ConfigInvalid(problems.join("\n  - ")).into())
+    }
 }
 
 pub fn config_exist(filename: &str) -> bool {
@@ -225,20 +1093,377 @@ pub fn config_exist(filename: &str) -> bool {
 }
 
 pub fn get_home_config_path() -> PathBuf {
-    PathBuf::from(get_env_home_dir()).join(".env.config.json")
+    get_env_config_dir().join(CONFIG_FILE_NAME)
+}
+
+/// 将旧位置的配置文件移动到新位置, 新位置已有配置时不迁移, 返回是否发生了迁移
+pub fn migrate_config(legacy: &Path, target: &Path) -> Result<bool> {
+    if legacy == target || !legacy.is_file() || target.exists() {
+        return Ok(false);
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // 跨文件系统时 rename 会失败, 退化为复制后删除
+    if fs::rename(legacy, target).is_err() {
+        fs::copy(legacy, target)?;
+        fs::remove_file(legacy)?;
+    }
+    Ok(true)
+}
+
+/// 配置的安装目录不是本机可用的绝对路径(为空或是默认配置中的 Windows 路径)时返回应使用的目录:
+/// 旧的 ~/.dev_env 下已有安装的环境时继续使用旧目录, 使这些安装仍被识别, 否则使用数据目录
+fn default_install_path(configured: &str, legacy_dir: &Path, data_dir: &Path) -> Option<PathBuf> {
+    if !configured.is_empty() && Path::new(configured).is_absolute() {
+        return None;
+    }
+    if has_installs(legacy_dir) {
+        return Some(legacy_dir.to_path_buf());
+    }
+    Some(data_dir.to_path_buf())
+}
+
+/// 目录下是否有环境的安装目录, 隐藏的文件及目录(锁文件、配置等)不算
+fn has_installs(dir: &Path) -> bool {
+    let std::result::Result::Ok(entries) = fs::read_dir(dir) else {
+        return false;
+    };
+    entries
+        .flatten()
+        .any(|entry| entry.path().is_dir() && !entry.file_name().to_string_lossy().starts_with('.'))
 }
 
+/// 迁移后的配置中安装目录在本机不可用时改为实际的安装位置, 避免旧目录下的安装被遗漏
+fn pin_legacy_install_path(path: &Path, legacy_dir: &Path) -> Result<()> {
+    // 配置格式错误时留给加载配置时报告
+    let std::result::Result::Ok(mut config) =
+        serde_json::from_str::<EnvConfig>(&fs::read_to_string(path)?)
+    else {
+        return Ok(());
+    };
+    let Some(install_path) =
+        default_install_path(&config.install_path, legacy_dir, &get_env_data_dir())
+    else {
+        return Ok(());
+    };
+
+    config.install_path = install_path.to_str().unwrap().to_string();
+    write_config_atomic(path, &config)
+}
+
+/// 当前使用的版本快照, 供编辑器及构建脚本读取
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ActiveVersion {
+    pub version: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub home: Option<String>,
+}
+
+/// 当前版本快照文件的位置
+pub fn get_active_file_path() -> PathBuf {
+    get_env_config_dir().join(ACTIVE_FILE_NAME)
+}
+
+/// 各环境当前使用的版本, 按环境名称排序
+pub fn active_versions(config: &EnvConfig) -> BTreeMap<String, ActiveVersion> {
+    config
+        .installed
+        .iter()
+        .flatten()
+        .filter_map(|env| {
+            let version = env.current_version.clone()?;
+            Some((
+                env.name.to_lowercase(),
+                ActiveVersion {
+                    version,
+                    home: env.home_dir.clone(),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// 重新生成当前版本快照文件, 每次切换版本后调用
+pub fn write_active_file(config: &EnvConfig) -> Result<()> {
+    let path = get_active_file_path();
+    let content = serde_json::to_string_pretty(&active_versions(config))?;
+    let temp_path = path.with_extension(format!("tmp.{}", std::process::id()));
+
+    fs::write(&temp_path, content)?;
+    fs::rename(&temp_path, &path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })?;
+    Ok(())
+}
+
+/// 刷新配置前的备份文件位置
+pub fn get_backup_config_path() -> PathBuf {
+    get_home_config_path().with_extension("json.bak")
+}
+
+/// 用默认配置刷新全局配置, 修改前列出将要发生的变化并确认, 原配置备份到 `.env.config.json.bak`
 pub fn flush_env_config() -> anyhow::Result<()> {
-    // 初始化home配置文件
-    let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG)?;
+    let path = get_home_config_path();
+    let before: EnvConfig = serde_json::from_str(&fs::read_to_string(&path)?)?;
+    let mut after = before.clone();
+    refresh_env_config(&mut after)?;
+
+    let changes = describe_changes(&before, &after);
+    if changes.is_empty() {
+        status!("配置已是最新, 无需刷新");
+        return Ok(());
+    }
+    status!("刷新配置将进行以下修改:");
+    for change in &changes {
+        status!("  - {}", change);
+    }
+
+    if is_dry_run() {
+        status!(
+            "[dry-run] 备份配置到 {}",
+            get_backup_config_path().display()
+        );
+        return Ok(());
+    }
+
+    if show_status() && !is_assume_yes() {
+        let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("确认刷新配置?")
+            .default(false)
+            .interact_opt()
+            .map_err(prompt_error)?
+            .ok_or_else(cancelled)?;
+        if !confirmed {
+            return Err(cancelled());
+        }
+    }
+
+    let backup_path = get_backup_config_path();
+    fs::copy(&path, &backup_path)?;
+    status!("已备份原配置到 {}", backup_path.display());
+
+    EnvConfig::update(|config| {
+        refresh_env_config(config)?;
+        write_active_file(config)
+    })
+}
+
+/// 从刷新前的备份恢复全局配置
+pub fn restore_env_config() -> Result<()> {
+    let backup_path = get_backup_config_path();
+    let content = fs::read_to_string(&backup_path)
+        .map_err(|e| anyhow!("无法读取备份配置 {}: {}", backup_path.display(), e))?;
+    let config: EnvConfig = serde_json::from_str(&content)
+        .map_err(|e| anyhow!("备份配置格式错误 {}: {}", backup_path.display(), e))?;
 
-    find_all_installed_version(&mut config)?;
+    if is_dry_run() {
+        status!("[dry-run] 从 {} 恢复配置", backup_path.display());
+        return Ok(());
+    }
 
     EnvConfig::save(&config)?;
+    status!("已从 {} 恢复配置", backup_path.display());
+    Ok(())
+}
+
+/// 列出刷新配置前后的差异, 用于确认前展示
+pub fn describe_changes(before: &EnvConfig, after: &EnvConfig) -> Vec<String> {
+    let mut changes = vec![];
+
+    for env in &after.environments {
+        match before.get_enviroment(&env.name) {
+            None => changes.push(format!("新增环境: {}", env.name)),
+            Some(old) => {
+                if serde_json::to_value(&old).ok() != serde_json::to_value(env).ok() {
+                    changes.push(format!("环境 {} 的定义恢复为默认配置", env.name));
+                }
+            }
+        }
+    }
+
+    let installed = |config: &EnvConfig| -> Vec<(String, String)> {
+        config
+            .installed
+            .iter()
+            .flatten()
+            .flat_map(|env| {
+                env.installed_versions
+                    .iter()
+                    .map(|v| (env.name.to_lowercase(), v.clone()))
+            })
+            .collect()
+    };
+    let (old_versions, new_versions) = (installed(before), installed(after));
+    for (name, version) in &old_versions {
+        if !new_versions.contains(&(name.clone(), version.clone())) {
+            changes.push(format!("移除已安装记录: {} {}", name, version));
+        }
+    }
+    for (name, version) in &new_versions {
+        if !old_versions.contains(&(name.clone(), version.clone())) {
+            changes.push(format!("登记安装目录中发现的版本: {} {}", name, version));
+        }
+    }
+
+    for env in before.installed.iter().flatten() {
+        let current = after.get_current_version(&env.name);
+        if env.current_version.is_some() && env.current_version != current {
+            changes.push(format!(
+                "清除 {} 的当前版本 {}",
+                env.name,
+                env.current_version.as_deref().unwrap_or_default()
+            ));
+        }
+    }
+
+    changes
+}
+
+/// 获取全局配置的文件锁, 返回的文件被释放时解锁
+fn lock_config() -> Result<File> {
+    let path = get_home_config_path().with_extension("lock");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let file = File::create(&path)?;
+    file.lock()
+        .map_err(|e| anyhow!("无法锁定配置文件 {}: {}", path.display(), e))?;
+    Ok(file)
+}
+
+/// 支持多语言之前的配置没有 `lang` 字段, 这些用户一直看到的是中文输出,
+/// locale 不是中文时写入 `lang: zh`, 避免升级后输出语言突然变成英文, 返回是否修改了配置
+fn pin_legacy_lang(path: &Path, locale: Lang) -> Result<bool> {
+    if locale == Lang::Zh {
+        return Ok(false);
+    }
+    // 配置格式错误时留给加载配置时报告
+    let std::result::Result::Ok(mut config) =
+        serde_json::from_str::<EnvConfig>(&fs::read_to_string(path)?)
+    else {
+        return Ok(false);
+    };
+    if config.lang.is_some() {
+        return Ok(false);
+    }
 
+    config.lang = Some(Lang::Zh);
+    write_config_atomic(path, &config)?;
+    Ok(true)
+}
+
+/// 先写入同目录下的临时文件再重命名, 写入中断时不会破坏原配置
+pub fn write_config_atomic(path: &Path, config: &EnvConfig) -> Result<()> {
+    let content = serde_json::to_string_pretty(config)?;
+    let temp_path = path.with_extension(format!("tmp.{}", std::process::id()));
+
+    fs::write(&temp_path, content)?;
+    fs::rename(&temp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })?;
     Ok(())
 }
 
+/// 用默认配置刷新内置环境并重新扫描安装目录,
+/// 保留用户的安装目录、当前版本以及自定义环境
+pub fn refresh_env_config(config: &mut EnvConfig) -> anyhow::Result<()> {
+    let default_config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG)?;
+
+    for env in default_config.environments {
+        match config
+            .environments
+            .iter_mut()
+            .find(|e| e.name.to_lowercase() == env.name.to_lowercase())
+        {
+            Some(existing) => *existing = env,
+            None => config.environments.push(env),
+        }
+    }
+
+    // 只补充用户没有配置的架构映射
+    for (name, mapping) in default_config.arch_mapping {
+        config.arch_mapping.entry(name).or_insert(mapping);
+    }
+
+    let previous = config.installed.take().unwrap_or_default();
+    find_all_installed_version(config)?;
+
+    let install_path = PathBuf::from(&config.install_path);
+    let installed = config.installed.get_or_insert_with(Vec::new);
+
+    // 重新扫描得到的版本没有当前版本信息, 从原配置中恢复
+    for mut prev in previous {
+        let adopted = is_adopted(&prev, &install_path);
+        // 登记的外部安装不在安装目录中, 重新扫描找不到, 仍然存在的保留
+        prev.external_homes
+            .retain(|_, home| Path::new(home).is_dir());
+        let env = installed
+            .iter_mut()
+            .find(|env| env.name.to_lowercase() == prev.name.to_lowercase());
+
+        match env {
+            Some(env) => {
+                let still_installed = prev
+                    .current_version
+                    .as_ref()
+                    .is_some_and(|v| env.installed_versions.contains(v));
+                if still_installed || adopted {
+                    env.current_version = prev.current_version.clone();
+                    env.home_dir = prev.home_dir.clone();
+                }
+                env.databases = prev.databases.clone();
+                env.last_args = prev.last_args.clone();
+                env.external_homes = prev.external_homes.clone();
+                if adopted {
+                    env.installed_versions.extend(prev.current_version);
+                }
+                env.installed_versions
+                    .extend(prev.external_homes.into_keys());
+                env.installed_versions = deduplicate(&env.installed_versions);
+                env.previous_version = prev
+                    .previous_version
+                    .clone()
+                    .filter(|v| env.installed_versions.contains(v));
+            }
+            None if adopted || !prev.external_homes.is_empty() => {
+                let mut versions: Vec<String> = prev.external_homes.keys().cloned().collect();
+                if adopted {
+                    versions.extend(prev.current_version.clone());
+                }
+                let current_version = prev
+                    .current_version
+                    .clone()
+                    .filter(|v| versions.contains(v));
+                installed.push(InstalledEnvironment {
+                    home_dir: current_version.as_ref().and(prev.home_dir.clone()),
+                    installed_versions: deduplicate(&versions),
+                    previous_version: prev
+                        .previous_version
+                        .clone()
+                        .filter(|v| versions.contains(v)),
+                    current_version,
+                    ..prev
+                });
+            }
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// 当前版本是否是登记的系统已有安装(位于安装目录之外且仍然存在)
+fn is_adopted(env: &InstalledEnvironment, install_path: &Path) -> bool {
+    env.home_dir.as_ref().is_some_and(|home| {
+        let home = Path::new(home);
+        home.exists() && !home.starts_with(install_path)
+    })
+}
+
 pub fn find_all_installed_version(env_config: &mut EnvConfig) -> anyhow::Result<()> {
     let install_dir = PathBuf::from(&env_config.install_path);
 
@@ -260,7 +1485,7 @@ pub fn find_all_installed_version(env_config: &mut EnvConfig) -> anyhow::Result<
         {
             continue;
         }
-        let installed_version: InstalledEnvironment = find_version_from_dir(&dir)?;
+        let installed_version = find_version_from_dir(&dir, env_config.layout)?;
 
         installeds.push(installed_version);
     }
@@ -270,7 +1495,21 @@ pub fn find_all_installed_version(env_config: &mut EnvConfig) -> anyhow::Result<
     Ok(())
 }
 
-pub fn find_version_from_dir(dir: &PathBuf) -> Result<InstalledEnvironment> {
+/// 从 `{name}-{version}` 格式的目录名中解析版本号, 版本号本身可以包含 `-`
+pub fn parse_version_dir_name(name: &str, dir_name: &str) -> Option<String> {
+    let prefix = format!("{}-", name.to_lowercase());
+    if !dir_name.to_lowercase().starts_with(&prefix) {
+        return None;
+    }
+
+    let version = dir_name.get(prefix.len()..)?;
+    if version.is_empty() {
+        return None;
+    }
+    Some(version.to_string())
+}
+
+pub fn find_version_from_dir(dir: &Path, layout: Layout) -> Result<InstalledEnvironment> {
     let name = dir.file_name().unwrap().to_str().unwrap().to_lowercase();
 
     let dirs = fs::read_dir(dir)?;
@@ -279,18 +1518,25 @@ pub fn find_version_from_dir(dir: &PathBuf) -> Result<InstalledEnvironment> {
 
     for dir in dirs {
         let dir = dir?.path();
-        let filename = dir.file_name().unwrap().to_str().unwrap().to_lowercase();
+        // 只识别版本目录, 跳过安装锁等文件
+        if !dir.is_dir() {
+            continue;
+        }
+        let filename = dir.file_name().unwrap().to_str().unwrap();
 
-        if filename.contains(&name) {
-            let version = filename.split("-").nth(1).unwrap();
-            versions.push(version.to_string());
+        if let Some(version) = layout.parse_dir_name(&name, filename) {
+            versions.push(version);
         }
     }
     Ok(InstalledEnvironment {
         name: name.to_string(),
         current_version: None,
+        previous_version: None,
         home_dir: None,
+        external_homes: BTreeMap::new(),
         installed_versions: versions,
+        databases: BTreeMap::new(),
+        last_args: HashMap::new(),
     })
 }
 
@@ -304,4 +1550,707 @@ mod tests {
         let env_config = EnvConfig::load();
         println!("{:?}", env_config);
     }
+
+    #[test]
+    fn test_validate() {
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        assert!(config.validate().is_ok());
+
+        config.environments[0].repository = Repository::from("");
+        config.environments[1].args[0].options.clear();
+        config.environments[2].args[0].type_ = "radio".to_string();
+
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("java: repository 不能为空"));
+        assert!(err.contains("python: 参数 version 缺少 options"));
+        assert!(err.contains("go: 参数 version 的类型 radio 无效"));
+    }
+
+    #[test]
+    fn test_pinned_version() {
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        config
+            .pinned
+            .insert("Node".to_string(), "18.19.0".to_string());
+
+        assert_eq!(
+            config.get_pinned_version("node"),
+            Some("18.19.0".to_string())
+        );
+        assert_eq!(config.get_pinned_version("java"), None);
+        assert!(config.validate().is_ok());
+        // 固定版本只在项目配置中生效, 不会写入全局配置
+        assert!(!serde_json::to_string(&config).unwrap().contains("pinned"));
+
+        config.pinned.insert("ruby".to_string(), "3.3".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_migrate_config() {
+        let dir = std::env::temp_dir().join("env_migrate_test");
+        let _ = fs::remove_dir_all(&dir);
+        let legacy = dir.join(".dev_env").join(CONFIG_FILE_NAME);
+        let target = dir.join(".config").join("env").join(CONFIG_FILE_NAME);
+        fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        fs::write(&legacy, "{}").unwrap();
+
+        assert!(migrate_config(&legacy, &target).unwrap());
+        assert!(!legacy.exists());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "{}");
+
+        // 新位置已有配置时不覆盖
+        fs::write(&legacy, "{\"install_path\": \"old\"}").unwrap();
+        assert!(!migrate_config(&legacy, &target).unwrap());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "{}");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_default_install_path() {
+        let dir = std::env::temp_dir().join("env_default_install_path_test");
+        let _ = fs::remove_dir_all(&dir);
+        let legacy = dir.join(".dev_env");
+        let data = dir.join(".local").join("share").join("env");
+        let custom = dir.join("opt");
+        fs::create_dir_all(&legacy).unwrap();
+        fs::write(legacy.join(".env.config.json.lock"), "").unwrap();
+
+        // 用户设置的绝对路径保持不变
+        assert_eq!(
+            default_install_path(custom.to_str().unwrap(), &legacy, &data),
+            None
+        );
+        // 旧目录下没有安装时使用数据目录
+        assert_eq!(default_install_path("", &legacy, &data), Some(data.clone()));
+
+        // 旧目录下已有安装时继续使用旧目录
+        fs::create_dir_all(legacy.join("java").join("17")).unwrap();
+        assert_eq!(
+            default_install_path("", &legacy, &data),
+            Some(legacy.clone())
+        );
+
+        // 迁移后的配置中安装目录为空时改为旧目录
+        let path = dir.join(CONFIG_FILE_NAME);
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        config.install_path = String::new();
+        write_config_atomic(&path, &config).unwrap();
+        pin_legacy_install_path(&path, &legacy).unwrap();
+        let config: EnvConfig = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(config.install_path, legacy.to_str().unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_config_atomic() {
+        let dir = std::env::temp_dir().join("env_atomic_write_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONFIG_FILE_NAME);
+
+        let config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        write_config_atomic(&path, &config).unwrap();
+
+        let saved: EnvConfig = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.environments.len(), config.environments.len());
+        // 不残留临时文件
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let dir = std::env::temp_dir().join("env_config_round_trip_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONFIG_FILE_NAME);
+
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        config.install_path = "/opt/env".to_string();
+        config.keep_downloads = true;
+        config.cache_dir = Some("/var/cache/env".to_string());
+        config.confirm_size_mb = 0;
+        config.timeout_secs = 5;
+        config.index_url = Some("https://example.com/index.json".to_string());
+        config.resolver_command = Some("resolve --quiet".to_string());
+        config.lang = Some(Lang::En);
+        config.path_strategy = PathStrategy::Append;
+        config.layout = Layout::Version;
+        config.keep_versions = 1;
+        config.arch_mapping.insert(
+            "MyTool".to_string(),
+            HashMap::from([
+                ("x64".to_string(), "AMD64".to_string()),
+                ("aarch64".to_string(), "ARM64".to_string()),
+            ]),
+        );
+        config.aliases.insert(
+            "java".to_string(),
+            HashMap::from([("LTS".to_string(), "17.0.9".to_string())]),
+        );
+        config
+            .default_versions
+            .insert("node".to_string(), "18.19.0".to_string());
+        config.installed = Some(vec![
+            InstalledEnvironment {
+                name: "mysql".to_string(),
+                current_version: Some("8.0.39".to_string()),
+                previous_version: Some("5.7.44".to_string()),
+                home_dir: Some("/opt/env/mysql/mysql-8.0.39".to_string()),
+                external_homes: BTreeMap::from([(
+                    "5.7.44".to_string(),
+                    "/usr/local/mysql".to_string(),
+                )]),
+                installed_versions: vec!["8.0.39".to_string(), "5.7.44".to_string()],
+                databases: BTreeMap::from([(
+                    "8.0.39".to_string(),
+                    DatabaseConfig {
+                        port: "3307".to_string(),
+                        data_dir: "/var/lib/mysql".to_string(),
+                        password_set: true,
+                    },
+                )]),
+                last_args: HashMap::from([
+                    ("Port".to_string(), Value::from("3307")),
+                    ("version".to_string(), Value::from("8.0.39")),
+                ]),
+            },
+            // 版本全部卸载后只剩下上一次安装的参数
+            InstalledEnvironment {
+                name: "go".to_string(),
+                current_version: None,
+                previous_version: None,
+                home_dir: None,
+                external_homes: BTreeMap::new(),
+                installed_versions: vec![],
+                databases: BTreeMap::new(),
+                last_args: HashMap::from([
+                    ("empty".to_string(), Value::from("")),
+                    ("null".to_string(), Value::Null),
+                    ("list".to_string(), serde_json::json!(["a", 1, true])),
+                    (
+                        "nested".to_string(),
+                        serde_json::json!({"Key.With.Dots": 1.5}),
+                    ),
+                ]),
+            },
+        ]);
+        // 自定义环境没有交互参数、可执行文件及环境变量时这些字段不会写入配置
+        let mut custom = config.get_enviroment("maven").unwrap();
+        custom.name = "ant".to_string();
+        custom.args.clear();
+        custom.executable.clear();
+        custom.environment.clear();
+        config.environments.push(custom);
+        config
+            .arch_mapping
+            .insert("empty".to_string(), HashMap::new());
+        config.arch_mapping.insert(
+            "dotted".to_string(),
+            HashMap::from([("x86.64".to_string(), "".to_string())]),
+        );
+
+        write_config_atomic(&path, &config).unwrap();
+        let loaded = EnvConfig::load_files(&[&path])
+            .unwrap()
+            .try_deserialize::<EnvConfig>()
+            .unwrap();
+        loaded.validate().unwrap();
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&config).unwrap()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_portable_config() {
+        let mut source: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        let mut custom = source.get_enviroment("maven").unwrap();
+        custom.name = "ant".to_string();
+        source.environments.push(custom);
+        source.installed = Some(vec![InstalledEnvironment {
+            name: "java".to_string(),
+            current_version: Some("17".to_string()),
+            previous_version: None,
+            home_dir: Some("/opt/java/java-17".to_string()),
+            external_homes: BTreeMap::from([("8".to_string(), "/usr/lib/jvm/8".to_string())]),
+            installed_versions: vec!["17".to_string(), "21".to_string(), "8".to_string()],
+            databases: BTreeMap::new(),
+            last_args: HashMap::new(),
+        }]);
+
+        let portable = source.to_portable().unwrap();
+        assert_eq!(portable.environments.len(), 1);
+        assert_eq!(portable.installed[0].home_dir, None);
+        assert!(portable.installed[0].external_homes.is_empty());
+
+        let mut target: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        target.installed = Some(vec![InstalledEnvironment::new("java", "21", "/opt")]);
+        target.merge_environments(&portable);
+
+        assert!(target.get_enviroment("ant").is_some());
+        assert_eq!(
+            target.get_missing_versions(&portable),
+            vec![
+                ("java".to_string(), "8".to_string()),
+                ("java".to_string(), "17".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_map_arch() {
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        assert_eq!(config.map_arch("go", "x64"), "amd64");
+        assert_eq!(config.map_arch("Go", "aarch64"), "arm64");
+        assert_eq!(config.map_arch("node", "x64"), "x64");
+
+        config.arch_mapping.remove("go");
+        assert_eq!(config.map_arch("go", "x64"), "x64");
+    }
+
+    #[test]
+    fn test_parse_version_dir_name() {
+        assert_eq!(
+            parse_version_dir_name("go", "go-1.22.0-beta1"),
+            Some("1.22.0-beta1".to_string())
+        );
+        assert_eq!(
+            parse_version_dir_name("node", "node-20.10.0-rc.1"),
+            Some("20.10.0-rc.1".to_string())
+        );
+        assert_eq!(
+            parse_version_dir_name("java", "java-17.0.9"),
+            Some("17.0.9".to_string())
+        );
+        assert_eq!(
+            parse_version_dir_name("Java", "java-17.0.9+9-LTS"),
+            Some("17.0.9+9-LTS".to_string())
+        );
+        // 未经重命名的原始 JDK 目录不属于受管理的版本目录
+        assert_eq!(parse_version_dir_name("java", "jdk-17.0.9"), None);
+        assert_eq!(parse_version_dir_name("java", "java-"), None);
+        assert_eq!(parse_version_dir_name("java", "temp"), None);
+    }
+
+    #[test]
+    fn test_layout() {
+        let install_dir = Path::new("/opt/dev");
+        assert_eq!(
+            Layout::NameVersion.version_dir(install_dir, "Java", "17.0.9"),
+            install_dir.join("Java").join("java-17.0.9")
+        );
+        assert_eq!(
+            Layout::Version.version_dir(install_dir, "Java", "17.0.9"),
+            install_dir.join("Java").join("17.0.9")
+        );
+
+        // 每种布局都能解析自己生成的目录名, 不识别另一种布局的目录
+        for layout in [Layout::NameVersion, Layout::Version] {
+            for version in ["17.0.9", "1.22.0-beta1", "stable"] {
+                let dir_name = layout.dir_name("java", version);
+                assert_eq!(
+                    layout.parse_dir_name("java", &dir_name).as_deref(),
+                    Some(version)
+                );
+            }
+        }
+        assert_eq!(Layout::Version.parse_dir_name("java", "java-17.0.9"), None);
+        assert_eq!(Layout::NameVersion.parse_dir_name("java", "17.0.9"), None);
+
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        assert_eq!(config.layout, Layout::NameVersion);
+        config.set_value("layout", "version").unwrap();
+        assert_eq!(config.layout, Layout::Version);
+        assert!(config.set_value("layout", "flat").is_err());
+    }
+
+    #[test]
+    fn test_refresh_keeps_user_settings() {
+        let install_dir = std::env::temp_dir().join("env_refresh_test");
+        let _ = fs::remove_dir_all(&install_dir);
+        fs::create_dir_all(install_dir.join("java").join("java-17")).unwrap();
+        fs::create_dir_all(install_dir.join("java").join("java-21")).unwrap();
+
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        config.install_path = install_dir.to_str().unwrap().to_string();
+        let mut java = InstalledEnvironment::new("java", "17", "/opt/java");
+        java.last_args
+            .insert("version".to_string(), Value::from("17"));
+        config.installed = Some(vec![java]);
+
+        let mut custom = config.get_enviroment("maven").unwrap();
+        custom.name = "ant".to_string();
+        config.environments.push(custom);
+
+        refresh_env_config(&mut config).unwrap();
+
+        assert_eq!(config.install_path, install_dir.to_str().unwrap());
+        assert_eq!(config.get_current_version("java"), Some("17".to_string()));
+        assert!(config.get_enviroment("ant").is_some());
+        assert_eq!(
+            config.get_last_arg("java", "version"),
+            Some(&Value::from("17"))
+        );
+
+        let mut versions = config.get_install_versions("java");
+        versions.sort();
+        assert_eq!(versions, vec!["17".to_string(), "21".to_string()]);
+
+        fs::remove_dir_all(&install_dir).unwrap();
+    }
+
+    #[test]
+    fn test_active_versions() {
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        config.record_switch("Java", "21", "/opt/java/java-21");
+        config.record_installed("go", "1.23.4");
+
+        // 只包含有当前版本的环境
+        let active = active_versions(&config);
+        assert_eq!(active.len(), 1);
+        assert_eq!(
+            active["java"],
+            ActiveVersion {
+                version: "21".to_string(),
+                home: Some("/opt/java/java-21".to_string()),
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&active).unwrap(),
+            r#"{"java":{"version":"21","home":"/opt/java/java-21"}}"#
+        );
+    }
+
+    #[test]
+    fn test_password_args() {
+        let arg: EnvironmentInteractArgs = serde_json::from_value(serde_json::json!({
+            "name": "password",
+            "type": "password",
+            "default": "",
+            "options": [],
+            "description": "root密码"
+        }))
+        .unwrap();
+        assert!(arg.confirm);
+        assert_eq!(arg.get_env_var("mysql"), "ENV_MYSQL_PASSWORD");
+        assert!(!serde_json::to_string(&arg).unwrap().contains("confirm"));
+
+        let arg = EnvironmentInteractArgs {
+            confirm: false,
+            env_var: Some("ENV_TEST_ROOT_PASSWORD".to_string()),
+            ..arg
+        };
+        let json = serde_json::to_value(&arg).unwrap();
+        assert_eq!(json["confirm"], false);
+        assert_eq!(arg.get_env_password("mysql"), None);
+        std::env::set_var("ENV_TEST_ROOT_PASSWORD", "secret");
+        assert_eq!(
+            arg.get_env_password("mysql"),
+            Some(("ENV_TEST_ROOT_PASSWORD".to_string(), "secret".to_string()))
+        );
+        std::env::remove_var("ENV_TEST_ROOT_PASSWORD");
+    }
+
+    #[test]
+    fn test_default_versions() {
+        let mut config: EnvConfig =
+            serde_json::from_str(include_str!("../.env.config.default.json")).unwrap();
+        assert_eq!(config.get_default_version("node"), None);
+
+        config
+            .set_value("default_versions", r#"{"Node": "18.19.0", "java": "lts"}"#)
+            .unwrap();
+        config.set_alias("java", "lts", "17.0.9").unwrap();
+        assert_eq!(
+            config.get_default_version("node").as_deref(),
+            Some("18.19.0")
+        );
+        assert_eq!(
+            config.get_default_version("java").as_deref(),
+            Some("17.0.9")
+        );
+
+        let err = config
+            .set_value("default_versions", r#"{"nodejs": "18.19.0"}"#)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("default_versions: 未找到环境 nodejs"));
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+
+        config.set_alias("Java", "lts", "17.0.9").unwrap();
+        assert_eq!(config.resolve_alias("java", "lts"), "17.0.9");
+        assert_eq!(config.resolve_alias("java", "21"), "21");
+        assert_eq!(config.resolve_alias("node", "lts"), "lts");
+
+        // 重新设置时覆盖原来的版本
+        config.set_alias("java", "lts", "21").unwrap();
+        assert_eq!(config.get_aliases("java").len(), 1);
+        assert_eq!(config.resolve_alias("java", "lts"), "21");
+
+        assert!(config.set_alias("java", "17", "21").is_err());
+        assert!(config.set_alias("java", "latest", "21").is_err());
+        assert!(config.validate().is_ok());
+
+        assert_eq!(config.remove_alias("JAVA", "lts").as_deref(), Some("21"));
+        assert!(config.aliases.is_empty());
+        assert_eq!(config.remove_alias("java", "lts"), None);
+    }
+
+    #[test]
+    fn test_set_value() {
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+
+        assert_eq!(config.set_value("keep_versions", "5").unwrap(), 5);
+        assert_eq!(config.keep_versions, 5);
+        assert_eq!(
+            config.set_value("path_strategy", "append").unwrap(),
+            "append"
+        );
+        assert_eq!(config.path_strategy, PathStrategy::Append);
+        assert_eq!(config.get_value("path_strategy").unwrap(), "append");
+
+        // 可选配置项设置后可以用 null 取消
+        assert!(config.get_value("cache_dir").is_err());
+        config.set_value("cache_dir", "/data/env cache").unwrap();
+        assert_eq!(config.cache_dir.as_deref(), Some("/data/env cache"));
+        assert_eq!(config.set_value("cache_dir", "null").unwrap(), Value::Null);
+        assert_eq!(config.cache_dir, None);
+
+        config.set_value("lang", "en").unwrap();
+        assert_eq!(config.lang, Some(Lang::En));
+        assert!(config.set_value("lang", "fr").is_err());
+
+        // 字符串配置项的值可以是数字等其他 JSON 值的写法
+        assert_eq!(config.set_value("install_path", "2024").unwrap(), "2024");
+        assert_eq!(config.install_path, "2024");
+        assert_eq!(config.set_value("keep_versions", "3").unwrap(), 3);
+
+        assert!(config.set_value("keep_versions", "many").is_err());
+        assert!(config.set_value("no_such_key", "1").is_err());
+        assert!(config.set_value("environments", "[]").is_err());
+        assert_eq!(config.keep_versions, 3);
+    }
+
+    #[test]
+    fn test_describe_changes() {
+        let mut before: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        before.environments.retain(|env| env.name != "kubectl");
+        before.environments[0].repository = Repository::from("https://mirror.example.com/jdk");
+        before.record_switch("go", "1.23.4", "/opt/go/go-1.23.4");
+
+        let mut after: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        after.installed = Some(vec![]);
+
+        let changes = describe_changes(&before, &after);
+        assert!(changes.contains(&"新增环境: kubectl".to_string()));
+        assert!(changes.contains(&"环境 java 的定义恢复为默认配置".to_string()));
+        assert!(changes.contains(&"移除已安装记录: go 1.23.4".to_string()));
+        assert!(changes.contains(&"清除 go 的当前版本 1.23.4".to_string()));
+        assert_eq!(changes.len(), 4);
+
+        assert!(describe_changes(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn test_refresh_keeps_adopted_install() {
+        let install_dir = std::env::temp_dir().join("env_refresh_adopted_test");
+        let system_dir = std::env::temp_dir().join("env_refresh_adopted_system");
+        let _ = fs::remove_dir_all(&install_dir);
+        fs::create_dir_all(install_dir.join("java").join("java-21")).unwrap();
+        fs::create_dir_all(&system_dir).unwrap();
+
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        config.install_path = install_dir.to_str().unwrap().to_string();
+        config.installed = Some(vec![
+            InstalledEnvironment::new("java", "17.0.12", system_dir.to_str().unwrap()),
+            InstalledEnvironment::new("go", "1.23.4", system_dir.to_str().unwrap()),
+        ]);
+
+        refresh_env_config(&mut config).unwrap();
+
+        assert_eq!(
+            config.get_current_version("java"),
+            Some("17.0.12".to_string())
+        );
+        let mut versions = config.get_install_versions("java");
+        versions.sort();
+        assert_eq!(versions, vec!["17.0.12".to_string(), "21".to_string()]);
+        assert_eq!(config.get_current_version("go"), Some("1.23.4".to_string()));
+
+        fs::remove_dir_all(&install_dir).unwrap();
+        fs::remove_dir_all(&system_dir).unwrap();
+    }
+
+    #[test]
+    fn test_pin_legacy_lang() {
+        let dir = std::env::temp_dir().join("env_pin_lang_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONFIG_FILE_NAME);
+        let config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        write_config_atomic(&path, &config).unwrap();
+
+        // 中文 locale 不需要固定
+        assert!(!pin_legacy_lang(&path, Lang::Zh).unwrap());
+
+        // 旧配置在英文 locale 下保持中文, 已配置 lang 时不再修改
+        assert!(pin_legacy_lang(&path, Lang::En).unwrap());
+        let pinned: EnvConfig = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(pinned.lang, Some(Lang::Zh));
+        assert!(!pin_legacy_lang(&path, Lang::En).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_adopted_version_home() {
+        let install_dir = std::env::temp_dir().join("env_adopt_home_test");
+        let system_dir = std::env::temp_dir().join("env_adopt_home_system");
+        let _ = fs::remove_dir_all(&install_dir);
+        let managed_dir = install_dir.join("java").join("java-21");
+        fs::create_dir_all(&managed_dir).unwrap();
+        fs::create_dir_all(&system_dir).unwrap();
+        let (managed, system) = (managed_dir.to_str().unwrap(), system_dir.to_str().unwrap());
+
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        config.install_path = install_dir.to_str().unwrap().to_string();
+        config.record_adopt("java", "17.0.12", system);
+        config.record_switch("java", "21", managed);
+
+        // 切换到其他版本后仍然记得登记的目录
+        let java = config.get_installed("java").unwrap();
+        assert_eq!(java.home_of("17.0.12"), Some(system));
+        assert_eq!(java.home_of("21"), Some(managed));
+        assert_eq!(config.external_home("java", "21"), None);
+
+        // 切换回登记的版本时不使用安装目录中的版本目录
+        config.record_switch("java", "17.0.12", "/opt/env/java/java-17.0.12");
+        assert_eq!(
+            config.get_installed("java").unwrap().home_dir.as_deref(),
+            Some(system)
+        );
+
+        // 重新扫描时保留不是当前版本的登记
+        config.record_switch("java", "21", managed);
+        refresh_env_config(&mut config).unwrap();
+        let mut versions = config.get_install_versions("java");
+        versions.sort();
+        assert_eq!(versions, vec!["17.0.12".to_string(), "21".to_string()]);
+        assert_eq!(
+            config.external_home("java", "17.0.12"),
+            Some(system_dir.clone())
+        );
+
+        config.record_uninstall("java", "17.0.12");
+        assert_eq!(config.external_home("java", "17.0.12"), None);
+
+        fs::remove_dir_all(&install_dir).unwrap();
+        fs::remove_dir_all(&system_dir).unwrap();
+    }
+
+    #[test]
+    fn test_channel() {
+        let config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        let rust = config.get_enviroment("rust").unwrap();
+        let arg = rust.args.iter().find(|arg| arg.name == "version").unwrap();
+
+        let filter = |channel| -> Vec<&String> {
+            arg.options
+                .iter()
+                .filter(|v| arg.get_channel(v).matches(channel))
+                .collect()
+        };
+        assert_eq!(filter(Channel::Stable), vec!["stable"]);
+        assert_eq!(filter(Channel::Beta), vec!["stable", "beta", "nightly"]);
+        assert!(filter(Channel::Lts).is_empty());
+
+        let java = config.get_enviroment("java").unwrap();
+        assert_eq!(java.args[0].get_channel("17"), Channel::Lts);
+        assert_eq!(Channel::Lts.label(), "LTS");
+    }
+
+    #[test]
+    fn test_previous_version() {
+        let mut env = InstalledEnvironment::new("java", "17", "/opt/java/java-17");
+        env.switch_to("21", "/opt/java/java-21");
+        assert_eq!(env.previous_version, Some("17".to_string()));
+
+        // 重复切换到当前版本不覆盖上一个版本
+        env.switch_to("21", "/opt/java/java-21");
+        assert_eq!(env.previous_version, Some("17".to_string()));
+
+        env.switch_to("17", "/opt/java/java-17");
+        assert_eq!(env.current_version, Some("17".to_string()));
+        assert_eq!(env.previous_version, Some("21".to_string()));
+        assert_eq!(env.installed_versions, vec!["17", "21"]);
+    }
+
+    #[test]
+    fn test_record_installed() {
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        config.record_installed("go", "1.23.4");
+        let go = config.get_installed("go").unwrap();
+        assert_eq!(go.current_version, None);
+        assert_eq!(go.installed_versions, vec!["1.23.4"]);
+
+        // 不改变当前版本
+        config.record_switch("java", "17", "/opt/java/java-17");
+        config.record_installed("Java", "21");
+        config.record_installed("java", "21");
+        let java = config.get_installed("java").unwrap();
+        assert_eq!(java.current_version, Some("17".to_string()));
+        assert_eq!(java.installed_versions, vec!["17", "21"]);
+    }
+
+    #[test]
+    fn test_record_last_args() {
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        let mysql = config.get_enviroment("mysql").unwrap();
+        let args = serde_json::json!({ "version": "8.0.40", "port": "3307", "password": "secret" });
+
+        // 尚未安装时不记录
+        config.record_last_args(&mysql, &args);
+        assert_eq!(config.get_last_arg("mysql", "port"), None);
+
+        config.record_switch("mysql", "8.0.40", "/opt/mysql/mysql-8.0.40");
+        config.record_last_args(&mysql, &args);
+        assert_eq!(
+            config.get_last_arg("MySQL", "port"),
+            Some(&Value::from("3307"))
+        );
+        assert_eq!(
+            config.get_last_arg("mysql", "version"),
+            Some(&Value::from("8.0.40"))
+        );
+        // 密码不保存
+        assert_eq!(config.get_last_arg("mysql", "password"), None);
+    }
+
+    #[test]
+    fn test_record_uninstall() {
+        let mut config: EnvConfig = serde_json::from_str(DEFAULT_ENV_CONFIG).unwrap();
+        config.record_switch("java", "17", "/opt/java/java-17");
+        config.record_switch("java", "21", "/opt/java/java-21");
+
+        config.record_uninstall("Java", "17");
+        let java = config.get_installed("java").unwrap();
+        assert_eq!(java.installed_versions, vec!["21"]);
+        assert_eq!(java.previous_version, None);
+        assert_eq!(java.current_version, Some("21".to_string()));
+
+        // 未安装的环境不报错
+        config.record_uninstall("go", "1.23.4");
+    }
 }
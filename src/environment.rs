@@ -1,28 +1,61 @@
 use crate::{
-    env_config::{EnvConfig, Environment, ENV_CONFIG}, install::is_supported_env, path::{set_persistent_env, set_persistent_path}, Result
+    database::apply_database_config,
+    env_config::{EnvConfig, Environment},
+    install::is_supported_env,
+    options::{get_channel, is_dry_run},
+    output::{emit_event, is_human_output},
+    path::{set_persistent_env, set_persistent_path, ProfileSnapshot},
+    search::LATEST_VERSION,
+    status, t, Result,
 };
+use anyhow::anyhow;
+use clap_complete::Shell;
 use colored::Colorize;
 use dialoguer::{theme::ColorfulTheme, Input, MultiSelect, Password, Select};
 use serde_json::Value;
-use std::{collections::HashMap, env, os::windows::process, path::PathBuf};
+use std::{
+    collections::HashMap,
+    env,
+    io::ErrorKind,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
-pub fn get_install_dir(env: &Environment, version: &str) -> PathBuf {
-    let name = env.name.as_str();
-    let install_dir = PathBuf::from(&ENV_CONFIG.install_path);
-    let install_dir = install_dir.join(name);
-    install_dir.join(format!("{}-{}", name, version))
-}
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use crate::{
+    options::is_system,
+    path::{fresh_shell_path, get_profile_file},
+};
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::{ffi::OsStr, fs};
 
-pub fn get_vars(env: &Environment, version: &str) -> HashMap<String, String> {
-    let install_dir = get_install_dir(env, version);
-    // HOME目录
-    let home_dir = install_dir.to_str().unwrap();
-    let mut vars = HashMap::new();
-    vars.insert("INSTALL_DIR".to_string(), home_dir.to_string());
+pub fn get_install_dir(env: &Environment, version: &str) -> Result<PathBuf> {
+    let config = EnvConfig::current()?;
+    Ok(config
+        .layout
+        .version_dir(Path::new(&config.install_path), &env.name, version))
+}
 
-    for (key, val) in env::vars() {
-        vars.insert(key, val);
+/// 版本实际所在的主目录: 登记在安装目录之外的版本使用登记的目录, 否则为安装目录中的版本目录
+pub fn get_version_home(env: &Environment, version: &str) -> Result<PathBuf> {
+    match EnvConfig::current()?.external_home(&env.name, version) {
+        Some(home) => Ok(home),
+        None => get_install_dir(env, version),
     }
+}
+
+pub fn get_vars(env: &Environment, version: &str) -> Result<HashMap<String, String>> {
+    Ok(get_dir_vars(&get_version_home(env, version)?))
+}
+
+/// 以指定目录作为 `%INSTALL_DIR%` 的变量, 同时包含当前进程的环境变量
+pub fn get_dir_vars(home_dir: &Path) -> HashMap<String, String> {
+    let mut vars: HashMap<String, String> = env::vars().collect();
+    vars.insert(
+        "INSTALL_DIR".to_string(),
+        home_dir.to_str().unwrap().to_string(),
+    );
     vars
 }
 
@@ -34,71 +67,461 @@ pub fn handle_vars(val: &str, vars: &HashMap<String, String>) -> String {
     value
 }
 
-pub fn switch_version(env: &Environment, version: &str) -> Result<()> {
-    let name = env.name.as_str();
+/// 解析环境需要设置的环境变量及可执行文件目录
+pub fn resolve_environment(
+    env: &Environment,
+    version: &str,
+) -> Result<(Vec<(String, String)>, PathBuf)> {
+    Ok(resolve_with_vars(env, get_vars(env, version)?))
+}
+
+/// 解析已安装版本的可执行文件目录, 优先使用配置中记录的主目录,
+/// 环境没有配置 `executable` 时返回主目录
+pub fn resolve_bin_dir(
+    env: &Environment,
+    version: &str,
+    home_dir: Option<&str>,
+) -> Result<PathBuf> {
+    Ok(resolve_installed(env, version, home_dir)?.1)
+}
+
+/// 解析已安装版本的环境变量及可执行文件目录, 优先使用配置中记录的主目录
+pub fn resolve_installed(
+    env: &Environment,
+    version: &str,
+    home_dir: Option<&str>,
+) -> Result<(Vec<(String, String)>, PathBuf)> {
+    let home_dir = match home_dir {
+        Some(home_dir) => PathBuf::from(home_dir),
+        None => get_version_home(env, version)?,
+    };
+
+    let (resolved, path) = resolve_with_vars(env, get_dir_vars(&home_dir));
+    if path.as_os_str().is_empty() {
+        Ok((resolved, home_dir))
+    } else {
+        Ok((resolved, path))
+    }
+}
+
+/// 生成只在当前 shell 中生效的环境变量设置命令, 用于 `eval "$(env shellenv java)"`
+pub fn shell_env_script(shell: Shell, vars: &[(String, String)], bin_dir: &Path) -> String {
+    let bin_dir = bin_dir.to_string_lossy();
+    let mut lines: Vec<String> = vars
+        .iter()
+        .map(|(key, value)| match shell {
+            Shell::Fish => format!("set -gx {} {}", key, quote_fish(value)),
+            Shell::PowerShell => format!("$env:{} = {}", key, quote_single(value)),
+            Shell::Elvish => format!("set-env {} {}", key, quote_single(value)),
+            _ => format!("export {}={}", key, quote_posix(value)),
+        })
+        .collect();
 
-    let mut vars = get_vars(env, version);
+    lines.push(match shell {
+        Shell::Fish => format!("set -gx PATH {} $PATH", quote_fish(&bin_dir)),
+        Shell::PowerShell => format!(
+            "$env:PATH = {} + [IO.Path]::PathSeparator + $env:PATH",
+            quote_single(&bin_dir)
+        ),
+        Shell::Elvish => format!("set paths = [{} $@paths]", quote_single(&bin_dir)),
+        _ => format!("export PATH={}:\"$PATH\"", quote_posix(&bin_dir)),
+    });
+    lines.join("\n")
+}
+
+/// sh/bash/zsh 的单引号字符串, 单引号写为 `'\''`
+fn quote_posix(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// fish 的单引号字符串, 反斜杠及单引号需要转义
+fn quote_fish(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
 
-    let environments = &env.environment;
-    let exec = &env.executable;
+/// PowerShell 及 elvish 的单引号字符串, 单引号写为两个单引号
+fn quote_single(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
 
-    // 设置环境变量
-    println!("正在设置环境变量...");
-    for (key, value) in environments {
+fn resolve_with_vars(
+    env: &Environment,
+    mut vars: HashMap<String, String>,
+) -> (Vec<(String, String)>, PathBuf) {
+    let mut resolved = vec![];
+    for (key, value) in &env.environment {
         // 处理环境变量
         let value = handle_vars(value, &vars);
-        set_persistent_env(&key, &value)?;
         //添加程序正在添加的环境变量
-        vars.insert(key.to_string(), value);
+        vars.insert(key.to_string(), value.clone());
+        resolved.push((key.to_string(), value));
     }
 
-    println!("添加可执行Path...");
     let mut path = PathBuf::new();
-    for val in exec {
+    for val in &env.executable {
         // 处理环境变量
         let value = handle_vars(val, &vars);
         path = path.join(value);
     }
 
-    let path = path.to_str().unwrap();
-    set_persistent_path(None, path)?;
+    (resolved, executable_dir(path))
+}
+
+/// `executable` 可以直接指向可执行文件(如只包含单个可执行文件的安装包),
+/// 此时 PATH 使用文件所在的目录, Windows 下可以省略 `.exe` 后缀
+fn executable_dir(path: PathBuf) -> PathBuf {
+    let with_suffix = PathBuf::from(format!("{}{}", path.display(), env::consts::EXE_SUFFIX));
+    if !path.is_file() && !with_suffix.is_file() {
+        return path;
+    }
+    match path.parent() {
+        Some(parent) => parent.to_path_buf(),
+        None => path,
+    }
+}
+
+pub fn switch_version(env: &Environment, version: &str) -> Result<()> {
+    let name = env.name.as_str();
+    // 参数是别名时切换到别名指向的版本
+    let version = EnvConfig::current()?.resolve_alias(name, version);
+    let version = version.as_str();
+
+    let (vars, path) = resolve_environment(env, version)?;
+    // PATH 写入 shell 配置文件, 不能使用有损转换后的路径
+    let path = path
+        .to_str()
+        .ok_or_else(|| anyhow!(t!("switch.invalid_path", path.display())))?;
 
-    println!("{}", "切换版本完成!".green());
-    println!("版本: {}", version);
+    if is_dry_run() {
+        for (key, value) in &vars {
+            status!("{}", t!("dry_run.set_var", key, value));
+        }
+        status!("{}", t!("dry_run.add_path", path));
+        emit_event("switch", name, version, "dry-run");
+        return Ok(());
+    }
+
+    // 先记录修改前的配置, 任一变量设置失败时回滚, 避免留下不完整的配置
+    let keys: Vec<&str> = vars.iter().map(|(key, _)| key.as_str()).collect();
+    let snapshot = ProfileSnapshot::take(&keys)?;
+    if let Err(e) = apply_environment(&vars, path) {
+        if let Err(restore_err) = snapshot.restore() {
+            log::error!("回滚环境变量配置失败: {}", restore_err);
+        }
+        return Err(e);
+    }
 
-    let install_dir = get_install_dir(env, version);
+    status!("{}", t!("switch.done").green());
+    status!("{}", t!("switch.version", version));
 
-    // 更新配置
-    EnvConfig::switch_version(name, version, &install_dir)?;
+    let home_dir = get_version_home(env, version)?;
 
+    // 所有环境变量设置成功后才更新配置
+    EnvConfig::switch_version(name, version, &home_dir)?;
+    emit_event("switch", name, version, "ok");
+
+    if let Err(e) = apply_database_config(env, version) {
+        status!(
+            "{}",
+            format!("应用 {} 的数据库配置失败: {}", name, e).yellow()
+        );
+    }
+
+    verify_path(Path::new(path));
     Ok(())
 }
 
-pub fn configure_environment(env: &Environment) -> Value {
+/// PATH 检查发现的问题
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[derive(Debug, PartialEq)]
+enum PathProblem {
+    /// PATH 中没有 bin 目录
+    Missing,
+    /// 排在 bin 目录之前的目录中有同名的可执行文件
+    Shadowed { dir: PathBuf, program: String },
+}
+
+/// 检查 PATH 能否找到 bin 目录中的可执行文件
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn check_path_value(path: &OsStr, bin_dir: &Path) -> Option<PathProblem> {
+    let dirs: Vec<PathBuf> = env::split_paths(path).collect();
+    let Some(index) = dirs.iter().position(|dir| dir == bin_dir) else {
+        return Some(PathProblem::Missing);
+    };
+
+    let programs: Vec<String> = fs::read_dir(bin_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+
+    dirs[..index].iter().find_map(|dir| {
+        programs
+            .iter()
+            .find(|program| dir.join(program).is_file())
+            .map(|program| PathProblem::Shadowed {
+                dir: dir.clone(),
+                program: program.clone(),
+            })
+    })
+}
+
+/// 当前进程的 PATH 是否已包含 bin 目录
+fn current_path_contains(bin_dir: &Path) -> bool {
+    env::var_os("PATH").is_some_and(|path| env::split_paths(&path).any(|dir| dir == bin_dir))
+}
+
+/// 切换后在新的 shell 中检查 PATH, 未生效时提示如何重新加载
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn verify_path(bin_dir: &Path) {
+    // /etc/environment 在重新登录后才会生效, 无法在 shell 中检查
+    if is_system() || !is_human_output() {
+        return;
+    }
+
+    let Some(path) = fresh_shell_path() else {
+        return;
+    };
+    let profile = get_profile_file(false);
+    match check_path_value(OsStr::new(&path), bin_dir) {
+        Some(PathProblem::Missing) => {
+            let shell = env::var("SHELL").unwrap_or_default();
+            status!(
+                "{}",
+                format!(
+                    "新打开的 shell({}) 的 PATH 中没有 {}, 环境变量写入了 {}, 请确认 shell 启动时会加载该文件(如在 ~/.zshrc 中添加 `source {}`)",
+                    shell,
+                    bin_dir.display(),
+                    profile.display(),
+                    profile.display()
+                )
+                .yellow()
+            );
+        }
+        Some(PathProblem::Shadowed { dir, program }) => {
+            status!(
+                "{}",
+                format!(
+                    "PATH 中 {} 排在 {} 之前, 新的终端中 {} 仍会使用 {} 中的版本",
+                    dir.display(),
+                    bin_dir.display(),
+                    program,
+                    dir.display()
+                )
+                .yellow()
+            );
+        }
+        None if !current_path_contains(bin_dir) => {
+            status!(
+                "{}",
+                format!(
+                    "在当前终端执行 `source {}` 或打开新的终端后生效",
+                    profile.display()
+                )
+                .yellow()
+            );
+        }
+        None => {}
+    }
+}
+
+/// Windows 下注册表中的修改不影响已打开的终端
+#[cfg(target_os = "windows")]
+fn verify_path(bin_dir: &Path) {
+    if is_human_output() && !current_path_contains(bin_dir) {
+        status!(
+            "{}",
+            "环境变量的修改不影响已打开的终端, 请打开新的终端后使用".yellow()
+        );
+    }
+}
+
+/// 依次写入环境变量及 PATH, 失败时指明出错的变量
+fn apply_environment(vars: &[(String, String)], path: &str) -> Result<()> {
+    status!("{}", t!("switch.setting_vars"));
+    for (key, value) in vars {
+        set_persistent_env(key, value).map_err(|e| anyhow!(t!("switch.set_var_failed", key, e)))?;
+    }
+
+    status!("{}", t!("switch.adding_path"));
+    set_persistent_path(None, path).map_err(|e| anyhow!(t!("switch.add_path_failed", path, e)))?;
+    Ok(())
+}
+
+/// 构造使用指定版本的环境变量及 PATH 的命令, 版本的主目录为 home_dir
+fn command_with_version(
+    env: &Environment,
+    version: &str,
+    home_dir: &Path,
+    program: &str,
+) -> Result<Command> {
+    let (vars, bin_dir) = resolve_installed(env, version, home_dir.to_str())?;
+
+    let mut paths = vec![bin_dir];
+    if let Some(path) = env::var_os("PATH") {
+        paths.extend(env::split_paths(&path));
+    }
+
+    let mut command = Command::new(program);
+    command.envs(vars).env("PATH", env::join_paths(paths)?);
+    Ok(command)
+}
+
+/// 使用指定版本的环境变量及 PATH 运行命令, 不修改全局配置, 返回命令的退出码
+pub fn exec_with_version(env: &Environment, version: &str, args: &[String]) -> Result<i32> {
+    let home_dir = get_version_home(env, version)?;
+    if !home_dir.exists() {
+        return Err(anyhow!("{} {} 未安装", env.name, version));
+    }
+
+    let (program, program_args) = args
+        .split_first()
+        .ok_or_else(|| anyhow!("未指定要执行的命令"))?;
+
+    let status = command_with_version(env, version, &home_dir, program)?
+        .args(program_args)
+        .status()
+        .map_err(|e| anyhow!("无法执行 {}: {}", program, e))?;
+
+    Ok(status.code().unwrap_or(1))
+}
+
+/// 解压完成后在版本目录中执行配置中的 `post_install` 命令,
+/// 命令中的 `%INSTALL_DIR%` 等变量会被替换
+pub fn run_post_install(env: &Environment, version: &str, install_dir: &Path) -> Result<()> {
+    if env.post_install.is_empty() {
+        return Ok(());
+    }
+
+    let mut vars = get_dir_vars(install_dir);
+    vars.extend(resolve_installed(env, version, install_dir.to_str())?.0);
+
+    for command in &env.post_install {
+        let command = handle_vars(command, &vars);
+        if is_dry_run() {
+            status!("[dry-run] 执行安装后命令: {}", command);
+            continue;
+        }
+
+        status!("执行安装后命令: {}", command);
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        let status = command_with_version(env, version, install_dir, shell)?
+            .arg(flag)
+            .arg(&command)
+            .current_dir(install_dir)
+            .status()
+            .map_err(|e| anyhow!("无法执行安装后命令 {}: {}", command, e))?;
+
+        if !status.success() {
+            return Err(anyhow!("安装后命令执行失败: {} ({})", command, status));
+        }
+    }
+    Ok(())
+}
+
+/// 交互被用户取消(Esc/Ctrl-C)时返回的错误
+pub fn cancelled() -> anyhow::Error {
+    anyhow!("操作已取消")
+}
+
+/// 转换交互式提示的错误, 被中断时视为取消
+pub fn prompt_error(e: dialoguer::Error) -> anyhow::Error {
+    match e {
+        dialoguer::Error::IO(e) if e.kind() == ErrorKind::Interrupted => cancelled(),
+        dialoguer::Error::IO(e) => anyhow!("交互输入失败: {}", e),
+    }
+}
+
+/// 多选的默认选中项, 选中上一次安装时选择的值
+fn multi_select_defaults(options: &[String], last: Option<&Value>) -> Vec<bool> {
+    let selected = last.and_then(Value::as_array);
+    options
+        .iter()
+        .map(|option| {
+            selected.is_some_and(|values| values.iter().any(|v| v.as_str() == Some(option)))
+        })
+        .collect()
+}
+
+/// 从配置结果中读取版本参数
+pub fn get_version_arg(env: &Environment, args: &Value) -> Result<String> {
+    args.get("version")
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or_else(|| anyhow!("{} 的配置缺少 version 参数, 请检查配置文件", env.name))
+}
+
+/// 端口是否可以监听, 已被其他程序占用时返回 false
+pub fn is_port_available(port: u16) -> bool {
+    TcpListener::bind(("127.0.0.1", port)).is_ok()
+}
+
+/// 从指定端口开始查找第一个可用的端口
+pub fn next_free_port(port: u16) -> Option<u16> {
+    (port..=u16::MAX).find(|port| is_port_available(*port))
+}
+
+/// 校验输入的端口, 错误信息在交互提示中显示后重新输入
+fn validate_port(input: &str) -> std::result::Result<(), String> {
+    let port = input
+        .trim()
+        .parse::<u16>()
+        .ok()
+        .filter(|port| *port > 0)
+        .ok_or_else(|| "端口必须是 1-65535 之间的数字".to_string())?;
+    if is_port_available(port) {
+        return Ok(());
+    }
+
+    match next_free_port(port) {
+        Some(free) => Err(format!("端口 {} 已被占用, 可以使用 {}", port, free)),
+        None => Err(format!("端口 {} 已被占用", port)),
+    }
+}
+
+pub fn configure_environment(env: &Environment) -> Result<Value> {
     if !is_supported_env(env) {
-        println!("不支持的环境: {}", env.name.red());
-        std::process::exit(1);
+        return Err(anyhow!("不支持的环境: {}", env.name.red()));
     }
 
     let args = &env.args;
+    let config = EnvConfig::current()?;
 
     let mut ret = HashMap::new();
     for arg in args {
+        // 优先使用上一次安装时选择的值作为默认值, 其次是配置中的默认版本
+        let last = config.get_last_arg(&env.name, &arg.name);
+        let fallback = match arg.name.as_str() {
+            "version" => config.get_default_version(&env.name),
+            _ => None,
+        }
+        .unwrap_or_else(|| arg.default.clone());
+        let default = last
+            .and_then(Value::as_str)
+            .unwrap_or(&fallback)
+            .to_string();
+
         let arg_type = arg.type_.as_str();
         let description = &arg.description;
-        let options = &arg.options;
-        let default_idx = options.iter().position(|v| v == &arg.default).unwrap_or(0);
+        let mut options = arg.options.clone();
         let select_description = arg.select_description.clone().unwrap_or(vec![]);
         let mut i = 0;
 
-        let items = options
+        let mut items = options
             .iter()
             .map(|v| {
                 let sd = {
                     if i < select_description.len() {
                         select_description[i].clone()
                     } else {
-                        String::new()
+                        // 没有描述时标注版本所属的通道
+                        arg.get_channel(v).label().to_string()
                     }
                 };
                 i += 1;
@@ -110,64 +533,222 @@ pub fn configure_environment(env: &Environment) -> Value {
             })
             .collect::<Vec<String>>();
 
+        // 指定 --channel 时只列出该通道的版本
+        if let (true, Some(channel)) = (arg.name == "version", get_channel()) {
+            (options, items) = options
+                .into_iter()
+                .zip(items)
+                .filter(|(v, _)| arg.get_channel(v).matches(channel))
+                .unzip();
+            if options.is_empty() {
+                return Err(anyhow!("{} 没有 {} 通道的版本", env.name, channel));
+            }
+        }
+        let default_idx = options
+            .iter()
+            .position(|v| v == &default)
+            .or_else(|| options.iter().position(|v| v == &fallback))
+            .unwrap_or(0);
+
         let mut value = Value::Null;
 
         match arg_type {
+            // 数据库端口已被占用时提示并建议下一个可用的端口
+            "input" if arg.name == "port" => {
+                let default = match default.parse::<u16>() {
+                    Ok(port) if !is_port_available(port) => next_free_port(port)
+                        .map(|port| port.to_string())
+                        .unwrap_or(default),
+                    _ => default,
+                };
+                value = Value::String(
+                    Input::<String>::with_theme(&ColorfulTheme::default())
+                        .with_prompt(description)
+                        .default(default)
+                        .validate_with(|input: &String| validate_port(input))
+                        .interact_text()
+                        .map_err(prompt_error)?,
+                );
+            }
             "input" => {
                 value = Value::String(
                     Input::<String>::with_theme(&ColorfulTheme::default())
                         .with_prompt(description)
-                        .default(arg.default.clone())
+                        .default(default)
                         .interact_text()
-                        .unwrap(),
+                        .map_err(prompt_error)?,
                 );
             }
             "select" => {
+                // 版本选择额外提供最新稳定版
+                if arg.name == "version" {
+                    items.push(format!("{} - 最新稳定版", LATEST_VERSION));
+                }
                 let selected = Select::with_theme(&ColorfulTheme::default())
                     .with_prompt(description)
                     .default(default_idx)
                     .items(&items)
-                    .interact()
-                    .unwrap();
-                value = Value::String(options[selected].clone());
+                    .interact_opt()
+                    .map_err(prompt_error)?
+                    .ok_or_else(cancelled)?;
+                let selected = options
+                    .get(selected)
+                    .map(String::as_str)
+                    .unwrap_or(LATEST_VERSION);
+                value = Value::String(selected.to_string());
             }
             "multi-select" => {
                 let selected = MultiSelect::with_theme(&ColorfulTheme::default())
                     .with_prompt(description)
-                    .defaults(&vec![false; items.len()])
+                    .defaults(&multi_select_defaults(&options, last))
                     .items(&items)
-                    .interact()
-                    .unwrap();
+                    .interact_opt()
+                    .map_err(prompt_error)?
+                    .ok_or_else(cancelled)?;
                 let arr = selected
                     .iter()
                     .map(|i| options[*i].clone())
                     .collect::<Vec<String>>();
 
-                value = serde_json::to_value(arr).unwrap();
+                value = serde_json::to_value(arr)?;
             }
             "password" => {
-                let confirm_prompt = "确认密码";
-                let mismatch_err = "两次输入的密码不一致";
-                value = Value::String(
-                    Password::with_theme(&ColorfulTheme::default())
-                        .with_prompt(description)
-                        .with_confirmation(confirm_prompt, mismatch_err)
-                        .interact()
-                        .unwrap(),
-                );
+                if let Some((key, password)) = arg.get_env_password(&env.name) {
+                    status!("{}: 使用环境变量 {} 中的密码", description, key);
+                    value = Value::String(password);
+                } else {
+                    let theme = ColorfulTheme::default();
+                    let mut prompt = Password::with_theme(&theme).with_prompt(description);
+                    if arg.confirm {
+                        prompt = prompt.with_confirmation("确认密码", "两次输入的密码不一致");
+                    }
+                    value = Value::String(prompt.interact().map_err(prompt_error)?);
+                }
             }
             _ => {}
         }
 
-        if !(arg_type == "password") {
-            println!(
+        if arg_type != "password" {
+            status!(
                 "{}: {}",
                 description,
-                serde_json::to_string(&value).unwrap().green()
+                serde_json::to_string(&value)?.green()
             );
         }
 
         ret.insert(arg.name.clone(), value);
     }
-    serde_json::to_value(ret).unwrap()
+    Ok(serde_json::to_value(ret)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_port() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        assert!(!is_port_available(port));
+        let err = validate_port(&port.to_string()).unwrap_err();
+        assert!(err.starts_with(&format!("端口 {} 已被占用, 可以使用 ", port)));
+        let free = next_free_port(port).unwrap();
+        assert!(free > port && is_port_available(free));
+
+        assert!(validate_port("0").is_err());
+        assert!(validate_port("65536").is_err());
+        assert!(validate_port("mysql").is_err());
+
+        drop(listener);
+        assert!(validate_port(&port.to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_shell_env_script() {
+        let vars = vec![("JAVA_HOME".to_string(), "/opt/it's/java-21".to_string())];
+        let bin_dir = Path::new("/opt/it's/java-21/bin");
+
+        assert_eq!(
+            shell_env_script(Shell::Bash, &vars, bin_dir),
+            "export JAVA_HOME='/opt/it'\\''s/java-21'\nexport PATH='/opt/it'\\''s/java-21/bin':\"$PATH\""
+        );
+        assert_eq!(
+            shell_env_script(Shell::Fish, &vars, bin_dir),
+            "set -gx JAVA_HOME '/opt/it\\'s/java-21'\nset -gx PATH '/opt/it\\'s/java-21/bin' $PATH"
+        );
+        assert!(
+            shell_env_script(Shell::PowerShell, &vars, bin_dir).starts_with(
+                "$env:JAVA_HOME = '/opt/it''s/java-21'\n$env:PATH = '/opt/it''s/java-21/bin'"
+            )
+        );
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_check_path_value() {
+        let root = env::temp_dir().join("env-check-path");
+        let old_bin = root.join("old/bin");
+        let bin = root.join("java-21/bin");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&old_bin).unwrap();
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(bin.join("java"), "").unwrap();
+
+        let path = |dirs: &[&Path]| env::join_paths(dirs).unwrap();
+        assert_eq!(
+            check_path_value(&path(&[&old_bin]), &bin),
+            Some(PathProblem::Missing)
+        );
+        assert_eq!(check_path_value(&path(&[&bin, &old_bin]), &bin), None);
+        assert_eq!(check_path_value(&path(&[&old_bin, &bin]), &bin), None);
+
+        // 旧版本的同名程序排在前面
+        fs::write(old_bin.join("java"), "").unwrap();
+        assert_eq!(
+            check_path_value(&path(&[&old_bin, &bin]), &bin),
+            Some(PathProblem::Shadowed {
+                dir: old_bin.clone(),
+                program: "java".to_string()
+            })
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_bin_dir() {
+        let config: EnvConfig =
+            serde_json::from_str(include_str!("../.env.config.default.json")).unwrap();
+        let mut env = config.get_enviroment("java").unwrap();
+
+        let home = PathBuf::from("/opt/env/java/java-21");
+        let bin = resolve_bin_dir(&env, "21", home.to_str()).unwrap();
+        assert_eq!(bin, home.join("bin"));
+
+        // 没有配置 executable 时使用主目录
+        env.executable.clear();
+        assert_eq!(resolve_bin_dir(&env, "21", home.to_str()).unwrap(), home);
+    }
+
+    #[test]
+    fn test_executable_file() {
+        let home = std::env::temp_dir().join("env_executable_file_test");
+        let _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::write(home.join(format!("kubectl{}", env::consts::EXE_SUFFIX)), "").unwrap();
+
+        let config: EnvConfig =
+            serde_json::from_str(include_str!("../.env.config.default.json")).unwrap();
+        let mut env = config.get_enviroment("kubectl").unwrap();
+        env.executable = vec!["%KUBECTL_HOME%".to_string(), "kubectl".to_string()];
+
+        // 指向文件时 PATH 使用版本目录本身
+        assert_eq!(
+            resolve_bin_dir(&env, "1.32.0", home.to_str()).unwrap(),
+            home
+        );
+
+        std::fs::remove_dir_all(&home).unwrap();
+    }
 }
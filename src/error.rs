@@ -0,0 +1,188 @@
+//! 可区分类型的错误, 调用方可以通过 `downcast_ref` 判断失败原因,
+//! JSON 输出中以稳定的错误码标识
+
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::t;
+
+#[derive(Debug, Error)]
+pub enum EnvError {
+    /// 下载地址无法访问或返回错误状态码
+    DownloadFailed { url: String, reason: String },
+
+    /// 连接或读取超时
+    Timeout { url: String, reason: String },
+
+    /// 下载的文件与期望的校验和不一致
+    ChecksumMismatch {
+        file: String,
+        expected: String,
+        actual: String,
+    },
+
+    /// 当前平台没有对应的安装包
+    UnsupportedPlatform { name: String, platform: String },
+
+    /// 环境限制了可用的操作系统
+    UnsupportedOs {
+        name: String,
+        os: String,
+        supported: String,
+    },
+
+    /// 配置中标记为不支持的环境
+    UnsupportedEnvironment(String),
+
+    /// 下载地址不存在
+    PackageNotFound {
+        name: String,
+        version: String,
+        platform: String,
+        url: String,
+    },
+
+    /// 配置文件内容无效
+    ConfigInvalid(String),
+
+    /// 安装包解压失败
+    ExtractFailed { file: String, reason: String },
+
+    /// 其他进程正在安装同一版本
+    InstallInProgress { name: String, version: String },
+
+    /// 安装目录所在磁盘空间不足
+    InsufficientSpace {
+        dir: String,
+        needed: String,
+        available: String,
+    },
+
+    /// 整个操作超过 `--deadline` 指定的时间
+    DeadlineExceeded(u64),
+}
+
+impl EnvError {
+    /// 稳定的错误码, 用于 `--format json` 的错误事件
+    pub fn code(&self) -> &'static str {
+        match self {
+            EnvError::DownloadFailed { .. } => "download_failed",
+            EnvError::Timeout { .. } => "timeout",
+            EnvError::ChecksumMismatch { .. } => "checksum_mismatch",
+            EnvError::UnsupportedPlatform { .. } | EnvError::UnsupportedOs { .. } => {
+                "unsupported_platform"
+            }
+            EnvError::UnsupportedEnvironment(_) => "unsupported_environment",
+            EnvError::PackageNotFound { .. } => "package_not_found",
+            EnvError::ConfigInvalid(_) => "config_invalid",
+            EnvError::ExtractFailed { .. } => "extract_failed",
+            EnvError::InstallInProgress { .. } => "install_in_progress",
+            EnvError::InsufficientSpace { .. } => "insufficient_space",
+            EnvError::DeadlineExceeded(_) => "deadline_exceeded",
+        }
+    }
+
+    /// 进程退出码, 参照 sysexits.h, 超时与 timeout 命令一致使用 124
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            EnvError::DownloadFailed { .. }
+            | EnvError::PackageNotFound { .. }
+            | EnvError::UnsupportedPlatform { .. }
+            | EnvError::UnsupportedOs { .. }
+            | EnvError::UnsupportedEnvironment(_) => 69,
+            EnvError::ChecksumMismatch { .. } | EnvError::ExtractFailed { .. } => 65,
+            EnvError::InsufficientSpace { .. } => 73,
+            EnvError::Timeout { .. } | EnvError::InstallInProgress { .. } => 75,
+            EnvError::ConfigInvalid(_) => 78,
+            EnvError::DeadlineExceeded(_) => 124,
+        }
+    }
+}
+
+/// 错误信息按当前语言输出
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            EnvError::DownloadFailed { url, reason } => t!("error.download_failed", url, reason),
+            EnvError::Timeout { url, reason } => t!("error.timeout", url, reason),
+            EnvError::ChecksumMismatch {
+                file,
+                expected,
+                actual,
+            } => t!("error.checksum_mismatch", file, expected, actual),
+            EnvError::UnsupportedPlatform { name, platform } => {
+                t!("error.unsupported_platform", name, platform)
+            }
+            EnvError::UnsupportedOs {
+                name,
+                os,
+                supported,
+            } => t!("error.unsupported_os", name, supported, os),
+            EnvError::UnsupportedEnvironment(name) => t!("error.unsupported_environment", name),
+            EnvError::PackageNotFound {
+                name,
+                version,
+                platform,
+                url,
+            } => t!("error.package_not_found", name, version, platform, url),
+            EnvError::ConfigInvalid(problems) => t!("error.config_invalid", problems),
+            EnvError::ExtractFailed { file, reason } => t!("error.extract_failed", file, reason),
+            EnvError::InstallInProgress { name, version } => {
+                t!("error.install_in_progress", name, version)
+            }
+            EnvError::InsufficientSpace {
+                dir,
+                needed,
+                available,
+            } => t!("error.insufficient_space", needed, dir, available),
+            EnvError::DeadlineExceeded(secs) => t!("error.deadline_exceeded", secs),
+        };
+        f.write_str(&message)
+    }
+}
+
+/// 获取错误链中第一个 `EnvError` 的错误码
+pub fn error_code(err: &anyhow::Error) -> Option<&'static str> {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<EnvError>())
+        .map(EnvError::code)
+}
+
+/// 获取错误链中第一个 `EnvError` 的退出码, 其他错误为 1
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<EnvError>())
+        .map_or(1, EnvError::exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_error_code() {
+        let err: anyhow::Error = EnvError::ExtractFailed {
+            file: "jdk.tar.gz".to_string(),
+            reason: "unexpected EOF".to_string(),
+        }
+        .into();
+        assert_eq!(error_code(&err), Some("extract_failed"));
+        assert_eq!(
+            err.to_string(),
+            t!("error.extract_failed", "jdk.tar.gz", "unexpected EOF")
+        );
+
+        // 外层添加了上下文时仍能找到
+        let err = Err::<(), _>(EnvError::UnsupportedEnvironment("python".to_string()))
+            .context("安装失败")
+            .unwrap_err();
+        assert_eq!(error_code(&err), Some("unsupported_environment"));
+
+        assert_eq!(exit_code(&err), 69);
+
+        assert_eq!(error_code(&anyhow::anyhow!("其他错误")), None);
+        assert_eq!(exit_code(&anyhow::anyhow!("其他错误")), 1);
+    }
+}
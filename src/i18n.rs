@@ -0,0 +1,785 @@
+//! 输出消息的多语言支持, 消息按 key 保存中英文两份,
+//! 语言由配置中的 `lang` 决定, 未配置时根据 `LC_ALL`/`LC_MESSAGES`/`LANG` 判断
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+/// 输出消息使用的语言
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    /// 中文
+    #[default]
+    Zh,
+    /// 英文
+    En,
+}
+
+impl Lang {
+    /// 根据 locale 判断语言, 以 zh 开头、C/C.UTF-8/POSIX 及空值使用中文, 其他使用英文
+    fn from_locale(locale: &str) -> Lang {
+        let locale = locale.trim().to_lowercase();
+        let base = locale.split(['.', '@']).next().unwrap_or_default();
+        if base.is_empty() || base.starts_with("zh") || base == "c" || base == "posix" {
+            Lang::Zh
+        } else {
+            Lang::En
+        }
+    }
+}
+
+static LANG: RwLock<Option<Lang>> = RwLock::new(None);
+
+/// 设置输出语言, 为 None 时根据环境变量判断
+pub fn set_lang(lang: Option<Lang>) {
+    *LANG.write().unwrap() = lang;
+}
+
+/// 当前的输出语言
+pub fn current_lang() -> Lang {
+    if let Some(lang) = *LANG.read().unwrap() {
+        return lang;
+    }
+    locale_lang()
+}
+
+/// 根据 LC_ALL、LC_MESSAGES、LANG 环境变量判断的语言
+pub fn locale_lang() -> Lang {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|key| env::var(key).ok())
+        .find(|value| !value.is_empty())
+        .map(|locale| Lang::from_locale(&locale))
+        .unwrap_or_default()
+}
+
+/// 消息 key, 中文, 英文, `{}` 按顺序替换为参数
+const MESSAGES: &[(&str, &str, &str)] = &[
+    // 安装
+    (
+        "install.start",
+        "开始安装 {}: {}...",
+        "Installing {}: {}...",
+    ),
+    (
+        "install.latest",
+        "{} 的最新版本为: {}",
+        "Latest version of {}: {}",
+    ),
+    (
+        "install.incomplete",
+        "检测到未完成的安装, 将重新安装: {}",
+        "Found an incomplete install, reinstalling: {}",
+    ),
+    (
+        "install.resolver_failed",
+        "解析命令失败, 使用内置的下载地址: {}",
+        "The resolver command failed, using the built-in download URL: {}",
+    ),
+    ("install.package_url", "下载地址: {}", "Download URL: {}"),
+    ("install.mirror_url", "备用地址: {}", "Mirror URL: {}"),
+    (
+        "install.cached",
+        "使用缓存的安装包: {}",
+        "Using cached package: {}",
+    ),
+    ("install.downloaded", "下载完成: {}", "Downloaded: {}"),
+    (
+        "install.local",
+        "使用本地安装包: {}",
+        "Using local package: {}",
+    ),
+    (
+        "install.streamed",
+        "下载并解压完成: {}",
+        "Downloaded and extracted: {}",
+    ),
+    (
+        "install.url_unavailable",
+        "地址不可用, 尝试下一个: {}",
+        "URL unavailable, trying the next one: {}",
+    ),
+    (
+        "install.download_retry",
+        "下载失败, 尝试下一个地址: {}",
+        "Download failed, trying the next URL: {}",
+    ),
+    (
+        "install.download_only",
+        "已下载安装包: {}",
+        "Downloaded package: {}",
+    ),
+    (
+        "install.cross_platform",
+        "目标平台 {}-{} 与当前平台不同, 只下载安装包: {}",
+        "Target platform {}-{} differs from the current one, only downloaded: {}",
+    ),
+    (
+        "install.remove_version",
+        "删除已安装的版本: {}",
+        "Removing installed version: {}",
+    ),
+    (
+        "install.remove_cached",
+        "删除缓存的安装包: {}",
+        "Removing cached package: {}",
+    ),
+    (
+        "install.extracting",
+        "正在解压到临时目录: {}",
+        "Extracting to temporary directory: {}",
+    ),
+    ("install.copy_binary", "复制 {} 到 {}", "Copying {} to {}"),
+    (
+        "install.empty_package",
+        "安装包内容为空",
+        "The package is empty",
+    ),
+    (
+        "install.post_install_failed",
+        "安装后命令执行失败, 版本仍记录为已安装, 可以稍后手动执行: {}",
+        "Post-install command failed, the version is still recorded as installed; run it manually later: {}",
+    ),
+    (
+        "install.save_args_failed",
+        "无法保存本次选择的参数: {}",
+        "Failed to save the chosen arguments: {}",
+    ),
+    (
+        "install.lock_failed",
+        "无法锁定 {}: {}",
+        "Failed to lock {}: {}",
+    ),
+    (
+        "install.adopt_prompt",
+        "检测到系统已安装 {} {} ({}), 是否直接使用而不重新下载?",
+        "Found {} {} installed at {}, use it instead of downloading again?",
+    ),
+    (
+        "install.adopt_done",
+        "已登记系统中的 {} {}: {}",
+        "Registered the system {} {}: {}",
+    ),
+    (
+        "install.select_hint",
+        "使用空格键选择/取消选择，回车键确认",
+        "Press space to select/deselect, enter to confirm",
+    ),
+    ("install.move_hint", "↑/↓ 或 j/k 移动光标", "Move with ↑/↓ or j/k"),
+    (
+        "install.select_prompt",
+        "选择要安装的环境",
+        "Select environments to install",
+    ),
+    (
+        "install.none_selected",
+        "未选择任何环境",
+        "No environment selected",
+    ),
+    ("install.resolve_failed", "解析版本失败", "failed to resolve version"),
+    ("install.failed", "安装失败", "install failed"),
+    ("install.download_failed", "下载失败", "download failed"),
+    (
+        "install.skip_unsupported",
+        "跳过暂不支持的环境: {}",
+        "Skipping unsupported environment: {}",
+    ),
+    (
+        "install.no_default_version",
+        "配置中没有默认版本",
+        "No default version in the configuration",
+    ),
+    (
+        "install.spec_stdin_failed",
+        "无法从标准输入读取安装清单: {}",
+        "Failed to read the install manifest from stdin: {}",
+    ),
+    (
+        "install.spec_read_failed",
+        "无法读取安装清单 {}: {}",
+        "Failed to read the install manifest {}: {}",
+    ),
+    (
+        "install.spec_invalid",
+        "安装清单格式错误: {}",
+        "Invalid install manifest: {}",
+    ),
+    (
+        "install.spec_version_type",
+        "{} 的 version 必须是字符串",
+        "The version of {} must be a string",
+    ),
+    (
+        "install.spec_version_missing",
+        "{} 缺少 version 且配置中没有默认版本",
+        "{} has no version and no default version in the configuration",
+    ),
+    ("install.spec_empty", "安装清单为空", "The install manifest is empty"),
+    ("install.summary", "\n安装结果:", "\nInstall results:"),
+    ("install.installed", "已安装", "installed"),
+    (
+        "install.installed_switched",
+        "已安装并切换",
+        "installed and switched",
+    ),
+    (
+        "install.installed_not_switched",
+        "已安装, 未切换",
+        "installed, not switched",
+    ),
+    ("install.switched", "已切换", "switched"),
+    (
+        "install.adopted",
+        "已登记系统安装",
+        "registered system install",
+    ),
+    ("install.downloaded_status", "已下载", "downloaded"),
+    (
+        "install.failed_count",
+        "{}/{} 个环境安装失败",
+        "{}/{} environments failed to install",
+    ),
+    // dry-run
+    (
+        "dry_run.cached",
+        "[dry-run] 使用缓存的安装包: {}",
+        "[dry-run] Use cached package: {}",
+    ),
+    (
+        "dry_run.local",
+        "[dry-run] 使用本地安装包 {} 安装到: {}",
+        "[dry-run] Install local package {} into: {}",
+    ),
+    (
+        "dry_run.download",
+        "[dry-run] 下载: {}",
+        "[dry-run] Download: {}",
+    ),
+    (
+        "dry_run.download_only",
+        "[dry-run] 只下载安装包, 不解压及切换版本",
+        "[dry-run] Only download the package, do not extract or switch",
+    ),
+    (
+        "dry_run.stream",
+        "[dry-run] 边下载边解压到: {}",
+        "[dry-run] Stream and extract to: {}",
+    ),
+    (
+        "dry_run.copy_binary",
+        "[dry-run] 复制可执行文件到: {}",
+        "[dry-run] Copy executable to: {}",
+    ),
+    (
+        "dry_run.installer",
+        "[dry-run] 运行 {} 安装程序, 安装到: {}",
+        "[dry-run] Run the {} installer into: {}",
+    ),
+    (
+        "dry_run.extract",
+        "[dry-run] 解压到: {}",
+        "[dry-run] Extract to: {}",
+    ),
+    (
+        "dry_run.remove_version",
+        "[dry-run] 删除已安装的版本: {}",
+        "[dry-run] Remove installed version: {}",
+    ),
+    (
+        "dry_run.remove_cached",
+        "[dry-run] 删除缓存的安装包: {}",
+        "[dry-run] Remove cached package: {}",
+    ),
+    (
+        "dry_run.set_var",
+        "[dry-run] 设置环境变量: {}={}",
+        "[dry-run] Set environment variable: {}={}",
+    ),
+    (
+        "dry_run.add_path",
+        "[dry-run] 添加 PATH: {}",
+        "[dry-run] Add to PATH: {}",
+    ),
+    (
+        "dry_run.adopt",
+        "[dry-run] 登记已安装的 {} {}: {}",
+        "[dry-run] Register the installed {} {}: {}",
+    ),
+    (
+        "dry_run.init_database",
+        "[dry-run] 初始化 {} 数据目录: {}, 端口: {}",
+        "[dry-run] Initialize the {} data directory: {}, port: {}",
+    ),
+    (
+        "dry_run.register",
+        "[dry-run] 登记已安装的版本, 不切换: {} {}",
+        "[dry-run] Register the installed version without switching: {} {}",
+    ),
+    (
+        "dry_run.unregister",
+        "[dry-run] 取消登记: {}",
+        "[dry-run] Unregister: {}",
+    ),
+    (
+        "dry_run.delete_alias",
+        "[dry-run] 删除别名: {} {}",
+        "[dry-run] Delete alias: {} {}",
+    ),
+    (
+        "dry_run.set_alias",
+        "[dry-run] 设置别名: {} {} -> {}",
+        "[dry-run] Set alias: {} {} -> {}",
+    ),
+    (
+        "dry_run.set_config",
+        "[dry-run] 设置 {} = {}",
+        "[dry-run] Set {} = {}",
+    ),
+    // 切换版本
+    ("switch.done", "切换版本完成!", "Switched version!"),
+    ("switch.version", "版本: {}", "Version: {}"),
+    (
+        "switch.setting_vars",
+        "正在设置环境变量...",
+        "Setting environment variables...",
+    ),
+    (
+        "switch.adding_path",
+        "添加可执行Path...",
+        "Adding executables to PATH...",
+    ),
+    (
+        "switch.set_var_failed",
+        "设置环境变量 {} 失败: {}",
+        "Failed to set environment variable {}: {}",
+    ),
+    (
+        "switch.add_path_failed",
+        "添加 PATH {} 失败: {}",
+        "Failed to add {} to PATH: {}",
+    ),
+    (
+        "switch.invalid_path",
+        "可执行文件目录不是有效的 UTF-8, 无法添加到 PATH: {}",
+        "The executable directory is not valid UTF-8 and cannot be added to PATH: {}",
+    ),
+    (
+        "switch.previous",
+        "切换回上一个版本: {} {}",
+        "Switching back to the previous version: {} {}",
+    ),
+    (
+        "switch.no_previous",
+        "{} 没有可以切换回的上一个版本",
+        "{} has no previous version to switch back to",
+    ),
+    // 版本管理
+    (
+        "env.not_found",
+        "未找到 {} 环境",
+        "Environment {} not found",
+    ),
+    (
+        "env.no_current",
+        "{} 没有正在使用的版本",
+        "{} has no version in use",
+    ),
+    ("env.not_installed", "{} 未安装", "{} is not installed"),
+    (
+        "env.pinned",
+        "使用项目固定的版本: {} {}",
+        "Using the version pinned by the project: {} {}",
+    ),
+    ("env.installed_mark", "{} (已安装)", "{} (installed)"),
+    ("version.current", "当前版本", "current"),
+    ("version.none", "无", "none"),
+    (
+        "version.not_found",
+        "未找到 {} 的版本",
+        "No versions of {} found",
+    ),
+    ("version.select", "选择版本", "Select a version"),
+    (
+        "version.select_uninstall",
+        "选择要卸载的版本",
+        "Select a version to uninstall",
+    ),
+    (
+        "version.not_installed",
+        "{} {} 未安装",
+        "{} {} is not installed",
+    ),
+    (
+        "version.in_use",
+        "{} 已在使用 {}",
+        "{} is already using {}",
+    ),
+    (
+        "version.registered",
+        "{} {} 已安装, 未切换当前版本",
+        "{} {} installed, the current version was not switched",
+    ),
+    ("manage.switch", "切换版本", "Switch version"),
+    ("manage.install", "安装新版本", "Install a new version"),
+    ("manage.uninstall", "卸载版本", "Uninstall a version"),
+    (
+        "manage.prompt",
+        "{} (当前版本: {})",
+        "{} (current version: {})",
+    ),
+    (
+        "manage.confirm_uninstall",
+        "确认卸载 {} {}?",
+        "Uninstall {} {}?",
+    ),
+    // 卸载及清理
+    (
+        "uninstall.in_use",
+        "不能卸载正在使用的版本 {} {}, 请先切换到其他版本",
+        "Cannot uninstall {} {} while it is in use, switch to another version first",
+    ),
+    (
+        "uninstall.remove_failed",
+        "删除版本目录失败 {}: {}",
+        "Failed to remove the version directory {}: {}",
+    ),
+    ("uninstall.done", "已卸载 {} {}", "Uninstalled {} {}"),
+    (
+        "prune.nothing",
+        "{} 没有需要清理的版本",
+        "{} has no versions to prune",
+    ),
+    (
+        "prune.plan",
+        "将删除 {} 的以下版本: {}",
+        "The following versions of {} will be removed: {}",
+    ),
+    ("prune.confirm", "确认删除?", "Remove them?"),
+    // 别名
+    (
+        "alias.none",
+        "{} 没有设置版本别名",
+        "{} has no version aliases",
+    ),
+    ("alias.not_found", "{} 没有别名 {}", "{} has no alias {}"),
+    (
+        "alias.not_installed",
+        "{} {} 尚未安装, 切换前需要先安装",
+        "{} {} is not installed yet, install it before switching",
+    ),
+    ("alias.deleted", "已删除别名: {} {}", "Deleted alias: {} {}"),
+    ("alias.set", "已设置别名: {} {} -> {}", "Set alias: {} {} -> {}"),
+    // 导入导出
+    (
+        "export.done",
+        "已导出配置到: {}",
+        "Exported the configuration to: {}",
+    ),
+    (
+        "import.read_failed",
+        "无法读取导入文件 {}: {}",
+        "Failed to read the import file {}: {}",
+    ),
+    (
+        "import.invalid",
+        "导入文件格式错误 {}: {}",
+        "Invalid import file {}: {}",
+    ),
+    (
+        "import.environments",
+        "已导入 {} 个自定义环境",
+        "Imported {} custom environments",
+    ),
+    (
+        "import.all_installed",
+        "所有版本均已安装",
+        "All versions are already installed",
+    ),
+    (
+        "import.missing",
+        "以下版本尚未安装: {}",
+        "The following versions are not installed: {}",
+    ),
+    ("import.prompt", "是否现在安装?", "Install them now?"),
+    // 状态
+    ("status.install_path", "安装目录: {}", "Install path: {}"),
+    ("status.cache", "下载缓存: {} ({})", "Download cache: {} ({})"),
+    ("status.index", "仓库索引: {}", "Repository index: {}"),
+    ("status.resolver", "解析命令: {}", "Resolver command: {}"),
+    ("status.proxy", "代理: {}", "Proxy: {}"),
+    ("status.no_proxy", "未配置", "not configured"),
+    (
+        "status.none_installed",
+        "尚未安装任何环境",
+        "No environments installed yet",
+    ),
+    (
+        "status.active",
+        "✅ PATH 中为当前版本",
+        "✅ current version is on PATH",
+    ),
+    (
+        "status.shadowed",
+        "⚠️  PATH 中使用的是 {}",
+        "⚠️  PATH resolves to {}",
+    ),
+    (
+        "status.missing",
+        "⚠️  PATH 中未找到, 请打开新的终端",
+        "⚠️  not found on PATH, open a new terminal",
+    ),
+    ("status.no_version", "未选择版本", "no version selected"),
+    // 修复
+    (
+        "repair.unknown_env",
+        "未找到 {} 环境的定义, 跳过",
+        "No definition found for environment {}, skipping",
+    ),
+    ("repair.not_installed", "{} 尚未安装", "{} is not installed"),
+    (
+        "repair.reinstall",
+        "{} {} 的安装目录缺失或不完整, 重新安装",
+        "The install directory of {} {} is missing or incomplete, reinstalling",
+    ),
+    (
+        "repair.reinstall_failed",
+        "重新安装 {} {} 失败: {}",
+        "Failed to reinstall {} {}: {}",
+    ),
+    (
+        "repair.external",
+        "{} {} 使用安装目录之外的 {}, 跳过",
+        "{} {} uses {} outside the install directory, skipping",
+    ),
+    (
+        "repair.switch_failed",
+        "切换 {} {} 失败: {}",
+        "Failed to switch {} {}: {}",
+    ),
+    (
+        "repair.failed",
+        "以下版本修复失败: {}",
+        "Failed to repair: {}",
+    ),
+    ("repair.done", "修复完成", "Repair finished"),
+    // 校验
+    (
+        "verify.not_cached",
+        "下载缓存中没有 {} {} ({}) 的安装包",
+        "No cached package for {} {} ({})",
+    ),
+    ("verify.ok", "校验通过:", "Verified:"),
+    // 数据库
+    (
+        "database.existing_dir",
+        "使用已有的数据目录: {}",
+        "Using the existing data directory: {}",
+    ),
+    (
+        "database.password_unchanged",
+        "{} 数据目录已存在, root 密码仍为初始化时设置的密码",
+        "The {} data directory already exists, the root password is still the one set at initialization",
+    ),
+    (
+        "database.init",
+        "正在初始化 {} 数据目录: {}",
+        "Initializing the {} data directory: {}",
+    ),
+    (
+        "database.missing_dir",
+        "{} {} 的数据目录不存在: {}",
+        "The data directory of {} {} does not exist: {}",
+    ),
+    (
+        "database.shared_dir",
+        "{} {} 与 {} 使用同一个数据目录 {}, 不同版本的数据文件可能不兼容",
+        "{} {} shares a data directory with {} ({}), data files of different versions may be incompatible",
+    ),
+    (
+        "database.port_mismatch",
+        "{} 中的端口与安装时记录的 {} 不一致",
+        "The port in {} differs from {} recorded at install time",
+    ),
+    (
+        "database.run_failed",
+        "无法运行 {}: {}",
+        "Failed to run {}: {}",
+    ),
+    (
+        "database.command_failed",
+        "{} 执行失败: {}",
+        "{} failed: {}",
+    ),
+    (
+        "database.mongodb_password",
+        "MongoDB 需要在启动后创建用户, 已忽略 password 参数",
+        "MongoDB users must be created after it starts, the password argument is ignored",
+    ),
+    // 配置
+    ("config.set", "已设置 {} = {}", "Set {} = {}"),
+    (
+        "config.lang_kept",
+        "输出语言保持为中文, 执行 `env config set lang en` 可切换为英文",
+        "Output stays in Chinese as before; run `env config set lang en` to switch to English",
+    ),
+    // 错误
+    ("error.prefix", "env error: {}", "env error: {}"),
+    (
+        "error.download_failed",
+        "下载失败 {}: {}",
+        "Download failed {}: {}",
+    ),
+    (
+        "error.timeout",
+        "请求超时 {}: {}",
+        "Request timed out {}: {}",
+    ),
+    (
+        "error.checksum_mismatch",
+        "校验和不匹配 {}: 期望 {}, 实际 {}",
+        "Checksum mismatch {}: expected {}, got {}",
+    ),
+    (
+        "error.unsupported_platform",
+        "{} 暂不支持当前平台: {}",
+        "{} does not support the current platform: {}",
+    ),
+    (
+        "error.unsupported_os",
+        "{} 只支持以下系统: {}, 当前系统: {}",
+        "{} only supports: {}, current system: {}",
+    ),
+    (
+        "error.unsupported_environment",
+        "不支持的环境: {}",
+        "Unsupported environment: {}",
+    ),
+    (
+        "error.package_not_found",
+        "未找到 {} {} 在平台 {} 上的安装包: {}",
+        "No package found for {} {} on {}: {}",
+    ),
+    (
+        "error.unknown_version",
+        "{} 不是 {} 的已知版本, 最接近的可用版本为: {}",
+        "{} is not a known version of {}, the closest available version is: {}",
+    ),
+    (
+        "error.no_matching_version",
+        "{} 的已知版本中没有满足 {} 的版本",
+        "No known version of {} satisfies {}",
+    ),
+    (
+        "error.invalid_version",
+        "无效的版本号: {}",
+        "Invalid version: {}",
+    ),
+    (
+        "error.config_invalid",
+        "配置校验失败:\n  - {}",
+        "Invalid configuration:\n  - {}",
+    ),
+    (
+        "error.extract_failed",
+        "解压失败 {}: {}",
+        "Failed to extract {}: {}",
+    ),
+    (
+        "error.install_in_progress",
+        "{} {} 正在由其他进程安装, 请等待其完成后重试",
+        "{} {} is already being installed by another process, retry after it finishes",
+    ),
+    (
+        "error.insufficient_space",
+        "磁盘空间不足: 解压需要约 {}, {} 所在磁盘只剩 {}",
+        "Not enough disk space: extracting needs about {}, the disk of {} has only {} left",
+    ),
+    (
+        "error.deadline_exceeded",
+        "操作超过 {} 秒没有完成, 已取消并清理未完成的安装",
+        "The operation did not finish within {} seconds, cancelled and cleaned up the unfinished install",
+    ),
+];
+
+lazy_static! {
+    static ref CATALOG: HashMap<&'static str, (&'static str, &'static str)> = MESSAGES
+        .iter()
+        .map(|(key, zh, en)| (*key, (*zh, *en)))
+        .collect();
+}
+
+/// 按当前语言查找消息并替换参数, 未知的 key 原样返回
+pub fn translate(key: &str, args: &[String]) -> String {
+    translate_in(current_lang(), key, args)
+}
+
+fn translate_in(lang: Lang, key: &str, args: &[String]) -> String {
+    let Some((zh, en)) = CATALOG.get(key) else {
+        log::warn!("未知的消息: {}", key);
+        return key.to_string();
+    };
+    let template = match lang {
+        Lang::Zh => zh,
+        Lang::En => en,
+    };
+
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+    let mut message = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        if let Some(arg) = args.next() {
+            message.push_str(arg);
+        }
+        message.push_str(part);
+    }
+    message
+}
+
+/// 按当前语言格式化消息, 如 `t!("install.start", name, version)`
+#[macro_export]
+macro_rules! t {
+    ($key:expr $(, $arg:expr)* $(,)?) => {
+        $crate::i18n::translate($key, &[$($arg.to_string()),*])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate() {
+        let args = ["java".to_string(), "21".to_string()];
+        assert_eq!(
+            translate_in(Lang::Zh, "install.start", &args),
+            "开始安装 java: 21..."
+        );
+        assert_eq!(
+            translate_in(Lang::En, "install.start", &args),
+            "Installing java: 21..."
+        );
+        assert_eq!(translate_in(Lang::En, "no.such.key", &[]), "no.such.key");
+
+        assert_eq!(Lang::from_locale("zh_CN.UTF-8"), Lang::Zh);
+        assert_eq!(Lang::from_locale("C"), Lang::Zh);
+        assert_eq!(Lang::from_locale("C.UTF-8"), Lang::Zh);
+        assert_eq!(Lang::from_locale("POSIX"), Lang::Zh);
+        assert_eq!(Lang::from_locale("en_US.UTF-8"), Lang::En);
+        assert_eq!(Lang::from_locale("de_DE.UTF-8"), Lang::En);
+
+        // key 不重复, 中英文的参数个数一致
+        assert_eq!(CATALOG.len(), MESSAGES.len());
+        for (key, zh, en) in MESSAGES {
+            assert_eq!(
+                zh.matches("{}").count(),
+                en.matches("{}").count(),
+                "{}",
+                key
+            );
+        }
+    }
+}
@@ -1,117 +1,228 @@
-use std::{collections::HashMap, env::consts, fs, path::PathBuf};
+use std::{
+    collections::HashMap,
+    env::consts,
+    fs::{self, File, TryLockError},
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::{
-    check::is_downloaded,
-    download::{copy_file_to_dir, download_packages},
-    env_config::{Environment, ENV_CONFIG},
-    environment::{configure_environment, switch_version},
-    zip::{auto_unzip, DEFAULT_FORMAT},
-    ChooseEnvironment,
+    cache_index::{self, verify_cached},
+    check::{
+        available_space, check_active_on_path, check_version, detect_installed_version,
+        get_cached_package, version_matches, write_install_marker, PathCheck,
+    },
+    database::init_database,
+    download::{
+        download_cache_size, get_download_cache_dir, is_cached_package, is_file_url, is_streamable,
+        local_package, offline_cache_error,
+    },
+    env_config::{EnvConfig, Environment, InstalledEnvironment, PortableConfig},
+    environment::{
+        cancelled, configure_environment, get_install_dir, get_version_arg, prompt_error,
+        resolve_bin_dir, run_post_install, switch_version,
+    },
+    error::EnvError,
+    installer::{get_installer_type, run_installer, set_executable},
+    interrupt::PendingCleanup,
+    options::{
+        get_source, get_target_arch, get_target_os, is_assume_yes, is_dry_run, is_force,
+        is_keep_archive, is_no_cache, is_no_switch, is_offline, is_only_download, set_no_switch,
+    },
+    output::{emit_error, emit_event, is_human_output},
+    pipeline::{Activator, Downloader, Extractor, InstallContext, Package},
+    repository::{lookup_package, resolve_with_command, DEFAULT_INDEX_URL},
+    search::{known_versions, resolve_latest, LATEST_VERSION},
+    status, t, version,
+    zip::estimate_unpacked_size,
 };
 use anyhow::{anyhow, Result};
 use colored::Colorize;
-use dialoguer::{theme::ColorfulTheme, MultiSelect, Select};
+use dialoguer::{theme::ColorfulTheme, Confirm, MultiSelect, Select};
+use indicatif::HumanBytes;
 use lazy_static::lazy_static;
+use log::debug;
+use serde::Deserialize;
 use serde_json::{json, Value};
+use tokio::{sync::Semaphore, task::JoinSet};
 
-lazy_static! {
-    static ref REPOSITORY_MAP: HashMap<String, Value> = {
-        let content = include_str!("../.env.repository.json");
-        serde_json::from_str(&content).unwrap()
-    };
-
-    static ref PLATFORM: HashMap<String, HashMap<String, Vec<String>>> = serde_json::from_value(json!({
-        "windows-x64": {
-            "alias": ["win64", "x86_64-pc-windows", "amd64", "x64"],
-            "file_types": ["zip", "msi", "exe", "7z"]
-        },
-        "linux-x64": {
-            "alias": ["linux64", "x86_64-linux", "amd64"],
-            "file_types": ["tgz", "xz", "gz"]
-        },
-        "linux-aarch64": {
-            "alias": ["linux-arm64", "aarch64-linux"],
-            "file_types": ["tgz", "xz", "gz"]
-        },
-        "macos-x64": {
-            "alias": ["darwin64", "x86_64-darwin", "amd64"],
-            "file_types": ["gz", "pkg", "dmg"]
-        },
-        "macos-aarch64": {
-            "alias": ["darwin-arm64", "aarch64-darwin", "arm64"],
-            "file_types": ["gz", "pkg", "dmg"]
-        }
-    })).unwrap();
+/// 同时下载安装包的最大数量
+const MAX_CONCURRENT_DOWNLOADS: usize = 3;
 
+lazy_static! {
+    static ref PLATFORM: HashMap<String, HashMap<String, Vec<String>>> =
+        serde_json::from_value(json!({
+            "windows-x64": {
+                "alias": ["win64", "x86_64-pc-windows", "amd64", "x64"],
+                "file_types": ["zip", "msi", "exe", "7z"]
+            },
+            "linux-x64": {
+                "alias": ["linux64", "x86_64-linux", "amd64"],
+                "file_types": ["tgz", "xz", "gz"]
+            },
+            "linux-aarch64": {
+                "alias": ["linux-arm64", "aarch64-linux"],
+                "file_types": ["tgz", "xz", "gz"]
+            },
+            "linux-armv7": {
+                "alias": ["linux-armv7l", "armv7-linux", "linux-arm"],
+                "file_types": ["tgz", "xz", "gz"]
+            },
+            "macos-x64": {
+                "alias": ["darwin64", "x86_64-darwin", "amd64"],
+                "file_types": ["gz", "pkg", "dmg"]
+            },
+            "macos-aarch64": {
+                "alias": ["darwin-arm64", "aarch64-darwin", "arm64"],
+                "file_types": ["gz", "pkg", "dmg"]
+            }
+        }))
+        .unwrap();
+    /// 平台对应的 Rust 目标三元组, 用于 rustup-init 的下载地址
+    static ref TARGET_TRIPLE: HashMap<String, String> = serde_json::from_value(json!({
+        "windows-x64": "x86_64-pc-windows-msvc",
+        "linux-x64": "x86_64-unknown-linux-gnu",
+        "linux-aarch64": "aarch64-unknown-linux-gnu",
+        "linux-armv7": "armv7-unknown-linux-gnueabihf",
+        "macos-x64": "x86_64-apple-darwin",
+        "macos-aarch64": "aarch64-apple-darwin"
+    }))
+    .unwrap();
     static ref ARCH_IDENTIFIER: HashMap<String, Vec<String>> = serde_json::from_value(json!({
         "x64": ["x64", "amd64", "x86_64"],
-        "aarch64": ["aarch64", "arm64"]
-    })).unwrap();
-
+        "aarch64": ["aarch64", "arm64"],
+        "armv7": ["armv7", "armv7l", "arm"]
+    }))
+    .unwrap();
     static ref OS_IDENTIFIER: HashMap<String, Vec<String>> = serde_json::from_value(json!({
         "windows": ["windows", "win"],
         "linux": ["linux"],
         "macos": ["macos", "darwin", "osx"]
-    })).unwrap();
+    }))
+    .unwrap();
 }
 
-pub async fn choose_and_install_from(env: &Environment, install_dir: &PathBuf) -> Result<()> {
-    let args = configure_environment(env);
+pub async fn choose_and_install_from(env: &Environment, install_dir: &Path) -> Result<()> {
+    let args = configure_environment(env)?;
 
     install_environment(env, &args, install_dir).await?;
 
     Ok(())
 }
 
-/// 解压并重命名目录为指定的版本目录
+/// 解压到临时目录后重命名为指定的版本目录
 fn extract_to_version_dir(
+    extractor: &impl Extractor,
     filename: &str,
-    install_dir: &PathBuf,
-    name: &str,
-    version: &str,
+    temp_dir: &Path,
+    version_dir: &Path,
 ) -> Result<()> {
     // 创建临时解压目录
-    let temp_dir = install_dir.join("temp");
     if temp_dir.exists() {
-        fs::remove_dir_all(&temp_dir)?;
+        fs::remove_dir_all(temp_dir)?;
+    }
+    if let Some(work_dir) = temp_dir.parent() {
+        fs::create_dir_all(work_dir)?;
+        check_disk_space(filename, work_dir)?;
+    }
+    fs::create_dir_all(temp_dir)?;
+
+    // 直接从下载缓存解压, 解压失败时安装包仍保留在缓存中
+    status!("{}", t!("install.extracting", temp_dir.display()));
+    if let Err(e) = extractor.extract(filename, temp_dir) {
+        let _ = fs::remove_dir_all(temp_dir);
+        return Err(e);
     }
-    fs::create_dir_all(&temp_dir)?;
 
-    // 复制到临时目录并解压
-    println!("正在解压到临时目录: {}", temp_dir.display());
-    let filename = copy_file_to_dir(filename, temp_dir.to_str().unwrap())?;
-    auto_unzip(&filename, temp_dir.to_str().unwrap())?;
+    replace_version_dir(temp_dir, version_dir)
+}
 
-    // 创建版本目录
-    let version_dir = install_dir.join(format!("{}-{}", name.to_lowercase(), version));
+/// 用临时目录中解压的内容替换版本目录, 完成后删除临时目录
+fn replace_version_dir(temp_dir: &Path, version_dir: &Path) -> Result<()> {
     if version_dir.exists() {
-        fs::remove_dir_all(&version_dir)?;
+        fs::remove_dir_all(version_dir)?;
     }
 
-    // 检查解压后的内容是否有一个主目录
-    let mut entries = fs::read_dir(&temp_dir)?;
-    let first_entry = entries.next();
+    move_to_version_dir(temp_dir, version_dir)?;
 
-    if let Some(Ok(entry)) = first_entry {
-        let path = entry.path();
-        if path.is_dir() && entries.next().is_none() {
-            // 只有一个目录，直接重命名
-            fs::rename(path, &version_dir)?;
-        } else {
-            // 多个文件或直接在根目录，移动所有内容
-            fs::create_dir_all(&version_dir)?;
-            for entry in fs::read_dir(&temp_dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                let target = version_dir.join(path.file_name().unwrap());
-                fs::rename(path, target)?;
-            }
+    // 清理临时目录
+    fs::remove_dir_all(temp_dir)?;
+
+    Ok(())
+}
+
+/// 解压前检查安装目录所在磁盘的剩余空间, 避免解压到一半时磁盘写满;
+/// 无法估算解压后大小的格式不检查
+fn check_disk_space(filename: &str, install_dir: &Path) -> Result<()> {
+    let Some(needed) = estimate_unpacked_size(filename) else {
+        return Ok(());
+    };
+    let Some(available) = available_space(install_dir) else {
+        return Ok(());
+    };
+
+    debug!(
+        "解压需要约 {}, 可用空间 {}",
+        HumanBytes(needed),
+        HumanBytes(available)
+    );
+    if needed > available {
+        return Err(EnvError::InsufficientSpace {
+            dir: install_dir.display().to_string(),
+            needed: HumanBytes(needed).to_string(),
+            available: HumanBytes(available).to_string(),
         }
+        .into());
     }
+    Ok(())
+}
 
-    // 清理临时目录
-    fs::remove_dir_all(&temp_dir)?;
+/// 单个可执行文件的安装包以环境名称复制到版本目录的 bin 目录
+fn install_binary(filename: &str, name: &str, version_dir: &Path) -> Result<()> {
+    let bin_dir = version_dir.join("bin");
+    fs::create_dir_all(&bin_dir)?;
+
+    let target = bin_dir.join(format!("{}{}", name.to_lowercase(), consts::EXE_SUFFIX));
+    status!("{}", t!("install.copy_binary", filename, target.display()));
+    fs::copy(filename, &target)?;
+    set_executable(target.to_str().unwrap())
+}
+
+/// 将解压目录中的内容移动到版本目录,
+/// 忽略隐藏文件后只有一个目录时直接将该目录重命名为版本目录
+fn move_to_version_dir(extract_dir: &Path, version_dir: &Path) -> Result<()> {
+    let entries = fs::read_dir(extract_dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    if entries.is_empty() {
+        return Err(anyhow!(t!("install.empty_package")));
+    }
+
+    let visible: Vec<&PathBuf> = entries
+        .iter()
+        .filter(|path| {
+            !path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        })
+        .collect();
 
+    if let [dir] = visible.as_slice() {
+        if dir.is_dir() {
+            // 只有一个主目录，直接重命名
+            fs::rename(dir, version_dir)?;
+            return Ok(());
+        }
+    }
+
+    // 多个文件或直接在根目录，移动所有内容
+    fs::create_dir_all(version_dir)?;
+    for path in entries {
+        let target = version_dir.join(path.file_name().unwrap());
+        fs::rename(path, target)?;
+    }
     Ok(())
 }
 
@@ -122,213 +233,2229 @@ pub fn is_supported_env(env: &Environment) -> bool {
 pub async fn install_environment(
     env: &Environment,
     args: &Value,
-    install_dir: &PathBuf,
+    install_dir: &Path,
 ) -> Result<()> {
+    install_with(&InstallContext::new(install_dir)?, env, args).await?;
+    remember_args(env, args);
+    Ok(())
+}
+
+/// 记住本次安装选择的参数, 下次交互安装时作为默认值, 保存失败只提示不影响安装结果
+fn remember_args(env: &Environment, args: &Value) {
+    if is_dry_run() || is_download_only() {
+        return;
+    }
+    if let Err(e) = EnvConfig::save_last_args(env, args) {
+        status!("{}", t!("install.save_args_failed", e).yellow());
+    }
+}
+
+/// 使用指定的下载、解压及切换版本实现安装环境
+pub async fn install_with<D, E, A>(
+    ctx: &InstallContext<D, E, A>,
+    env: &Environment,
+    args: &Value,
+) -> Result<()>
+where
+    D: Downloader,
+    E: Extractor,
+    A: Activator,
+{
     if !is_supported_env(env) {
-        println!("不支持的环境: {}", env.name.red());
-        std::process::exit(1);
+        return Err(EnvError::UnsupportedEnvironment(env.name.clone()).into());
+    }
+
+    let version = resolve_version(env, args).await?;
+    let _lock = lock_install(ctx, &env.name, &version)?;
+
+    // 只下载到缓存, 其他平台的安装包也无法在本机使用
+    if is_download_only() {
+        if let Some(package) = download_environment(ctx, env, &version).await? {
+            print_download_only(&package);
+        }
+        return Ok(());
+    }
+
+    // 版本已登记为安装目录之外的安装时直接切换, 不重新下载
+    let external = ctx.config.external_home(&env.name, &version);
+    if !is_force() && external.is_some_and(|home| home.is_dir()) {
+        return finish_install(ctx, env, &version, args, None);
+    }
+
+    if !ctx.is_installed(env, &version) && adopt_existing(env, &version)? {
+        return Ok(());
     }
 
-    let version = args.get("version").unwrap().as_str().unwrap();
+    let package = download_environment(ctx, env, &version).await?;
+    finish_install(ctx, env, &version, args, package.as_ref())
+}
+
+/// 获取同一版本的安装锁, 其他进程正在安装时直接报错而不是等待,
+/// 返回的锁被释放(安装完成或出错)时删除锁文件并解锁, dry-run 时不加锁
+fn lock_install<D, E, A>(
+    ctx: &InstallContext<D, E, A>,
+    name: &str,
+    version: &str,
+) -> Result<Option<InstallLock>> {
+    if is_dry_run() {
+        return Ok(None);
+    }
+
+    let path = ctx.lock_path(name, version);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // 加锁前持有锁的进程可能刚好删除了锁文件, 此时锁住的是已删除的文件, 需要重新创建
+    for _ in 0..3 {
+        let file = File::create(&path)?;
+        match file.try_lock() {
+            Ok(()) if is_same_file(&file, &path) => {
+                return Ok(Some(InstallLock { file, path }));
+            }
+            Ok(()) => continue,
+            Err(TryLockError::WouldBlock) => break,
+            Err(TryLockError::Error(e)) => {
+                return Err(anyhow!(t!("install.lock_failed", path.display(), e)))
+            }
+        }
+    }
+    Err(EnvError::InstallInProgress {
+        name: name.to_string(),
+        version: version.to_string(),
+    }
+    .into())
+}
+
+/// 安装锁, 释放时先删除锁文件再解锁, 安装目录下不会残留锁文件
+#[derive(Debug)]
+struct InstallLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl Drop for InstallLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        let _ = self.file.unlock();
+    }
+}
+
+/// 打开的文件是否仍是路径指向的文件
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(opened), Ok(current)) => opened.dev() == current.dev() && opened.ino() == current.ino(),
+        _ => false,
+    }
+}
+
+/// Windows 下打开的文件被删除后路径不再可用
+#[cfg(not(unix))]
+fn is_same_file(_file: &File, path: &Path) -> bool {
+    path.exists()
+}
+
+/// 是否只下载安装包, 不解压也不切换版本
+fn is_download_only() -> bool {
+    is_only_download() || is_cross_platform()
+}
+
+fn print_download_only(package: &Package) {
+    let Package::Archive(filename) = package else {
+        return;
+    };
+    let message = if is_cross_platform() {
+        let (os, arch) = get_os_arch();
+        t!("install.cross_platform", os, arch, filename)
+    } else {
+        t!("install.download_only", filename)
+    };
+    status!("{}", message.green());
+}
+
+/// 读取安装参数中的版本, `latest` 解析为最新的正式版本
+async fn resolve_version(env: &Environment, args: &Value) -> Result<String> {
+    let version = get_version_arg(env, args)?;
+    if version != LATEST_VERSION {
+        return Ok(version);
+    }
+
+    let version = resolve_latest(env).await?;
+    status!("{}", t!("install.latest", env.name, version));
+    Ok(version)
+}
+
+/// 系统中已安装匹配的版本时, 询问是否直接登记使用而不重新下载
+fn adopt_existing(env: &Environment, version: &str) -> Result<bool> {
+    let name = env.name.as_str();
+    // JSON 模式及 --yes 时不交互, 跨平台下载时本机的安装无关
+    if !is_human_output() || is_assume_yes() || is_force() || is_download_only() {
+        return Ok(false);
+    }
+
+    let detected = match detect_installed_version(name) {
+        Some(detected) if version_matches(version, &detected.version) => detected,
+        _ => return Ok(false),
+    };
+
+    let adopt = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(t!(
+            "install.adopt_prompt",
+            name,
+            detected.version,
+            detected.home_dir.display()
+        ))
+        .default(true)
+        .interact_opt()
+        .map_err(prompt_error)?
+        .ok_or_else(cancelled)?;
+    if !adopt {
+        return Ok(false);
+    }
+
+    if is_dry_run() {
+        status!(
+            "{}",
+            t!(
+                "dry_run.adopt",
+                name,
+                detected.version,
+                detected.home_dir.display()
+            )
+        );
+        emit_event("adopt", name, &detected.version, "dry-run");
+        return Ok(true);
+    }
+
+    EnvConfig::adopt_version(name, &detected.version, &detected.home_dir)
+        .inspect_err(|e| emit_error("adopt", name, &detected.version, e))?;
+    status!(
+        "{}",
+        t!(
+            "install.adopt_done",
+            name,
+            detected.version,
+            detected.home_dir.display()
+        )
+        .green()
+    );
+    emit_event("adopt", name, &detected.version, "ok");
+    Ok(true)
+}
+
+/// 是否边下载边解压: `--no-cache` 且安装包为支持顺序解压的压缩包
+fn should_stream(env: &Environment, url: &str) -> bool {
+    is_no_cache()
+        && !is_download_only()
+        && !env.binary
+        && get_installer_type(url).is_none()
+        && !is_file_url(url)
+        && is_streamable(url)
+}
+
+/// 下载环境安装包, 已安装或 dry-run 时不下载并返回 None
+async fn download_environment<D, E, A>(
+    ctx: &InstallContext<D, E, A>,
+    env: &Environment,
+    version: &str,
+) -> Result<Option<Package>>
+where
+    D: Downloader,
+{
     let name = env.name.as_str();
 
-    let is_downloaded = is_downloaded(name, version);
+    let force = is_force();
+    let download_only = is_download_only();
+    if !download_only && ctx.is_installed(env, version) && !force {
+        emit_event("download", name, version, "skipped");
+        return Ok(None);
+    }
+    let version_dir = ctx.version_dir(name, version);
+    if !download_only && version_dir.exists() && !force {
+        status!(
+            "{}",
+            t!("install.incomplete", version_dir.display()).yellow()
+        );
+    }
+
+    status!("{}", t!("install.start", name, version).green());
+    // 使用本地安装包时不解析下载地址
+    if let Some(source) = get_source() {
+        let filename =
+            local_package(&source).inspect_err(|e| emit_error("resolve", name, version, e))?;
+        if is_dry_run() {
+            status!("{}", t!("dry_run.local", filename, version_dir.display()));
+            emit_event("download", name, version, "dry-run");
+            return Ok(None);
+        }
+        status!("{}", t!("install.local", filename));
+        emit_event("download", name, version, "local");
+        return Ok(Some(Package::Archive(filename)));
+    }
+
+    // 下载安装包
+    let package_urls = choose_package(&ctx.config, env, version)
+        .inspect_err(|e| emit_error("resolve", name, version, e))?;
+    let package_url = package_urls[0].as_str();
+    status!("{}", t!("install.package_url", package_url));
+    for mirror in &package_urls[1..] {
+        status!("{}", t!("install.mirror_url", mirror));
+    }
+
+    let platform = get_platform();
+    let cache_dir = ctx.cache_dir();
+    if force {
+        // 只下载时保留本机已安装的版本
+        let installed_dir = (!download_only).then_some(version_dir.as_path());
+        remove_existing_install(&cache_dir, name, version, installed_dir, &package_urls)?;
+    }
+    // 优先按缓存索引查找, 再按下载地址推断的文件名查找
+    let cached = cache_index::find_in(&cache_dir, name, version, &platform)
+        .map(|path| path.to_str().unwrap().to_string())
+        .or_else(|| {
+            package_urls
+                .iter()
+                .find_map(|url| get_cached_package(&cache_dir, url))
+        });
+
+    if is_dry_run() {
+        match &cached {
+            Some(filename) => status!("{}", t!("dry_run.cached", filename)),
+            None => status!("{}", t!("dry_run.download", package_url)),
+        }
+        if download_only {
+            status!("{}", t!("dry_run.download_only"));
+            emit_event("download", name, version, "dry-run");
+            return Ok(None);
+        }
+        match get_installer_type(package_url) {
+            _ if cached.is_none() && should_stream(env, package_url) => {
+                status!("{}", t!("dry_run.stream", version_dir.display()))
+            }
+            _ if env.binary => status!(
+                "{}",
+                t!("dry_run.copy_binary", version_dir.join("bin").display())
+            ),
+            Some(installer) => status!(
+                "{}",
+                t!("dry_run.installer", installer, version_dir.display())
+            ),
+            None => status!("{}", t!("dry_run.extract", version_dir.display())),
+        }
+        run_post_install(env, version, &version_dir)?;
+        emit_event("download", name, version, "dry-run");
+        emit_event("extract", name, version, "dry-run");
+        return Ok(None);
+    }
+
+    if let Some(filename) = cached {
+        // 索引中记录了 sha256 的安装包先校验, 文件未变化时不重新计算
+        cache_index::check_in(&cache_dir, name, version, &platform, false)
+            .inspect_err(|e| emit_error("verify", name, version, e))?;
+        status!("{}", t!("install.cached", filename));
+        emit_event("download", name, version, "cached");
+        return Ok(Some(Package::Archive(filename)));
+    }
+
+    // 离线模式下缓存中没有时直接报错, 不尝试访问网络
+    if is_offline() {
+        let err = offline_cache_error(&cache_dir, package_url);
+        emit_error("download", name, version, &err);
+        return Err(err);
+    }
+
+    // 依次尝试各个地址, 先确认安装包存在, 避免创建安装目录后才发现无法下载
+    let mut last_err = None;
+    for url in &package_urls {
+        match ctx.downloader.exists(url).await {
+            Ok(true) => {}
+            Ok(false) => {
+                debug!("安装包不存在: {}", url);
+                continue;
+            }
+            Err(e) => {
+                status!("{}", t!("install.url_unavailable", e).yellow());
+                last_err = Some(("resolve", e));
+                continue;
+            }
+        }
+        emit_event("resolve", name, version, "ok");
+
+        if should_stream(env, url) {
+            match stream_to_temp_dir(ctx, name, version, url).await {
+                Ok(temp_dir) => {
+                    status!("{}", t!("install.streamed", temp_dir.display()));
+                    emit_event("download", name, version, "ok");
+                    return Ok(Some(Package::Extracted(temp_dir)));
+                }
+                Err(e) => {
+                    status!("{}", t!("install.download_retry", e).yellow());
+                    last_err = Some(("download", e));
+                }
+            }
+            continue;
+        }
+
+        match ctx.downloader.download(url).await {
+            Ok(filename) => {
+                status!("{}", t!("install.downloaded", filename));
+                let file = Path::new(&filename);
+                if let Err(e) = cache_index::record_in(&cache_dir, name, version, &platform, file) {
+                    log::warn!("记录缓存索引失败: {}", e);
+                }
+                emit_event("download", name, version, "ok");
+                return Ok(Some(Package::Archive(filename)));
+            }
+            Err(e) => {
+                status!("{}", t!("install.download_retry", e).yellow());
+                last_err = Some(("download", e));
+            }
+        }
+    }
+
+    // 所有地址都不存在时报告找不到安装包, 否则报告最后一次的错误
+    let (stage, err) = last_err.unwrap_or_else(|| {
+        (
+            "resolve",
+            EnvError::PackageNotFound {
+                name: name.to_string(),
+                version: version.to_string(),
+                platform: get_platform(),
+                url: package_urls.join(", "),
+            }
+            .into(),
+        )
+    });
+    emit_error(stage, name, version, &err);
+    Err(err)
+}
+
+/// 边下载边解压到版本的临时目录, 失败时删除临时目录
+async fn stream_to_temp_dir<D, E, A>(
+    ctx: &InstallContext<D, E, A>,
+    name: &str,
+    version: &str,
+    url: &str,
+) -> Result<PathBuf>
+where
+    D: Downloader,
+{
+    let temp_dir = ctx.temp_dir(name, version);
+    if temp_dir.exists() {
+        fs::remove_dir_all(&temp_dir)?;
+    }
+    fs::create_dir_all(&temp_dir)?;
+
+    let _pending = PendingCleanup::new(&[&temp_dir]);
+    if let Err(e) = ctx.downloader.stream_extract(url, &temp_dir).await {
+        let _ = fs::remove_dir_all(&temp_dir);
+        return Err(e);
+    }
+    Ok(temp_dir)
+}
+
+/// `--force` 时删除已安装的版本目录及缓存的安装包, 保证重新下载安装
+fn remove_existing_install(
+    cache_dir: &Path,
+    name: &str,
+    version: &str,
+    version_dir: Option<&Path>,
+    package_urls: &[String],
+) -> Result<()> {
+    let platform = get_platform();
+    // 不同镜像的文件名可能相同, 对应同一个缓存文件
+    let mut cached: Vec<String> = package_urls
+        .iter()
+        .filter_map(|url| get_cached_package(cache_dir, url))
+        .chain(
+            cache_index::find_in(cache_dir, name, version, &platform)
+                .map(|path| path.to_str().unwrap().to_string()),
+        )
+        .collect();
+    cached.sort();
+    cached.dedup();
+    let version_dir = version_dir.filter(|dir| dir.exists());
 
-    if !is_downloaded {
-        println!("{}", format!("开始安装 {}: {}...", name, version).green());
-        // 下载安装包
-        let package_url = choose_package(env, version);
-        println!("下载地址: {}", package_url);
+    if is_dry_run() {
+        if let Some(version_dir) = version_dir {
+            status!("{}", t!("dry_run.remove_version", version_dir.display()));
+        }
+        for filename in cached {
+            status!("{}", t!("dry_run.remove_cached", filename));
+        }
+        return Ok(());
+    }
+
+    if let Some(version_dir) = version_dir {
+        status!("{}", t!("install.remove_version", version_dir.display()));
+        fs::remove_dir_all(version_dir)?;
+    }
+    for filename in cached {
+        status!("{}", t!("install.remove_cached", filename));
+        fs::remove_file(filename)?;
+    }
+    cache_index::forget_in(cache_dir, name, version, &platform)
+}
 
-        let filename = download_packages(&package_url).await?;
-        println!("下载完成: {}", filename);
+/// 解压下载好的安装包并切换版本, 会修改 PATH, 多个环境时需要串行执行
+fn finish_install<D, E, A>(
+    ctx: &InstallContext<D, E, A>,
+    env: &Environment,
+    version: &str,
+    args: &Value,
+    package: Option<&Package>,
+) -> Result<()>
+where
+    E: Extractor,
+    A: Activator,
+{
+    let name = env.name.as_str();
+    let version_dir = ctx.version_dir(name, version);
 
+    if let Some(package) = package {
         // 创建安装目录
-        let install_dir = install_dir.join(name);
+        let install_dir = ctx.install_dir.join(name);
         if !install_dir.exists() {
             fs::create_dir_all(&install_dir)?;
         }
 
-        // 解压并重命名到版本目录
-        extract_to_version_dir(&filename, &install_dir, name, version)?;
+        // 写入安装标记前被中断时, 删除临时解压目录及不完整的版本目录
+        let temp_dir = ctx.temp_dir(name, version);
+        let _pending = PendingCleanup::new(&[&temp_dir, &version_dir]);
+
+        let filename = match package {
+            Package::Archive(filename) => filename.as_str(),
+            // 已边下载边解压到临时目录, 只需移动到版本目录
+            Package::Extracted(temp_dir) => {
+                replace_version_dir(temp_dir, &version_dir)
+                    .inspect_err(|e| emit_error("extract", name, version, e))?;
+                emit_event("extract", name, version, "ok");
+                ""
+            }
+        };
+
+        match get_installer_type(filename) {
+            _ if filename.is_empty() => {}
+            // 安装包本身就是可执行文件
+            _ if env.binary => {
+                install_binary(filename, name, &version_dir)
+                    .inspect_err(|e| emit_error("install", name, version, e))?;
+                emit_event("install", name, version, "ok");
+            }
+            // 安装程序需要直接运行
+            Some(installer) => {
+                run_installer(installer, env, version, filename, &version_dir)
+                    .inspect_err(|e| emit_error("install", name, version, e))?;
+                emit_event("install", name, version, "ok");
+            }
+            // 解压并重命名到版本目录
+            None => {
+                extract_to_version_dir(&ctx.extractor, filename, &temp_dir, &version_dir)
+                    .inspect_err(|e| emit_error("extract", name, version, e))?;
+                emit_event("extract", name, version, "ok");
+            }
+        }
+
+        // 安装成功后, 未开启 keep_downloads 或 --keep-archive 时删除缓存的安装包,
+        // 本地安装包不在下载缓存中, 始终保留
+        if is_cached_package(&ctx.cache_dir(), filename)
+            && !ctx.config.keep_downloads
+            && !is_keep_archive()
+        {
+            fs::remove_file(filename)?;
+        }
+
+        // 安装后命令失败时版本本身已可用, 只提示用户手动处理
+        match run_post_install(env, version, &version_dir) {
+            Ok(()) if !env.post_install.is_empty() => {
+                emit_event("post_install", name, version, "ok")
+            }
+            Ok(()) => {}
+            Err(e) => {
+                status!("{}", t!("install.post_install_failed", e).yellow());
+                emit_error("post_install", name, version, &e);
+            }
+        }
+
+        // 全部步骤完成后才写入标记, 中断的安装下次会重新安装
+        write_install_marker(&version_dir, version)?;
     }
 
-    // 切换版本
-    switch_version(env, version)?;
+    // 切换版本, `--no-switch` 时只登记已安装的版本
+    if is_no_switch() {
+        ctx.activator
+            .register(env, version, &version_dir)
+            .inspect_err(|e| emit_error("register", name, version, e))?;
+    } else {
+        ctx.activator
+            .activate(env, version, &version_dir)
+            .inspect_err(|e| emit_error("switch", name, version, e))?;
+    }
+
+    // 数据库需要初始化数据目录才能启动
+    init_database(env, version, args).inspect_err(|e| emit_error("init", name, version, e))?;
 
     Ok(())
 }
 
 /// 显示交互式选择菜单并安装选中的环境
-pub async fn choose_and_install(install_dir: &PathBuf) -> Result<()> {
-    let environments = &ENV_CONFIG.environments;
+pub async fn choose_and_install(install_dir: &Path) -> Result<()> {
+    let env_config = EnvConfig::current()?;
+    let environments: Vec<&Environment> = env_config
+        .environments
+        .iter()
+        .filter(|e| is_supported_env(e))
+        .collect();
 
     // 设置选项
     let items: Vec<String> = environments
         .iter()
-        .filter(|e| is_supported_env(e))
         .map(|e| format!("{} - {}", e.name, e.description))
         .collect();
 
-    println!("使用空格键选择/取消选择，回车键确认");
-    println!("↑/↓ 或 j/k 移动光标");
+    status!("{}", t!("install.select_hint"));
+    status!("{}", t!("install.move_hint"));
 
     let selections = MultiSelect::with_theme(&ColorfulTheme::default())
-        .with_prompt("选择要安装的环境")
+        .with_prompt(t!("install.select_prompt"))
         .items(&items)
         .defaults(&vec![false; items.len()])
-        .interact()?;
+        .interact_opt()
+        .map_err(prompt_error)?
+        .ok_or_else(cancelled)?;
 
     if selections.is_empty() {
-        println!("未选择任何环境");
+        println!("{}", t!("install.none_selected"));
         return Ok(());
     }
 
     // 配置选中的环境
-    let mut args = Vec::new();
+    let ctx = InstallContext::new(install_dir)?;
+    let mut versions = Vec::new();
+    let mut selected_args = vec![];
     let mut selected_envs = vec![];
+    let mut results = vec![];
+    let mut locks = vec![];
 
     for &index in selections.iter() {
-        let env = &environments[index];
-        let arg = configure_environment(env);
-        args.push(arg);
+        let env = environments[index];
+        let arg = configure_environment(env).map_err(|e| abort_batch(&results, e))?;
+        let version = match resolve_version(env, &arg).await {
+            Ok(version) => version,
+            Err(e) => {
+                results.push(InstallResult::failed(
+                    env,
+                    "",
+                    &t!("install.resolve_failed"),
+                    &e,
+                ));
+                continue;
+            }
+        };
+        match lock_install(&ctx, &env.name, &version) {
+            Ok(lock) => locks.push(lock),
+            Err(e) => {
+                results.push(InstallResult::failed(
+                    env,
+                    &version,
+                    &t!("install.failed"),
+                    &e,
+                ));
+                continue;
+            }
+        }
+        if !is_download_only()
+            && !ctx.is_installed(env, &version)
+            && adopt_existing(env, &version).map_err(|e| abort_batch(&results, e))?
+        {
+            remember_args(env, &arg);
+            results.push(InstallResult::new(env, &version, InstallOutcome::Adopted));
+            continue;
+        }
+        versions.push(version);
+        selected_args.push(arg);
         selected_envs.push(env.clone());
     }
 
-    // 安装配置后的环境
-    for (index, arg) in args.iter().enumerate() {
-        let env = &selected_envs[index];
-        install_environment(env, arg, install_dir).await?;
-    }
+    // 并发下载安装包
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_DOWNLOADS));
+    let mut tasks = JoinSet::new();
+    for (index, (env, version)) in selected_envs.iter().zip(&versions).enumerate() {
+        let env = env.clone();
+        let version = version.clone();
+        let ctx = ctx.clone();
+        let semaphore = semaphore.clone();
 
-    Ok(())
-}
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.expect("下载队列已关闭");
+            (index, download_environment(&ctx, &env, &version).await)
+        });
+    }
 
-pub fn select_version(
-    prompt: &str,
-    versions: &[String],
-    current_version: Option<String>,
-) -> Result<(String, bool)> {
-    let current_version = current_version.unwrap_or(String::new());
+    let mut downloads: Vec<Option<Result<Option<Package>>>> =
+        selected_envs.iter().map(|_| None).collect();
+    while let Some(ret) = tasks.join_next().await {
+        match ret {
+            Ok((index, ret)) => downloads[index] = Some(ret),
+            // 异常退出的下载任务没有结果, 对应的环境在下面按下载失败处理
+            Err(e) => debug!("下载任务异常退出: {}", e),
+        }
+    }
 
-    let items = versions
-        .iter()
-        .map(|v| {
-            if v == &current_version {
-                format!("{} - ({})", v, "当前版本".green())
-            } else {
-                v.clone()
+    // 解压与切换版本会修改 PATH, 按选择顺序串行执行, 单个环境失败不影响其余环境
+    for (index, env) in selected_envs.iter().enumerate() {
+        let version = &versions[index];
+        let download = downloads[index]
+            .take()
+            .unwrap_or_else(|| Err(anyhow!("下载任务异常退出")));
+        let package = match download {
+            Ok(package) => package,
+            Err(e) => {
+                results.push(InstallResult::failed(
+                    env,
+                    version,
+                    &t!("install.download_failed"),
+                    &e,
+                ));
+                continue;
             }
-        })
-        .collect::<Vec<String>>();
-
-    // 默认选中当前版本否则默认选中第一个
-    let default_idx = versions
-        .iter()
-        .position(|v| v == &current_version)
-        .unwrap_or(0);
+        };
 
-    // 标志当前版本在安装版本中的位置
-    let pos = versions
-        .iter()
-        .position(|v| v == &current_version)
-        .unwrap_or(usize::MAX);
+        if is_download_only() {
+            if let Some(package) = &package {
+                print_download_only(package);
+            }
+            results.push(InstallResult::new(env, version, InstallOutcome::Downloaded));
+            continue;
+        }
 
-    let selected = Select::with_theme(&ColorfulTheme::default())
-        .with_prompt(prompt)
-        .default(default_idx)
-        .items(&items)
-        .interact()
-        .unwrap();
+        let args = &selected_args[index];
+        let result = match finish_install(&ctx, env, version, args, package.as_ref()) {
+            Ok(()) if package.is_some() => {
+                InstallResult::new(env, version, InstallOutcome::Installed)
+            }
+            Ok(()) => InstallResult::new(env, version, InstallOutcome::Switched),
+            Err(e) => InstallResult::failed(env, version, &t!("install.failed"), &e),
+        };
+        if !matches!(result.outcome, InstallOutcome::Failed(_)) {
+            remember_args(env, args);
+        }
+        results.push(result);
+    }
 
-    Ok((versions[selected].clone(), selected == pos))
+    print_install_summary(&results)
 }
 
-pub fn choose_version(env: &ChooseEnvironment) -> Result<()> {
-    let environments = &ENV_CONFIG.environments;
-    let name = env.get_name();
+/// `env dev --all` 安装的环境, 均使用配置中的默认版本(Java 与 Node.js 为 LTS 版本),
+/// Python 官方没有提供免安装的二进制包, 默认配置中不支持安装, 因此不在其中
+pub const DEFAULT_ENVIRONMENTS: [&str; 3] = ["java", "node", "rust"];
 
-    let choose_env = environments
-        .iter()
-        .find(|e| e.name.to_lowercase() == name.to_lowercase());
+/// 不交互, 依次安装默认的环境组合, 单个环境失败不影响其余环境
+pub async fn install_default_environments(install_dir: &Path) -> Result<()> {
+    let env_config = EnvConfig::current()?;
+    let mut items = vec![];
+    let mut results = vec![];
 
-    if let Some(env) = choose_env {
-        let is_supported = is_supported_env(env);
-        if !is_supported {
-            println!("不支持的环境: {}", env.name.red());
-            std::process::exit(1);
+    for name in DEFAULT_ENVIRONMENTS {
+        let Some(env) = env_config.find_environment(name) else {
+            continue;
+        };
+        if !is_supported_env(&env) {
+            status!("{}", t!("install.skip_unsupported", env.name).yellow());
+            continue;
         }
+        let Some(version) = env.get_default_version() else {
+            let err = anyhow!(t!("install.no_default_version"));
+            results.push(InstallResult::failed(
+                &env,
+                "",
+                &t!("install.resolve_failed"),
+                &err,
+            ));
+            continue;
+        };
+        items.push((env, json!({ "version": version })));
+    }
 
+    results.extend(install_batch(install_dir, &items).await?);
+    print_install_summary(&results)
+}
 
-        let versions = ENV_CONFIG.get_install_versions(name);
-        let current_version = ENV_CONFIG.get_current_version(name);
+/// `env dev --json-config` 读取的安装清单
+#[derive(Deserialize, Debug)]
+pub struct InstallSpec {
+    pub installs: Vec<InstallSpecEntry>,
+}
 
-        if versions.is_empty() {
-            println!("未找到 {} 的版本", name);
-            return Ok(());
-        }
+/// 清单中的单个环境, 除 name 外的字段直接作为安装参数
+#[derive(Deserialize, Debug)]
+pub struct InstallSpecEntry {
+    pub name: String,
 
-        let (selected_version, skip) = select_version("选择版本", &versions, current_version)?;
+    #[serde(flatten)]
+    pub args: serde_json::Map<String, Value>,
+}
 
-        // 相同版本不需要切换
-        if skip {
-            return Ok(());
-        }
-        // 切换版本
-        switch_version(env, &selected_version)?;
+/// 读取安装清单, 路径为 `-` 时从标准输入读取
+pub fn read_install_spec(path: &str) -> Result<InstallSpec> {
+    let content = if path == "-" {
+        let mut content = String::new();
+        std::io::stdin()
+            .read_to_string(&mut content)
+            .map_err(|e| anyhow!(t!("install.spec_stdin_failed", e)))?;
+        content
+    } else {
+        fs::read_to_string(path).map_err(|e| anyhow!(t!("install.spec_read_failed", path, e)))?
+    };
+
+    serde_json::from_str(&content).map_err(|e| anyhow!(t!("install.spec_invalid", e)))
+}
+
+/// 检查清单中的环境及版本, 任一项无效时不安装任何环境
+async fn resolve_install_spec(
+    env_config: &EnvConfig,
+    spec: &InstallSpec,
+) -> Result<Vec<(Environment, Value)>> {
+    let mut items = vec![];
+    for entry in &spec.installs {
+        let env = env_config
+            .find_environment(&entry.name)
+            .ok_or_else(|| anyhow!(t!("error.unsupported_environment", entry.name)))?;
+        if !is_supported_env(&env) {
+            return Err(EnvError::UnsupportedEnvironment(env.name.clone()).into());
+        }
+
+        // 未指定版本时使用配置中的默认版本
+        let version = match entry.args.get("version") {
+            Some(Value::String(version)) => version.clone(),
+            Some(_) => return Err(anyhow!(t!("install.spec_version_type", env.name))),
+            None => env
+                .get_default_version()
+                .ok_or_else(|| anyhow!(t!("install.spec_version_missing", env.name)))?,
+        };
+
+        let known = known_versions(env_config, &env, &version).await;
+        let mut args = entry.args.clone();
+        args.insert(
+            "version".to_string(),
+            json!(check_version(&env, &version, &known)?),
+        );
+        items.push((env, Value::Object(args)));
+    }
+    Ok(items)
+}
+
+/// 按安装清单依次安装, 不交互也不读取上次选择的参数
+pub async fn install_from_spec(spec: &InstallSpec, install_dir: &Path) -> Result<()> {
+    let items = resolve_install_spec(&EnvConfig::current()?, spec).await?;
+    if items.is_empty() {
+        status!("{}", t!("install.spec_empty"));
+        return Ok(());
+    }
+
+    let results = install_batch(install_dir, &items).await?;
+    print_install_summary(&results)
+}
+
+/// 依次安装多个环境, 单个环境失败不影响其余环境
+async fn install_batch(
+    install_dir: &Path,
+    items: &[(Environment, Value)],
+) -> Result<Vec<InstallResult>> {
+    let ctx = InstallContext::new(install_dir)?;
+    let mut results = vec![];
+
+    for (env, args) in items {
+        let version = get_version_arg(env, args).unwrap_or_default();
+        let installed = ctx.is_installed(env, &version);
+        let ret = install_with(&ctx, env, args).await;
+        if ret.is_ok() {
+            remember_args(env, args);
+        }
+        let result = match ret {
+            Ok(()) if installed => InstallResult::new(env, &version, InstallOutcome::Switched),
+            Ok(()) => InstallResult::new(env, &version, InstallOutcome::Installed),
+            Err(e) => InstallResult::failed(env, &version, &t!("install.failed"), &e),
+        };
+        results.push(result);
+    }
+    Ok(results)
+}
+
+/// 批量安装中单个环境的结果
+enum InstallOutcome {
+    /// 下载并安装后切换
+    Installed,
+    /// 已安装, 直接切换
+    Switched,
+    /// 登记系统中已有的安装
+    Adopted,
+    /// 只下载了安装包
+    Downloaded,
+    Failed(String),
+}
+
+struct InstallResult {
+    name: String,
+    version: String,
+    outcome: InstallOutcome,
+}
+
+impl InstallResult {
+    fn new(env: &Environment, version: &str, outcome: InstallOutcome) -> InstallResult {
+        InstallResult {
+            name: env.name.clone(),
+            version: version.to_string(),
+            outcome,
+        }
+    }
+
+    fn failed(
+        env: &Environment,
+        version: &str,
+        reason: &str,
+        err: &anyhow::Error,
+    ) -> InstallResult {
+        let outcome = InstallOutcome::Failed(format!("{}: {}", reason, err));
+        InstallResult::new(env, version, outcome)
+    }
+}
+
+/// 批量安装中途取消或出错时, 先输出已完成环境的结果, 避免这些记录随错误一起丢失
+fn abort_batch(results: &[InstallResult], err: anyhow::Error) -> anyhow::Error {
+    if !results.is_empty() {
+        let _ = print_install_summary(results);
+    }
+    err
+}
+
+/// 输出批量安装的汇总结果, 有环境失败时返回错误
+fn print_install_summary(results: &[InstallResult]) -> Result<()> {
+    status!("{}", t!("install.summary"));
+    for result in results {
+        let (mark, status) = match &result.outcome {
+            InstallOutcome::Installed if is_no_switch() => ("✅", t!("install.installed")),
+            InstallOutcome::Installed => ("✅", t!("install.installed_switched")),
+            InstallOutcome::Switched if is_no_switch() => {
+                ("✅", t!("install.installed_not_switched"))
+            }
+            InstallOutcome::Switched => ("✅", t!("install.switched")),
+            InstallOutcome::Adopted => ("✅", t!("install.adopted")),
+            InstallOutcome::Downloaded => ("✅", t!("install.downloaded_status")),
+            InstallOutcome::Failed(reason) => ("❌", reason.clone()),
+        };
+        status!("  {} {} {} {}", result.name, result.version, mark, status);
+
+        let event_status = match result.outcome {
+            InstallOutcome::Failed(_) => "error",
+            _ => "ok",
+        };
+        emit_event("summary", &result.name, &result.version, event_status);
+    }
+
+    let failed = results
+        .iter()
+        .filter(|r| matches!(r.outcome, InstallOutcome::Failed(_)))
+        .count();
+    if failed > 0 {
+        return Err(anyhow!(t!("install.failed_count", failed, results.len())));
+    }
+    Ok(())
+}
+
+pub fn select_version(
+    prompt: &str,
+    versions: &[String],
+    current_version: Option<String>,
+) -> Result<(String, bool)> {
+    let current_version = current_version.unwrap_or_default();
+
+    let items = versions
+        .iter()
+        .map(|v| {
+            if v == &current_version {
+                format!("{} - ({})", v, t!("version.current").green())
+            } else {
+                v.clone()
+            }
+        })
+        .collect::<Vec<String>>();
+
+    // 默认选中当前版本否则默认选中第一个
+    let default_idx = versions
+        .iter()
+        .position(|v| v == &current_version)
+        .unwrap_or(0);
+
+    // 标志当前版本在安装版本中的位置
+    let pos = versions
+        .iter()
+        .position(|v| v == &current_version)
+        .unwrap_or(usize::MAX);
+
+    let selected = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(default_idx)
+        .items(&items)
+        .interact_opt()
+        .map_err(prompt_error)?
+        .ok_or_else(cancelled)?;
+
+    Ok((versions[selected].clone(), selected == pos))
+}
+
+/// 导出自定义环境及已安装的版本到文件
+pub fn export_config(path: &Path) -> Result<()> {
+    let portable = EnvConfig::current()?.to_portable()?;
+    fs::write(path, serde_json::to_string_pretty(&portable)?)?;
+
+    status!("{}", t!("export.done", path.display()));
+    Ok(())
+}
+
+/// 导入配置, 合并自定义环境并询问是否安装缺失的版本
+pub async fn import_config(path: &Path, install_dir: &Path) -> Result<()> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow!(t!("import.read_failed", path.display(), e)))?;
+    let portable: PortableConfig = serde_json::from_str(&content)
+        .map_err(|e| anyhow!(t!("import.invalid", path.display(), e)))?;
+
+    if !portable.environments.is_empty() {
+        EnvConfig::update(|config| {
+            config.merge_environments(&portable);
+            config.validate()
+        })?;
+        status!("{}", t!("import.environments", portable.environments.len()));
+    }
+
+    let env_config = EnvConfig::current()?;
+    let missing = env_config.get_missing_versions(&portable);
+    if missing.is_empty() {
+        status!("{}", t!("import.all_installed"));
+        return Ok(());
+    }
+
+    let summary = missing
+        .iter()
+        .map(|(name, version)| format!("{} {}", name, version))
+        .collect::<Vec<String>>()
+        .join(", ");
+    status!("{}", t!("import.missing", summary));
+
+    // JSON 模式下不交互, 只导入配置
+    if !is_human_output() {
+        return Ok(());
+    }
+    let install = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(t!("import.prompt"))
+        .default(true)
+        .interact_opt()
+        .map_err(prompt_error)?
+        .ok_or_else(cancelled)?;
+    if !install {
+        return Ok(());
+    }
+
+    for (name, version) in missing {
+        let env = env_config
+            .find_environment(&name)
+            .ok_or_else(|| anyhow!(t!("env.not_found", name)))?;
+        install_environment(&env, &json!({ "version": version }), install_dir).await?;
+    }
+    Ok(())
+}
+
+/// 修复已安装的环境: 重新安装目录缺失或不完整的版本, 再按当前的安装目录重新切换各环境的当前版本,
+/// 用于移动安装目录或用户目录后恢复环境变量及 PATH; 单个版本失败时继续修复其他版本
+pub async fn repair_installed(name: Option<&str>, install_dir: &Path) -> Result<()> {
+    let env_config = EnvConfig::current()?;
+    let ctx = InstallContext::new(install_dir)?;
+    let mut targets = vec![];
+    for installed in env_config.installed.iter().flatten() {
+        if name.is_some_and(|name| !name.eq_ignore_ascii_case(&installed.name)) {
+            continue;
+        }
+        match env_config.find_environment(&installed.name) {
+            Some(env) => targets.push((env, installed.clone())),
+            None => status!("{}", t!("repair.unknown_env", installed.name).yellow()),
+        }
+    }
+    if let Some(name) = name.filter(|_| targets.is_empty()) {
+        return Err(anyhow!(t!("repair.not_installed", name)));
+    }
+
+    // 重新安装时只登记版本, 当前版本统一在之后切换
+    let no_switch = is_no_switch();
+    set_no_switch(true);
+    let mut failed = vec![];
+    for (env, installed) in &targets {
+        for version in &installed.installed_versions {
+            if ctx.is_installed(env, version)
+                || external_home(installed, version, install_dir).is_some()
+            {
+                continue;
+            }
+            status!("{}", t!("repair.reinstall", env.name, version).yellow());
+            // 沿用上一次安装的参数, 如数据库的端口
+            let mut args: serde_json::Map<String, Value> =
+                installed.last_args.clone().into_iter().collect();
+            args.insert("version".to_string(), json!(version));
+            if let Err(e) = install_with(&ctx, env, &Value::Object(args)).await {
+                status!(
+                    "{}",
+                    t!("repair.reinstall_failed", env.name, version, e).red()
+                );
+                failed.push(format!("{} {}", env.name, version));
+            }
+        }
+    }
+    set_no_switch(no_switch);
+
+    for (env, installed) in &targets {
+        let Some(version) = &installed.current_version else {
+            continue;
+        };
+        if failed.contains(&format!("{} {}", env.name, version)) {
+            continue;
+        }
+        if let Some(home_dir) = external_home(installed, version, install_dir) {
+            status!("{}", t!("repair.external", env.name, version, home_dir));
+            continue;
+        }
+        if let Err(e) = switch_version(env, version) {
+            status!("{}", t!("repair.switch_failed", env.name, version, e).red());
+            failed.push(format!("{} {}", env.name, version));
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(anyhow!(t!("repair.failed", failed.join(", "))));
+    }
+    status!("{}", t!("repair.done").green());
+    Ok(())
+}
+
+/// 版本登记的是安装目录之外仍然存在的目录(如登记的系统安装)时返回该目录, 修复时保持不变;
+/// 除当前版本外, 登记过的其他版本同样按记录的目录判断
+fn external_home(
+    installed: &InstalledEnvironment,
+    version: &str,
+    install_dir: &Path,
+) -> Option<String> {
+    let home_dir = installed.home_of(version)?;
+    (!Path::new(home_dir).starts_with(install_dir) && Path::new(home_dir).is_dir())
+        .then(|| home_dir.to_string())
+}
+
+/// 重新计算下载缓存中安装包的 sha256, 与下载时记录的值比较, 不一致时返回错误
+pub fn verify_package(name: &str, version: &str) -> Result<()> {
+    let env_config = EnvConfig::current()?;
+    let env = env_config
+        .find_environment(name)
+        .ok_or_else(|| anyhow!(t!("env.not_found", name)))?;
+    let name = env.name.as_str();
+    let version = env_config.resolve_alias(name, version);
+    let platform = get_platform();
 
-        Ok(())
+    let entry = verify_cached(name, &version, &platform)
+        .inspect_err(|e| emit_error("verify", name, &version, e))?
+        .ok_or_else(|| anyhow!(t!("verify.not_cached", name, version, platform)))?;
+
+    if is_human_output() {
+        println!("{} {}", t!("verify.ok").green(), entry.filename);
+        println!("sha256: {}", entry.sha256);
     } else {
-        return Err(anyhow!("未找到 {} 环境", name));
+        println!(
+            "{}",
+            json!({
+                "name": name,
+                "version": version,
+                "file": entry.filename,
+                "sha256": entry.sha256,
+                "ok": true,
+            })
+        );
+    }
+    Ok(())
+}
+
+/// 获取环境已安装的版本及当前版本
+fn get_installed_versions(name: &str) -> Result<(Environment, Vec<String>, Option<String>)> {
+    let env_config = EnvConfig::current()?;
+    let env = env_config
+        .find_environment(name)
+        .ok_or_else(|| anyhow!(t!("env.not_found", name)))?;
+
+    if !is_supported_env(&env) {
+        return Err(anyhow!(t!("error.unsupported_environment", env.name.red())));
+    }
+
+    let versions = env_config.get_install_versions(&env.name);
+    let current_version = env_config.get_current_version(&env.name);
+    Ok((env, versions, current_version))
+}
+
+pub fn choose_version(name: &str) -> Result<()> {
+    let (env, versions, current_version) = get_installed_versions(name)?;
+    let name = env.name.as_str();
+
+    if versions.is_empty() {
+        println!("{}", t!("version.not_found", name));
+        return Ok(());
+    }
+
+    let (selected_version, skip) =
+        select_version(&t!("version.select"), &versions, current_version)?;
+
+    // 相同版本不需要切换
+    if skip {
+        return Ok(());
+    }
+    // 切换版本
+    switch_version(&env, &selected_version)?;
+
+    Ok(())
+}
+
+/// 切换到指定的已安装版本, 参数可以是版本别名
+pub fn choose_version_to(name: &str, version: &str) -> Result<()> {
+    let (env, versions, current_version) = get_installed_versions(name)?;
+
+    let resolved = EnvConfig::current()?.resolve_alias(&env.name, version);
+    if resolved != version {
+        status!("{} -> {} {}", version, env.name, resolved);
+    }
+    if !versions.contains(&resolved) {
+        return Err(anyhow!(t!("version.not_installed", env.name, resolved)));
+    }
+    if current_version.as_deref() == Some(resolved.as_str()) {
+        status!("{}", t!("version.in_use", env.name, resolved));
+        return Ok(());
+    }
+
+    switch_version(&env, &resolved)
+}
+
+/// 列出、查询、设置或删除环境的版本别名
+pub fn manage_alias(
+    name: &str,
+    alias: Option<&str>,
+    version: Option<&str>,
+    delete: bool,
+) -> Result<()> {
+    let env = EnvConfig::current()?
+        .find_environment(name)
+        .ok_or_else(|| anyhow!(t!("env.not_found", name)))?;
+    let name = env.name.as_str();
+    let aliases = EnvConfig::current()?.get_aliases(name);
+
+    match (alias, version) {
+        (None, _) => {
+            if !is_human_output() {
+                println!("{}", serde_json::to_string(&aliases)?);
+                return Ok(());
+            }
+            if aliases.is_empty() {
+                status!("{}", t!("alias.none", name));
+            }
+            for (alias, version) in &aliases {
+                println!("{} -> {}", alias, version);
+            }
+            Ok(())
+        }
+        (Some(alias), _) if delete => {
+            if !aliases.contains_key(alias) {
+                return Err(anyhow!(t!("alias.not_found", name, alias)));
+            }
+            if is_dry_run() {
+                status!("{}", t!("dry_run.delete_alias", name, alias));
+                return Ok(());
+            }
+            EnvConfig::update(|config| {
+                config.remove_alias(name, alias);
+                Ok(())
+            })?;
+            status!("{}", t!("alias.deleted", name, alias));
+            Ok(())
+        }
+        (Some(alias), None) => {
+            let version = aliases
+                .get(alias)
+                .ok_or_else(|| anyhow!(t!("alias.not_found", name, alias)))?;
+            println!("{}", version);
+            Ok(())
+        }
+        (Some(alias), Some(version)) => {
+            let installed = EnvConfig::current()?.get_install_versions(name);
+            if !installed.iter().any(|v| v == version) {
+                status!("{}", t!("alias.not_installed", name, version).yellow());
+            }
+            if is_dry_run() {
+                // 只校验别名, 不写入配置
+                EnvConfig::current()?.set_alias(name, alias, version)?;
+                status!("{}", t!("dry_run.set_alias", name, alias, version));
+                return Ok(());
+            }
+            EnvConfig::update(|config| config.set_alias(name, alias, version))?;
+            status!("{}", t!("alias.set", name, alias, version));
+            Ok(())
+        }
+    }
+}
+
+/// 切换回上一次使用的版本
+pub fn choose_previous_version(name: &str) -> Result<()> {
+    let (env, versions, _) = get_installed_versions(name)?;
+
+    let previous = EnvConfig::current()?
+        .get_previous_version(&env.name)
+        .filter(|v| versions.contains(v))
+        .ok_or_else(|| anyhow!(t!("switch.no_previous", env.name)))?;
+
+    status!("{}", t!("switch.previous", env.name, previous));
+    switch_version(&env, &previous)
+}
+
+/// 版本管理菜单中的操作, 保存消息 key
+const MANAGE_ACTIONS: [&str; 3] = ["manage.switch", "manage.install", "manage.uninstall"];
+
+/// 在一个菜单中切换、安装或卸载环境的版本
+pub async fn manage_versions(name: &str, install_dir: &Path) -> Result<()> {
+    let (env, versions, current_version) = get_installed_versions(name)?;
+
+    let current = current_version
+        .clone()
+        .unwrap_or_else(|| t!("version.none"));
+    let actions = MANAGE_ACTIONS.map(|key| t!(key));
+    let action = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(t!("manage.prompt", env.name, current))
+        .default(0)
+        .items(&actions)
+        .interact_opt()
+        .map_err(prompt_error)?
+        .ok_or_else(cancelled)?;
+
+    match action {
+        0 => choose_version(&env.name),
+        1 => choose_and_install_from(&env, install_dir).await,
+        _ => {
+            if versions.is_empty() {
+                println!("{}", t!("version.not_found", env.name));
+                return Ok(());
+            }
+            let (version, _) =
+                select_version(&t!("version.select_uninstall"), &versions, current_version)?;
+            let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(t!("manage.confirm_uninstall", env.name, version))
+                .default(false)
+                .interact_opt()
+                .map_err(prompt_error)?
+                .ok_or_else(cancelled)?;
+            if !confirmed {
+                return Ok(());
+            }
+            uninstall_version(&env, &version)
+        }
     }
 }
 
-pub fn choose_package(env: &Environment, version: &str) -> String {
-    let mut os = consts::OS.to_string();
-    let mut arch = consts::ARCH.to_string();
-    
-    // 统一化操作系统名称
-    for (os_name, alias) in OS_IDENTIFIER.iter() {
-        if alias.contains(&os) {
-            os = os_name.to_string();
-            break;
+/// 删除已安装的版本目录并从配置中移除, 正在使用的版本需要先切换到其他版本
+pub fn uninstall_version(env: &Environment, version: &str) -> Result<()> {
+    let name = env.name.as_str();
+    if EnvConfig::current()?.get_current_version(name).as_deref() == Some(version) {
+        return Err(anyhow!(t!("uninstall.in_use", name, version)));
+    }
+
+    // 登记的外部安装不属于 env 管理, 只取消登记而不删除目录
+    let external = EnvConfig::current()?.external_home(name, version);
+    let version_dir = get_install_dir(env, version)?;
+    if is_dry_run() {
+        match &external {
+            Some(home) => status!("{}", t!("dry_run.unregister", home.display())),
+            None => status!("{}", t!("dry_run.remove_version", version_dir.display())),
         }
+        emit_event("uninstall", name, version, "dry-run");
+        return Ok(());
     }
 
-    // 统一化架构名称
-    for (arch_name, alias) in ARCH_IDENTIFIER.iter() {
-        if alias.contains(&arch) {
-            arch = arch_name.to_string();
-            break;
+    if external.is_none() && version_dir.exists() {
+        if let Err(e) = fs::remove_dir_all(&version_dir) {
+            let err = anyhow!(t!("uninstall.remove_failed", version_dir.display(), e));
+            emit_error("uninstall", name, version, &err);
+            return Err(err);
         }
     }
-    
-    let platform = format!("{}-{}", &os, &arch);
-   
-    // 首先尝试从映射配置中获取URL
-    if let Some(repos) = REPOSITORY_MAP.get(&env.name) {
-        if let Some(version_map) = repos.get(&platform) {
-            if let Some(url) = version_map.get(version) {
-                if let Some(url_str) = url.as_str() {
-                    return url_str.to_string();
-                }
+    EnvConfig::uninstall_version(name, version)?;
+
+    status!("{}", t!("uninstall.done", name, version).green());
+    emit_event("uninstall", name, version, "ok");
+    Ok(())
+}
+
+/// 按版本号从新到旧保留 keep 个版本, 返回需要删除的版本, 当前使用的版本始终保留
+pub fn versions_to_prune(versions: &[String], current: Option<&str>, keep: usize) -> Vec<String> {
+    let mut sorted = versions.to_vec();
+    version::sort_newest_first(&mut sorted);
+    sorted
+        .into_iter()
+        .skip(keep)
+        .filter(|v| Some(v.as_str()) != current)
+        .collect()
+}
+
+/// 删除较旧的已安装版本, 未指定数量时使用配置中的 `keep_versions`
+pub fn prune_versions(name: &str, keep: Option<usize>) -> Result<()> {
+    let (env, versions, current_version) = get_installed_versions(name)?;
+    let keep = keep.unwrap_or(EnvConfig::current()?.keep_versions);
+
+    let pruned = versions_to_prune(&versions, current_version.as_deref(), keep);
+    if pruned.is_empty() {
+        status!("{}", t!("prune.nothing", env.name));
+        return Ok(());
+    }
+    status!("{}", t!("prune.plan", env.name, pruned.join(", ")));
+
+    // JSON 模式及 --yes 时不询问
+    if is_human_output() && !is_assume_yes() && !is_dry_run() {
+        let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(t!("prune.confirm"))
+            .default(false)
+            .interact_opt()
+            .map_err(prompt_error)?
+            .ok_or_else(cancelled)?;
+        if !confirmed {
+            return Err(cancelled());
+        }
+    }
+
+    for version in &pruned {
+        uninstall_version(&env, version)?;
+    }
+    Ok(())
+}
+
+/// 输出安装目录、下载缓存、下载地址来源及各环境当前版本的概览
+pub fn print_status() -> Result<()> {
+    let env_config = EnvConfig::current()?;
+    let cache_dir = get_download_cache_dir()?;
+    let cache_size = download_cache_size()?;
+    let index_url = env_config
+        .index_url
+        .clone()
+        .unwrap_or_else(|| DEFAULT_INDEX_URL.to_string());
+    // reqwest 使用这些环境变量中的代理
+    let proxy = [
+        "HTTPS_PROXY",
+        "https_proxy",
+        "HTTP_PROXY",
+        "http_proxy",
+        "ALL_PROXY",
+    ]
+    .iter()
+    .find_map(|key| std::env::var(key).ok().filter(|v| !v.is_empty()));
+
+    let mut environments = vec![];
+    for installed in env_config.installed.iter().flatten() {
+        let Some(env) = env_config.find_environment(&installed.name) else {
+            continue;
+        };
+        let Some(version) = &installed.current_version else {
+            environments.push((env.name.clone(), None, None));
+            continue;
+        };
+        let home_dir = match installed.home_of(version) {
+            Some(home) => PathBuf::from(home),
+            None => get_install_dir(&env, version)?,
+        };
+        let bin_dir = resolve_bin_dir(&env, version, home_dir.to_str())?;
+        let check = check_active_on_path(&env.name, &bin_dir, &home_dir);
+        environments.push((env.name.clone(), Some(version.clone()), Some(check)));
+    }
+
+    if !is_human_output() {
+        let environments: Vec<Value> = environments
+            .iter()
+            .map(|(name, version, check)| {
+                let path = match check {
+                    Some(PathCheck::Active) => json!("active"),
+                    Some(PathCheck::Shadowed(path)) => json!({ "shadowed_by": path }),
+                    Some(PathCheck::Missing) => json!("missing"),
+                    None => Value::Null,
+                };
+                json!({ "name": name, "version": version, "path": path })
+            })
+            .collect();
+        println!(
+            "{}",
+            json!({
+                "install_path": env_config.install_path,
+                "cache_dir": cache_dir,
+                "cache_size": cache_size,
+                "index_url": index_url,
+                "resolver_command": env_config.resolver_command,
+                "proxy": proxy,
+                "environments": environments,
+            })
+        );
+        return Ok(());
+    }
+
+    println!("{}", t!("status.install_path", env_config.install_path));
+    println!(
+        "{}",
+        t!("status.cache", cache_dir.display(), HumanBytes(cache_size))
+    );
+    println!("{}", t!("status.index", index_url));
+    if let Some(command) = &env_config.resolver_command {
+        println!("{}", t!("status.resolver", command));
+    }
+    let proxy = proxy.unwrap_or_else(|| t!("status.no_proxy"));
+    println!("{}", t!("status.proxy", proxy));
+
+    println!();
+    if environments.is_empty() {
+        println!("{}", t!("status.none_installed"));
+        return Ok(());
+    }
+    for (name, version, check) in &environments {
+        let (version, state) = match (version, check) {
+            (Some(version), Some(PathCheck::Active)) => {
+                (version.as_str(), t!("status.active").green())
+            }
+            (Some(version), Some(PathCheck::Shadowed(path))) => (
+                version.as_str(),
+                t!("status.shadowed", path.display()).yellow(),
+            ),
+            (Some(version), _) => (version.as_str(), t!("status.missing").yellow()),
+            (None, _) => ("-", t!("status.no_version").normal()),
+        };
+        println!("  {:<10} {:<12} {}", name, version, state);
+    }
+    Ok(())
+}
+
+/// 不交互, 每行输出一个已安装的版本, 当前版本以 `*` 标记
+pub fn list_versions(name: &str) -> Result<()> {
+    let (env, versions, current_version) = get_installed_versions(name)?;
+
+    if !is_human_output() {
+        println!(
+            "{}",
+            json!({ "name": env.name, "versions": versions, "current": current_version })
+        );
+        return Ok(());
+    }
+
+    for version in &versions {
+        if Some(version) == current_version.as_ref() {
+            println!("* {}", version);
+        } else {
+            println!("  {}", version);
+        }
+    }
+    Ok(())
+}
+
+/// 在别名表中查找统一化后的名称
+fn normalize_identifier(identifiers: &HashMap<String, Vec<String>>, value: &str) -> Option<String> {
+    let value = value.to_lowercase();
+    identifiers
+        .iter()
+        .find(|(_, alias)| alias.contains(&value))
+        .map(|(name, _)| name.to_string())
+}
+
+/// 获取统一化后的当前操作系统与架构名称
+pub fn get_host_os_arch() -> (String, String) {
+    let os = normalize_identifier(&OS_IDENTIFIER, consts::OS).unwrap_or(consts::OS.to_string());
+    let arch =
+        normalize_identifier(&ARCH_IDENTIFIER, consts::ARCH).unwrap_or(consts::ARCH.to_string());
+
+    debug!(
+        "平台识别: {}/{} -> {}/{}",
+        consts::OS,
+        consts::ARCH,
+        os,
+        arch
+    );
+    (os, arch)
+}
+
+/// 获取下载安装包使用的操作系统与架构名称, `--os`/`--arch` 优先于当前平台
+pub fn get_os_arch() -> (String, String) {
+    let (os, arch) = get_host_os_arch();
+    (
+        get_target_os().unwrap_or(os),
+        get_target_arch().unwrap_or(arch),
+    )
+}
+
+/// 目标平台是否与当前平台不同, 不同时只下载安装包
+pub fn is_cross_platform() -> bool {
+    get_os_arch() != get_host_os_arch()
+}
+
+/// 解析 `--os` 参数, 返回统一化后的操作系统名称
+pub fn parse_os(value: &str) -> Result<String> {
+    normalize_identifier(&OS_IDENTIFIER, value).ok_or_else(|| {
+        anyhow!(
+            "未知的操作系统: {}, 可选: {}",
+            value,
+            identifier_names(&OS_IDENTIFIER)
+        )
+    })
+}
+
+/// 解析 `--arch` 参数, 返回统一化后的架构名称
+pub fn parse_arch(value: &str) -> Result<String> {
+    normalize_identifier(&ARCH_IDENTIFIER, value).ok_or_else(|| {
+        anyhow!(
+            "未知的架构: {}, 可选: {}",
+            value,
+            identifier_names(&ARCH_IDENTIFIER)
+        )
+    })
+}
+
+fn identifier_names(identifiers: &HashMap<String, Vec<String>>) -> String {
+    let mut names: Vec<&String> = identifiers.keys().collect();
+    names.sort();
+    names
+        .iter()
+        .map(|name| name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 获取当前平台标识, 如 linux-x64
+pub fn get_platform() -> String {
+    let (os, arch) = get_os_arch();
+    format!("{}-{}", os, arch)
+}
+
+/// 获取安装包的候选下载地址, 按优先级排列: 仓库索引中的地址, 然后是配置中的各个镜像模板
+pub fn choose_package(config: &EnvConfig, env: &Environment, version: &str) -> Result<Vec<String>> {
+    let (os, arch) = get_os_arch();
+    let platform = format!("{}-{}", os, arch);
+
+    // 未知平台直接报错, 避免拼出错误的下载地址
+    if !PLATFORM.contains_key(&platform) {
+        return Err(EnvError::UnsupportedPlatform {
+            name: env.name.clone(),
+            platform: format!("{} ({}-{})", platform, consts::OS, consts::ARCH),
+        }
+        .into());
+    }
+
+    debug!("平台标识: {}", platform);
+
+    if !env.platforms.is_empty() && !env.platforms.contains(&os) {
+        return Err(EnvError::UnsupportedOs {
+            name: env.name.clone(),
+            os,
+            supported: env.platforms.join(", "),
+        }
+        .into());
+    }
+
+    let mut urls = vec![];
+
+    // 配置了外部解析命令时优先使用其输出的地址, 命令失败时提示并使用内置的解析方式
+    match resolve_with_command(config, &env.name, version, &os, &arch) {
+        Ok(Some(url)) => {
+            debug!("下载地址来自解析命令: {} {} -> {}", env.name, version, url);
+            urls.push(url);
+        }
+        Ok(None) => {}
+        Err(e) => status!("{}", t!("install.resolver_failed", e).yellow()),
+    }
+
+    // 然后尝试从仓库索引中获取URL
+    if let Some(url) = lookup_package(&env.name, &platform, version) {
+        debug!("下载地址来自仓库索引: {} {} -> {}", env.name, version, url);
+        urls.push(url);
+    }
+
+    // 模板方式的地址作为后备
+    let format = env.package_format(&os);
+    let arch = config.map_arch(&env.name, &arch);
+    let target = TARGET_TRIPLE
+        .get(&platform)
+        .map(String::as_str)
+        .unwrap_or("");
+
+    for template in env.repository.templates() {
+        debug!(
+            "下载地址来自模板: {}, 架构: {}, 格式: {}",
+            template, arch, format
+        );
+
+        let mut package_url = template
+            .replace("%version%", version)
+            .replace("%arch%", &arch)
+            .replace("%platform%", &os)
+            .replace("%target%", target)
+            .replace("%format%", &format);
+
+        if os == "windows" && package_url.contains("rustup-init") {
+            package_url = format!("{}.exe", package_url);
+        }
+        if !urls.contains(&package_url) {
+            urls.push(package_url);
+        }
+    }
+    Ok(urls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check::INSTALL_MARKER;
+    use crate::env_config::EnvConfig;
+    use crate::error::error_code;
+    use crate::zip::DEFAULT_FORMAT;
+
+    /// 内置的默认配置, 测试不读取用户的全局配置
+    fn default_config() -> EnvConfig {
+        serde_json::from_str(include_str!("../.env.config.default.json")).unwrap()
+    }
+
+    fn default_environment(name: &str) -> Environment {
+        default_config().get_enviroment(name).unwrap()
+    }
+
+    fn prepare_extract_dir(name: &str, entries: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        for entry in entries {
+            let path = dir.join("extract").join(entry);
+            if entry.ends_with('/') {
+                fs::create_dir_all(path).unwrap();
+            } else {
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, "").unwrap();
+            }
+        }
+        dir
+    }
+
+    #[test]
+    fn test_move_single_dir_with_dotfile() {
+        let dir = prepare_extract_dir(
+            "env_move_single_test",
+            &[".DS_Store", "jdk-17.0.12/bin/java", "jdk-17.0.12/release"],
+        );
+        let version_dir = dir.join("java-17");
+
+        move_to_version_dir(&dir.join("extract"), &version_dir).unwrap();
+
+        assert!(version_dir.join("bin").join("java").exists());
+        assert!(version_dir.join("release").exists());
+        assert!(!version_dir.join("jdk-17.0.12").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_version_dir_round_trip() {
+        let dir = prepare_extract_dir(
+            "env_version_dir_round_trip_test",
+            &["node-v20.10.0-linux-x64/bin/node"],
+        );
+        let ctx = InstallContext::with_config(&dir.join("install"), default_config());
+
+        // 解压出的目录名与版本目录不同, 重命名后重新扫描得到安装的版本
+        let version_dir = ctx.version_dir("Node", "20.10.0");
+        fs::create_dir_all(version_dir.parent().unwrap()).unwrap();
+        move_to_version_dir(&dir.join("extract"), &version_dir).unwrap();
+        assert!(version_dir.ends_with("node-20.10.0"));
+        assert!(version_dir.join("bin").join("node").exists());
+
+        // 安装锁及未清理的临时目录不在环境目录下, 不算已安装的版本
+        let env_dir = version_dir.parent().unwrap();
+        let temp_dir = ctx.temp_dir("Node", "20.10.0");
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(ctx.lock_path("Node", "20.10.0"), "").unwrap();
+        assert!(!temp_dir.starts_with(env_dir));
+
+        let installed =
+            crate::env_config::find_version_from_dir(env_dir, ctx.config.layout).unwrap();
+        assert_eq!(installed.name, "node");
+        assert_eq!(installed.installed_versions, vec!["20.10.0"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_move_single_file() {
+        let dir = prepare_extract_dir("env_move_single_file_test", &["kubectl.exe"]);
+        let version_dir = dir.join("kubectl-1.32.0");
+
+        move_to_version_dir(&dir.join("extract"), &version_dir).unwrap();
+
+        // 单个文件直接放在版本目录中
+        assert!(version_dir.join("kubectl.exe").is_file());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_move_multiple_entries() {
+        let dir = prepare_extract_dir(
+            "env_move_multiple_test",
+            &["bin/", "lib/", "README.md", ".gitignore"],
+        );
+        let version_dir = dir.join("go-1.23.4");
+
+        move_to_version_dir(&dir.join("extract"), &version_dir).unwrap();
+
+        for entry in ["bin", "lib", "README.md", ".gitignore"] {
+            assert!(version_dir.join(entry).exists(), "{} 未移动", entry);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_choose_package_maven() {
+        let env = default_environment("maven");
+        let url = choose_package(&default_config(), &env, "3.9.6")
+            .unwrap()
+            .remove(0);
+        assert!(url.starts_with("https://dlcdn.apache.org/maven/maven-3/3.9.6/binaries/"));
+        assert!(url.contains("apache-maven-3.9.6-bin."));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_install_spec() {
+        let config = default_config();
+        let spec: InstallSpec = serde_json::from_str(
+            r#"{"installs": [
+                {"name": "java", "version": "17", "vendor": "temurin"},
+                {"name": "Node"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let items = resolve_install_spec(&config, &spec).await.unwrap();
+        assert_eq!(items[0].0.name, "java");
+        assert_eq!(items[0].1["version"], "17");
+        assert_eq!(
+            items[1].1["version"],
+            json!(config.get_enviroment("node").unwrap().get_default_version())
+        );
+        assert_eq!(items[0].1["vendor"], "temurin");
+
+        // 任一项无效时整个清单无效
+        for invalid in [
+            r#"{"installs": [{"name": "java"}, {"name": "cobol", "version": "1"}]}"#,
+            r#"{"installs": [{"name": "mysql", "version": "8.4.3"}]}"#,
+            r#"{"installs": [{"name": "java", "version": 17}]}"#,
+            // 格式正确但不是已知的版本
+            r#"{"installs": [{"name": "java", "version": "17.0.9"}]}"#,
+        ] {
+            let spec: InstallSpec = serde_json::from_str(invalid).unwrap();
+            assert!(resolve_install_spec(&config, &spec).await.is_err());
+        }
+    }
+
+    #[test]
+    fn test_choose_package_format() {
+        let mut env = default_environment("maven");
+        let (os, _) = get_os_arch();
+        assert_eq!(
+            env.package_format(&os),
+            DEFAULT_FORMAT[&os].as_str().unwrap()
+        );
+
+        env.format.insert(os.clone(), "7z".to_string());
+        let url = choose_package(&default_config(), &env, "3.9.6")
+            .unwrap()
+            .remove(0);
+        assert!(url.ends_with("apache-maven-3.9.6-bin.7z"));
+    }
+
+    #[test]
+    fn test_choose_package_rust() {
+        let env = default_environment("rust");
+        let url = choose_package(&default_config(), &env, "stable")
+            .unwrap()
+            .remove(0);
+        let target = &TARGET_TRIPLE[&get_platform()];
+        assert!(url.starts_with(&format!(
+            "https://static.rust-lang.org/rustup/dist/{}/rustup-init",
+            target
+        )));
+    }
+
+    #[test]
+    fn test_choose_package_gradle() {
+        let env = default_environment("gradle");
+        let url = choose_package(&default_config(), &env, "8.5")
+            .unwrap()
+            .remove(0);
+        assert_eq!(
+            url,
+            "https://services.gradle.org/distributions/gradle-8.5-bin.zip"
+        );
+    }
+
+    #[test]
+    fn test_choose_package_single_binary() {
+        if get_platform() != "linux-x64" {
+            return;
+        }
+
+        let config = default_config();
+        let url = choose_package(&config, &default_environment("kubectl"), "1.32.0")
+            .unwrap()
+            .remove(0);
+        assert_eq!(
+            url,
+            "https://dl.k8s.io/release/v1.32.0/bin/linux/amd64/kubectl"
+        );
+
+        // 解析命令失败时使用内置的下载地址
+        let mut failing = default_config();
+        failing.resolver_command = Some("false".to_string());
+        assert_eq!(
+            choose_package(&failing, &default_environment("kubectl"), "1.32.0").unwrap(),
+            vec![url]
+        );
+
+        let url = choose_package(&config, &default_environment("cmake"), "3.31.3")
+            .unwrap()
+            .remove(0);
+        assert_eq!(
+            url,
+            "https://github.com/Kitware/CMake/releases/download/v3.31.3/cmake-3.31.3-linux-x86_64.tar.gz"
+        );
+
+        let err = choose_package(&config, &default_environment("git"), "2.47.1").unwrap_err();
+        assert_eq!(error_code(&err), Some("unsupported_platform"));
+    }
+
+    #[test]
+    fn test_versions_to_prune() {
+        let versions: Vec<String> = ["9.0.0", "10.0.0", "8", "21", "17", "11"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+
+        // 按版本号而不是字符串比较, 当前版本不删除
+        assert_eq!(
+            versions_to_prune(&versions, Some("8"), 3),
+            vec!["10.0.0", "9.0.0"]
+        );
+        assert_eq!(
+            versions_to_prune(&versions, Some("21"), 6),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            versions_to_prune(&versions[..2], None, 0),
+            vec!["10.0.0", "9.0.0"]
+        );
+    }
+
+    #[test]
+    fn test_default_environments() {
+        for name in DEFAULT_ENVIRONMENTS {
+            let env = default_environment(name);
+            assert!(is_supported_env(&env), "{} 不支持安装", name);
+            let version = env.get_default_version().unwrap();
+            assert!(env.get_version_options().contains(&version));
+        }
+        assert_eq!(
+            default_environment("java").get_default_version(),
+            Some("21".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_os_arch() {
+        assert_eq!(parse_os("Darwin").unwrap(), "macos");
+        assert_eq!(parse_os("win").unwrap(), "windows");
+        assert_eq!(parse_arch("arm64").unwrap(), "aarch64");
+        assert_eq!(parse_arch("x86_64").unwrap(), "x64");
+
+        let err = parse_arch("sparc").unwrap_err();
+        assert!(err.to_string().contains("aarch64, armv7, x64"));
+        assert!(parse_os("plan9").is_err());
+    }
+
+    #[test]
+    fn test_install_binary() {
+        let dir = prepare_extract_dir("env_install_binary_test", &[]);
+        fs::create_dir_all(&dir).unwrap();
+        let download = dir.join("0123456789abcdef-kubectl");
+        fs::write(&download, "binary").unwrap();
+
+        let version_dir = dir.join("kubectl-1.32.0");
+        install_binary(download.to_str().unwrap(), "kubectl", &version_dir).unwrap();
+        let target = version_dir
+            .join("bin")
+            .join(format!("kubectl{}", consts::EXE_SUFFIX));
+        assert_eq!(fs::read_to_string(target).unwrap(), "binary");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_install_summary() {
+        let java = default_environment("java");
+        let go = default_environment("go");
+
+        let results = vec![
+            InstallResult::new(&java, "17", InstallOutcome::Installed),
+            InstallResult::new(&go, "1.23.4", InstallOutcome::Switched),
+        ];
+        assert!(print_install_summary(&results).is_ok());
+
+        let results = vec![
+            InstallResult::new(&java, "17", InstallOutcome::Installed),
+            InstallResult::failed(&go, "1.23.4", "下载失败", &anyhow!("连接超时")),
+        ];
+        let err = print_install_summary(&results).unwrap_err();
+        assert_eq!(err.to_string(), t!("install.failed_count", 1, 2));
+        assert!(
+            matches!(&results[1].outcome, InstallOutcome::Failed(reason) if reason == "下载失败: 连接超时")
+        );
+    }
+
+    #[test]
+    fn test_external_home() {
+        let system_home = std::env::temp_dir().join("env_external_home_test");
+        fs::create_dir_all(&system_home).unwrap();
+        let install_dir = Path::new("/opt/dev");
+        let installed = |home_dir: &Path| InstalledEnvironment {
+            installed_versions: vec!["17".to_string(), "21".to_string()],
+            ..InstalledEnvironment::new("java", "21", home_dir.to_str().unwrap())
+        };
+
+        // 登记的系统安装保持不变
+        let adopted = installed(&system_home);
+        assert_eq!(
+            external_home(&adopted, "21", install_dir).as_deref(),
+            system_home.to_str()
+        );
+        assert_eq!(external_home(&adopted, "17", install_dir), None);
+
+        // 登记过的非当前版本同样保持不变
+        let mut switched = installed(&install_dir.join("java").join("java-21"));
+        switched
+            .external_homes
+            .insert("17".to_string(), system_home.to_str().unwrap().to_string());
+        assert_eq!(
+            external_home(&switched, "17", install_dir).as_deref(),
+            system_home.to_str()
+        );
+        assert_eq!(external_home(&switched, "21", install_dir), None);
+
+        // 安装目录中的版本及已不存在的旧目录需要修复
+        let managed = installed(&install_dir.join("java").join("java-21"));
+        assert_eq!(external_home(&managed, "21", install_dir), None);
+        let moved = installed(Path::new("/home/old/dev/java/java-21"));
+        assert_eq!(external_home(&moved, "21", install_dir), None);
+
+        fs::remove_dir_all(&system_home).unwrap();
+    }
+
+    #[test]
+    fn test_lock_install() {
+        let root = std::env::temp_dir().join("env_install_lock_test");
+        let _ = fs::remove_dir_all(&root);
+        let ctx = InstallContext::with_config(&root, default_config());
+
+        let lock = lock_install(&ctx, "java", "17.0.9").unwrap();
+        assert!(lock.is_some());
+        assert!(ctx.lock_path("java", "17.0.9").is_file());
+
+        // 同一版本正在安装时直接报错, 其他版本不受影响
+        let err = lock_install(&ctx, "java", "17.0.9").unwrap_err();
+        assert_eq!(error_code(&err), Some("install_in_progress"));
+        assert!(lock_install(&ctx, "java", "21").unwrap().is_some());
+
+        // 释放后删除锁文件, 可以再次加锁
+        drop(lock);
+        assert!(!ctx.lock_path("java", "17.0.9").exists());
+        assert!(lock_install(&ctx, "java", "17.0.9").unwrap().is_some());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    /// 返回本地安装包的下载实现, `dead_host` 中的地址模拟无法访问的镜像
+    struct FixtureDownloader {
+        archive: PathBuf,
+        dead_host: Option<&'static str>,
+    }
+
+    impl Downloader for FixtureDownloader {
+        async fn exists(&self, url: &str) -> Result<bool> {
+            match self.dead_host {
+                Some(host) if url.contains(host) => Err(anyhow!("无法连接 {}", host)),
+                _ => Ok(true),
             }
         }
+
+        async fn download(&self, _url: &str) -> Result<String> {
+            // 安装完成后会删除安装包, 复制一份避免影响夹具
+            let filename = self.archive.with_extension("download.tar.gz");
+            fs::copy(&self.archive, &filename)?;
+            Ok(filename.to_str().unwrap().to_string())
+        }
+
+        async fn stream_extract(&self, _url: &str, output_dir: &Path) -> Result<()> {
+            let file = fs::File::open(&self.archive)?;
+            tar::Archive::new(flate2::read::GzDecoder::new(file)).unpack(output_dir)?;
+            Ok(())
+        }
+    }
+
+    /// 只在内存配置中记录版本, 不修改用户的环境变量
+    struct MemoryActivator {
+        config: std::sync::Mutex<EnvConfig>,
+    }
+
+    impl Activator for MemoryActivator {
+        fn activate(&self, env: &Environment, version: &str, version_dir: &Path) -> Result<()> {
+            self.config.lock().unwrap().record_switch(
+                &env.name,
+                version,
+                version_dir.to_str().unwrap(),
+            );
+            Ok(())
+        }
+
+        fn register(&self, env: &Environment, version: &str, _version_dir: &Path) -> Result<()> {
+            self.config
+                .lock()
+                .unwrap()
+                .record_installed(&env.name, version);
+            Ok(())
+        }
+    }
+
+    /// 创建包含 `fixture-1.0.0/bin/fixture` 的 tar.gz 安装包
+    fn create_fixture_archive(path: &Path) {
+        let file = fs::File::create(path).unwrap();
+        let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+
+        let content = b"#!/bin/sh\necho fixture\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "fixture-1.0.0/bin/fixture", &content[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    /// 安装包由 `create_fixture_archive` 生成的测试环境
+    fn fixture_environment(post_install: &[&str]) -> Environment {
+        serde_json::from_value(json!({
+            "name": "fixture",
+            "description": "测试环境",
+            "repository": [
+                "https://dead.example.com/env-pipeline-fixture-%version%.tar.gz",
+                "https://example.com/env-pipeline-fixture-%version%.tar.gz"
+            ],
+            "args": [],
+            "executable": ["%INSTALL_DIR%", "bin"],
+            "environment": {},
+            "post_install": post_install
+        }))
+        .unwrap()
+    }
+
+    /// 在 `root` 下创建安装包, 返回只访问本地文件和内存配置的安装上下文
+    fn fixture_context(
+        root: &Path,
+    ) -> InstallContext<FixtureDownloader, crate::pipeline::ArchiveExtractor, MemoryActivator> {
+        let _ = fs::remove_dir_all(root);
+        fs::create_dir_all(root).unwrap();
+        let archive = root.join("fixture.tar.gz");
+        create_fixture_archive(&archive);
+
+        let mut config = default_config();
+        config.cache_dir = Some(root.join("cache").to_str().unwrap().to_string());
+        InstallContext {
+            install_dir: root.join("install"),
+            config: config.clone(),
+            downloader: FixtureDownloader {
+                archive,
+                dead_host: Some("dead.example.com"),
+            },
+            extractor: crate::pipeline::ArchiveExtractor,
+            activator: MemoryActivator {
+                config: std::sync::Mutex::new(config),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_install_with_fixture_archive() {
+        let root = std::env::temp_dir().join("env_install_pipeline_test");
+        let env = fixture_environment(&[]);
+        let ctx = fixture_context(&root);
+
+        // 第一个镜像无法访问时使用下一个
+        assert_eq!(choose_package(&ctx.config, &env, "1.0.0").unwrap().len(), 2);
+
+        // 中断的解压留下的空目录不算已安装
+        let partial_dir = ctx.version_dir("fixture", "1.0.0");
+        fs::create_dir_all(&partial_dir).unwrap();
+        assert!(!ctx.is_installed(&env, "1.0.0"));
+
+        install_with(&ctx, &env, &json!({ "version": "1.0.0" }))
+            .await
+            .unwrap();
+
+        let version_dir = root.join("install").join("fixture").join("fixture-1.0.0");
+        assert!(version_dir.join("bin").join("fixture").is_file());
+        assert!(version_dir.join(INSTALL_MARKER).is_file());
+        assert!(ctx.is_installed(&env, "1.0.0"));
+
+        let config = ctx.activator.config.lock().unwrap();
+        assert_eq!(
+            config.get_current_version("fixture"),
+            Some("1.0.0".to_string())
+        );
+        assert_eq!(
+            config.get_installed("fixture").unwrap().home_dir.as_deref(),
+            version_dir.to_str()
+        );
+        drop(config);
+
+        fs::remove_dir_all(&root).unwrap();
     }
 
-    // 如果没有找到映射，使用模板方式
-    let url = &env.repository;
-    let mut format = &DEFAULT_FORMAT[&os];
-    
+    #[tokio::test]
+    async fn test_install_with_failing_post_install() {
+        let root = std::env::temp_dir().join("env_install_post_install_test");
+        let env = fixture_environment(&["exit 3"]);
+        let ctx = fixture_context(&root);
+
+        // 安装后命令失败只提示, 版本仍然记录为已安装
+        install_with(&ctx, &env, &json!({ "version": "1.0.0" }))
+            .await
+            .unwrap();
 
-    let mut package_url = url
-        .replace("%version%", version)
-        .replace("%arch%", &arch)
-        .replace("%platform%", &os)
-        .replace("%format%", format.as_str().unwrap_or(""));
+        let version_dir = ctx.version_dir("fixture", "1.0.0");
+        assert!(version_dir.join(INSTALL_MARKER).is_file());
+        assert!(ctx.is_installed(&env, "1.0.0"));
+        assert_eq!(
+            ctx.activator
+                .config
+                .lock()
+                .unwrap()
+                .get_current_version("fixture"),
+            Some("1.0.0".to_string())
+        );
 
-    if os == "windows" && package_url.contains("rustup-init") {
-        package_url = format!("{}.exe", package_url);
+        fs::remove_dir_all(&root).unwrap();
     }
-    package_url
 }
@@ -0,0 +1,284 @@
+//! 可执行安装程序的处理, 这类安装包不能解压, 需要直接运行
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{anyhow, Result};
+
+use crate::env_config::Environment;
+use crate::environment::resolve_environment;
+use crate::{get_temp_dir, status};
+
+/// 安装程序类型
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InstallerType {
+    /// rustup-init, 运行后安装指定的工具链
+    Rustup,
+    Msi,
+    Exe,
+    Pkg,
+    Dmg,
+}
+
+impl fmt::Display for InstallerType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            InstallerType::Rustup => "rustup-init",
+            InstallerType::Msi => "msi",
+            InstallerType::Exe => "exe",
+            InstallerType::Pkg => "pkg",
+            InstallerType::Dmg => "dmg",
+        };
+        f.write_str(name)
+    }
+}
+
+/// 根据文件名判断是否为安装程序, 压缩包返回 None
+pub fn get_installer_type(filename: &str) -> Option<InstallerType> {
+    let filename = Path::new(filename).file_name()?.to_str()?.to_lowercase();
+
+    if filename.starts_with("rustup-init") {
+        return Some(InstallerType::Rustup);
+    }
+
+    match filename.rsplit('.').next()? {
+        "msi" => Some(InstallerType::Msi),
+        "exe" => Some(InstallerType::Exe),
+        "pkg" => Some(InstallerType::Pkg),
+        "dmg" => Some(InstallerType::Dmg),
+        _ => None,
+    }
+}
+
+/// 运行安装程序, 将环境安装到版本目录
+pub fn run_installer(
+    installer: InstallerType,
+    env: &Environment,
+    version: &str,
+    filename: &str,
+    version_dir: &Path,
+) -> Result<()> {
+    match installer {
+        InstallerType::Rustup => run_rustup_init(env, version, filename, version_dir),
+        InstallerType::Msi if cfg!(windows) => run_msi(filename, version_dir),
+        InstallerType::Exe if cfg!(windows) => run_exe(filename, version_dir),
+        InstallerType::Pkg if cfg!(target_os = "macos") => run_pkg(filename, version_dir),
+        InstallerType::Dmg if cfg!(target_os = "macos") => run_dmg(filename, version_dir),
+        _ => Err(anyhow!(
+            "暂不支持在当前平台运行 {} 格式的安装包: {}",
+            installer,
+            filename
+        )),
+    }
+}
+
+/// 执行安装命令并检查退出状态
+fn run_command(command: &mut Command, program: &str) -> Result<()> {
+    let status = command
+        .status()
+        .map_err(|e| anyhow!("无法运行 {}: {}", program, e))?;
+
+    if !status.success() {
+        return Err(anyhow!("{} 执行失败: {}", program, status));
+    }
+    Ok(())
+}
+
+/// 使用 msiexec 静默安装到版本目录
+fn run_msi(filename: &str, version_dir: &Path) -> Result<()> {
+    fs::create_dir_all(version_dir)?;
+
+    status!("正在运行 msiexec 安装: {}", filename);
+    run_command(
+        Command::new("msiexec")
+            .args(["/i", filename, "/qn", "/norestart"])
+            .arg(format!("INSTALLDIR={}", version_dir.display())),
+        "msiexec",
+    )
+}
+
+/// 静默运行 exe 安装程序, 同时传入 Inno Setup 与 NSIS 的静默参数和安装目录,
+/// NSIS 要求 /D= 必须是最后一个参数
+fn run_exe(filename: &str, version_dir: &Path) -> Result<()> {
+    fs::create_dir_all(version_dir)?;
+
+    status!("正在静默运行安装程序: {}", filename);
+    run_command(
+        Command::new(filename)
+            .args(["/VERYSILENT", "/SUPPRESSMSGBOXES", "/NORESTART"])
+            .arg(format!("/DIR={}", version_dir.display()))
+            .arg("/S")
+            .arg(format!("/D={}", version_dir.display())),
+        filename,
+    )
+}
+
+/// 使用 pkgutil 展开 pkg, 将各组件的 Payload 复制到版本目录, 不写入系统或用户目录
+fn run_pkg(filename: &str, version_dir: &Path) -> Result<()> {
+    let expand_dir = get_temp_dir().join("env_pkg_expand");
+    if expand_dir.exists() {
+        fs::remove_dir_all(&expand_dir)?;
+    }
+
+    status!("正在展开 pkg: {}", filename);
+    run_command(
+        Command::new("pkgutil")
+            .arg("--expand-full")
+            .arg(filename)
+            .arg(&expand_dir),
+        "pkgutil",
+    )?;
+
+    let ret = copy_payloads(&expand_dir, version_dir);
+    let _ = fs::remove_dir_all(&expand_dir);
+    ret
+}
+
+/// 复制展开后 pkg 中的 Payload 内容, 兼容单组件与多组件的 pkg
+fn copy_payloads(expand_dir: &Path, version_dir: &Path) -> Result<()> {
+    let mut payloads = vec![expand_dir.join("Payload")];
+    for entry in fs::read_dir(expand_dir)? {
+        payloads.push(entry?.path().join("Payload"));
+    }
+
+    let payloads: Vec<_> = payloads.into_iter().filter(|p| p.is_dir()).collect();
+    if payloads.is_empty() {
+        return Err(anyhow!("pkg 中未找到 Payload: {}", expand_dir.display()));
+    }
+
+    fs::create_dir_all(version_dir)?;
+    for payload in payloads {
+        status!("复制 {} 到 {}", payload.display(), version_dir.display());
+        run_command(
+            Command::new("cp")
+                .arg("-R")
+                .arg(payload.join("."))
+                .arg(version_dir),
+            "cp",
+        )?;
+    }
+    Ok(())
+}
+
+/// 挂载 dmg, 安装其中的 pkg 或复制 .app 到版本目录, 完成后卸载
+fn run_dmg(filename: &str, version_dir: &Path) -> Result<()> {
+    let mount_point = get_temp_dir().join("env_dmg_mount");
+    if mount_point.exists() {
+        fs::remove_dir_all(&mount_point)?;
+    }
+    fs::create_dir_all(&mount_point)?;
+    let mount = mount_point.to_str().unwrap();
+
+    status!("正在挂载 {}", filename);
+    run_command(
+        Command::new("hdiutil").args([
+            "attach",
+            "-nobrowse",
+            "-readonly",
+            "-mountpoint",
+            mount,
+            filename,
+        ]),
+        "hdiutil",
+    )?;
+
+    let ret = install_from_volume(&mount_point, version_dir);
+
+    // 无论安装是否成功都需要卸载
+    run_command(
+        Command::new("hdiutil").args(["detach", mount, "-quiet"]),
+        "hdiutil",
+    )?;
+    ret
+}
+
+fn install_from_volume(volume: &Path, version_dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(volume)? {
+        let path = entry?.path();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("pkg") => return run_pkg(path.to_str().unwrap(), version_dir),
+            Some("app") => {
+                fs::create_dir_all(version_dir)?;
+                let target = version_dir.join(path.file_name().unwrap());
+                status!("复制 {} 到 {}", path.display(), target.display());
+                return run_command(Command::new("cp").arg("-R").arg(&path).arg(&target), "cp");
+            }
+            _ => {}
+        }
+    }
+    Err(anyhow!("dmg 中未找到 .pkg 或 .app: {}", volume.display()))
+}
+
+/// 运行 rustup-init, RUSTUP_HOME/CARGO_HOME 指向版本目录, 不修改 shell 配置
+fn run_rustup_init(
+    env: &Environment,
+    version: &str,
+    filename: &str,
+    version_dir: &Path,
+) -> Result<()> {
+    set_executable(filename)?;
+    fs::create_dir_all(version_dir)?;
+
+    status!("正在运行 rustup-init 安装工具链: {}", version);
+    let (vars, _) = resolve_environment(env, version)?;
+    run_command(
+        Command::new(filename)
+            .args(["-y", "--no-modify-path", "--default-toolchain", version])
+            .envs(vars),
+        "rustup-init",
+    )
+}
+
+#[cfg(unix)]
+pub(crate) fn set_executable(filename: &str) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(filename, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn set_executable(_filename: &str) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::installer::{copy_payloads, get_installer_type, InstallerType};
+
+    #[test]
+    fn test_get_installer_type() {
+        assert_eq!(
+            get_installer_type("/tmp/env_download_cache/rustup-init"),
+            Some(InstallerType::Rustup)
+        );
+        assert_eq!(
+            get_installer_type("rustup-init.exe"),
+            Some(InstallerType::Rustup)
+        );
+        assert_eq!(
+            get_installer_type("node-v22.12.0-x64.msi"),
+            Some(InstallerType::Msi)
+        );
+        assert_eq!(get_installer_type("apache-maven-3.9.9-bin.tar.gz"), None);
+    }
+
+    #[test]
+    fn test_copy_payloads() {
+        let root = std::env::temp_dir().join("env_test_copy_payloads");
+        let _ = std::fs::remove_dir_all(&root);
+        let expand = root.join("expand");
+        std::fs::create_dir_all(expand.join("jdk.pkg/Payload/Contents/Home/bin")).unwrap();
+        std::fs::write(expand.join("jdk.pkg/Payload/Contents/Home/bin/java"), "").unwrap();
+        std::fs::create_dir_all(expand.join("Resources")).unwrap();
+
+        let version_dir = root.join("17.0.2");
+        copy_payloads(&expand, &version_dir).unwrap();
+        assert!(version_dir.join("Contents/Home/bin/java").exists());
+
+        assert!(copy_payloads(&root.join("17.0.2"), &root.join("empty")).is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,159 @@
+//! Ctrl-C 处理, 中断或超过 `--deadline` 时删除未完成的解压目录及版本目录后退出,
+//! 下载缓存中的 `.part` 文件保留, 下次可以继续下载
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// 安装中尚未完成的目录, 中断时删除
+    static ref PENDING: Mutex<Vec<PathBuf>> = Mutex::new(vec![]);
+}
+
+/// 超过 `--deadline` 后置位, 解压及下载在处理下一个条目或数据块前检查并停止
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// 通知正在进行的解压及下载尽快停止
+pub fn cancel() {
+    CANCELLED.store(true, Ordering::Relaxed);
+}
+
+/// 已取消时返回错误, 由解压及下载的循环调用
+pub fn check_cancelled() -> Result<()> {
+    if CANCELLED.load(Ordering::Relaxed) {
+        return Err(anyhow!("操作已取消"));
+    }
+    Ok(())
+}
+
+/// 登记安装中尚未完成的目录, 离开作用域后中断时不再删除
+pub struct PendingCleanup {
+    paths: Vec<PathBuf>,
+}
+
+impl PendingCleanup {
+    pub fn new(paths: &[&Path]) -> PendingCleanup {
+        let paths: Vec<PathBuf> = paths.iter().map(|p| p.to_path_buf()).collect();
+        lock_pending().extend(paths.iter().cloned());
+        PendingCleanup { paths }
+    }
+}
+
+impl Drop for PendingCleanup {
+    fn drop(&mut self) {
+        let mut pending = lock_pending();
+        for path in &self.paths {
+            if let Some(index) = pending.iter().position(|p| p == path) {
+                pending.remove(index);
+            }
+        }
+    }
+}
+
+/// 中断可能发生在持有锁的线程 panic 之后, 忽略锁中毒
+fn lock_pending() -> std::sync::MutexGuard<'static, Vec<PathBuf>> {
+    PENDING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 删除所有未完成的目录
+pub fn cleanup_pending() {
+    let paths = lock_pending().clone();
+    for path in paths {
+        if path.exists() {
+            let _ = fs::remove_dir_all(&path);
+        }
+    }
+}
+
+/// 删除未完成的目录后退出, 退出码与 shell 中被 SIGINT 终止时一致
+fn cleanup_and_exit() -> ! {
+    cleanup_pending();
+
+    // 交互提示会隐藏光标, 退出前恢复
+    eprint!("\x1b[?25h");
+    eprintln!("\n操作已取消");
+    std::process::exit(130);
+}
+
+/// 注册 SIGINT 处理, 信号处理函数只向管道写入一个字节, 由单独的线程完成清理
+#[cfg(unix)]
+pub fn install_handler() -> Result<()> {
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    static WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+
+    extern "C" fn on_sigint(_: libc::c_int) {
+        let fd = WRITE_FD.load(Ordering::Relaxed);
+        unsafe {
+            libc::write(fd, [1u8].as_ptr() as *const libc::c_void, 1);
+        }
+    }
+
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    WRITE_FD.store(fds[1], Ordering::Relaxed);
+
+    std::thread::spawn(move || loop {
+        let mut buf = [0u8; 1];
+        let n = unsafe { libc::read(fds[0], buf.as_mut_ptr() as *mut libc::c_void, 1) };
+        if n == 1 {
+            cleanup_and_exit();
+        }
+        if n == 0 || std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            return;
+        }
+    });
+
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+    Ok(())
+}
+
+/// 注册控制台 Ctrl-C 处理, 回调在系统创建的线程中执行, 可以直接清理
+#[cfg(windows)]
+pub fn install_handler() -> Result<()> {
+    use windows_sys::Win32::System::Console::{SetConsoleCtrlHandler, CTRL_C_EVENT};
+
+    unsafe extern "system" fn on_ctrl(ctrl_type: u32) -> i32 {
+        if ctrl_type == CTRL_C_EVENT {
+            cleanup_and_exit();
+        }
+        0
+    }
+
+    if unsafe { SetConsoleCtrlHandler(Some(on_ctrl), 1) } == 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_cleanup() {
+        let temp = Path::new("/tmp/env-pending/java/temp");
+        let version_dir = Path::new("/tmp/env-pending/java/java-21");
+
+        let guard = PendingCleanup::new(&[temp, version_dir]);
+        {
+            let _other = PendingCleanup::new(&[temp]);
+            assert_eq!(lock_pending().iter().filter(|p| *p == temp).count(), 2);
+        }
+        assert!(lock_pending().iter().any(|p| p == version_dir));
+
+        drop(guard);
+        assert!(!lock_pending().iter().any(|p| p == temp || p == version_dir));
+    }
+}
@@ -1,25 +1,32 @@
-use anyhow::{anyhow, Ok, Result};
-use check::is_downloaded;
-use clap::{Args, Parser, Subcommand, ValueEnum};
-use dialoguer::Password;
-use dialoguer::{theme::ColorfulTheme, Input, MultiSelect, Select};
-use env_config::{EnvConfig, Environment, EnvironmentSelectArgs, ENV_CONFIG};
-use environment::switch_version;
-use serde_json::{json, Value};
-use std::ops::Index;
-use std::path::PathBuf;
-use std::{fs, usize};
-use zip::auto_unzip;
+use clap::builder::PossibleValuesParser;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use env_config::Channel;
+use output::{OutputFormat, Verbosity};
+use std::{io, path::PathBuf};
 
+pub mod cache_index;
 pub mod check;
+pub mod database;
 pub mod download;
 pub mod env_config;
 pub mod environment;
+pub mod error;
+pub mod i18n;
 pub mod install;
+pub mod installer;
+pub mod interrupt;
+pub mod options;
+pub mod output;
 pub mod path;
+pub mod pipeline;
+pub mod repository;
+pub mod search;
+pub mod version;
 pub mod zip;
 
 /// 自定义Result类型，用于统一错误处理
+pub use anyhow::Result;
 
 /// 命令行参数结构体
 #[derive(Parser)]
@@ -31,6 +38,59 @@ pub struct EnvArgs {
     /// 子命令指定要安装的环境
     #[command(subcommand)]
     pub command: Option<EnvSubCommand>,
+
+    /// 输出格式, json 模式下安装过程以每行一个JSON事件输出
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Human)]
+    pub format: OutputFormat,
+
+    /// 只打印将要执行的操作, 不下载、不解压、不修改环境变量
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// 为所有用户设置环境变量(写入 /etc/environment), 需要 root 权限
+    #[arg(long, global = true)]
+    pub system: bool,
+
+    /// 跳过下载大文件前的确认
+    #[arg(short, long, global = true)]
+    pub yes: bool,
+
+    /// 离线模式, 只使用下载缓存中已有的安装包, 不访问网络
+    #[arg(long, global = true)]
+    pub offline: bool,
+
+    /// 安装完成后保留下载缓存中的安装包, 便于之后重新安装或复制到其他机器
+    #[arg(long, global = true)]
+    pub keep_archive: bool,
+
+    /// 整个操作(下载、解压及切换版本)的最长时间, 超过后取消并清理未完成的安装
+    #[arg(long, global = true, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub deadline: Option<u64>,
+
+    /// 下载缓存目录, 覆盖配置中的 cache_dir
+    #[arg(long, global = true, value_name = "DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// 静默模式, 不显示进度条及提示信息, 只输出错误
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// 输出调试信息, 如平台识别结果及下载地址的来源
+    #[arg(short, long, global = true, conflicts_with = "quiet")]
+    pub verbose: bool,
+}
+
+impl EnvArgs {
+    /// 根据命令行参数计算输出详细程度
+    pub fn verbosity(&self) -> Verbosity {
+        if self.quiet {
+            Verbosity::Quiet
+        } else if self.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
 }
 
 #[derive(Subcommand, Clone, Debug)]
@@ -41,27 +101,231 @@ pub enum EnvSubCommand {
         #[arg(short, long)]
         dir: Option<PathBuf>,
 
-        /// 刷新配置
+        /// 用默认配置刷新内置环境并重新扫描安装目录, 修改前备份原配置
         #[arg(long)]
         flush: bool,
+
+        /// 从刷新前的备份恢复配置
+        #[arg(long, conflicts_with = "flush")]
+        restore: bool,
+
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
     },
 
     Dev(DevEnvironmentArgs),
 
+    /// 清理下载缓存
+    Clean {
+        /// 只清理修改时间早于指定天数的文件
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u64>,
+    },
+
+    /// 使用指定版本运行命令, 不切换全局版本
+    Exec {
+        /// 环境名称
+        name: String,
+
+        /// 使用的版本, 默认为当前版本
+        #[arg(long)]
+        version: Option<String>,
+
+        /// 要执行的命令及参数, 放在 `--` 之后
+        #[arg(last = true, required = true)]
+        args: Vec<String>,
+    },
+
+    /// 查询上游发布的可用版本
+    Search {
+        /// 环境名称
+        name: String,
+    },
+
+    /// 输出环境当前使用的版本, 只输出版本号便于在脚本中使用
+    Current {
+        /// 环境名称
+        name: String,
+    },
+
+    /// 输出环境当前版本的可执行文件目录, 未安装时返回非零退出码
+    Which {
+        /// 环境名称
+        name: String,
+    },
+
+    /// 概览安装目录、下载缓存、下载地址来源及各环境的当前版本
+    Status,
+
+    /// 输出在当前 shell 中使用当前版本的命令, 不修改配置文件, 用法: eval "$(env shellenv java)"
+    Shellenv {
+        /// 环境名称
+        name: String,
+
+        /// 目标shell, 默认根据 SHELL 环境变量检测
+        #[arg(long, value_enum)]
+        shell: Option<Shell>,
+    },
+
     /// 版本选择
     Choose {
+        /// 环境名称, 支持内置环境及配置文件中自定义的环境
+        name: String,
+
+        /// 只列出已安装的版本, 不进行交互选择
+        #[arg(long)]
+        list_versions: bool,
+
+        /// 要切换到的已安装版本或别名, 不指定时交互选择
+        #[arg(conflicts_with_all = ["list_versions", "previous"])]
+        version: Option<String>,
+
+        /// 切换回上一次使用的版本
+        #[arg(long, conflicts_with = "list_versions")]
+        previous: bool,
+    },
+
+    /// 管理版本别名, 如 `env alias java lts 17.0.9` 后可以用 `env choose java lts` 切换
+    Alias {
+        /// 环境名称
+        name: String,
+
+        /// 别名, 不指定时列出环境的所有别名
+        alias: Option<String>,
+
+        /// 别名指向的版本, 不指定时输出别名当前指向的版本
+        version: Option<String>,
+
+        /// 删除别名
+        #[arg(long, requires = "alias", conflicts_with = "version")]
+        delete: bool,
+    },
+
+    /// 重新计算下载缓存中安装包的 sha256, 与下载时记录的值比较
+    Verify {
+        /// 环境名称
+        name: String,
+
+        /// 版本或别名
+        version: String,
+    },
+
+    /// 修复已安装的环境: 重新下载目录缺失或不完整的版本, 并按当前安装目录重新设置环境变量及 PATH
+    #[command(alias = "reinstall-all")]
+    Repair {
+        /// 环境名称, 不指定时修复所有已安装的环境
+        name: Option<String>,
+    },
+
+    /// 删除较旧的已安装版本, 只保留最新的几个及当前使用的版本
+    Prune {
+        /// 环境名称
+        name: String,
+
+        /// 保留的版本数量, 覆盖配置中的 keep_versions
+        #[arg(long, value_name = "N")]
+        keep: Option<usize>,
+    },
+
+    /// 导出自定义环境及已安装的版本, 用于迁移到其他机器
+    Export {
+        /// 导出文件路径
+        path: PathBuf,
+    },
+
+    /// 导入配置, 并安装缺失的版本
+    Import {
+        /// 导入文件路径
+        path: PathBuf,
+    },
+
+    /// 下载最新的仓库索引, 无需升级程序即可安装新发布的版本
+    UpdateIndex {
+        /// 索引地址, 覆盖配置中的 index_url
+        #[arg(long)]
+        url: Option<String>,
+    },
+
+    /// 生成命令补全脚本, 输出到标准输出
+    Completions {
+        /// 目标shell
         #[arg(value_enum)]
-        name: ChooseEnvironment,
+        shell: Shell,
+    },
+}
+
+/// 读取或修改顶层配置项, 如 cache_dir、path_strategy、keep_versions
+#[derive(Subcommand, Clone, Debug)]
+pub enum ConfigAction {
+    /// 输出配置项的值
+    Get {
+        /// 配置项名称
+        key: String,
+    },
+
+    /// 修改配置项, 值按 JSON 解析, 无法解析时作为字符串, null 表示取消设置
+    Set {
+        /// 配置项名称
+        key: String,
+
+        /// 新的值
+        value: String,
     },
 }
 
 #[derive(Args, Clone, Debug)]
 pub struct DevEnvironmentArgs {
-    // 安装所有支持的环境
-    // #[arg(short, long)]
-    // pub all: bool,
-    #[arg(value_enum)]
-    pub name: Option<ChooseEnvironment>,
+    /// 不交互, 安装默认的环境组合(Java、Node.js、Rust), 也可以使用 `env dev all`
+    #[arg(short, long, conflicts_with_all = ["name", "version"])]
+    pub all: bool,
+
+    /// 按 JSON 安装清单安装, 如 `{"installs":[{"name":"java","version":"17"}]}`, `-` 表示从标准输入读取
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["all", "name", "version"])]
+    pub json_config: Option<String>,
+
+    /// 环境名称, 支持内置环境及配置文件中自定义的环境
+    pub name: Option<String>,
+
+    /// 直接安装指定版本, 跳过交互式配置
+    #[arg(long, requires = "name")]
+    pub version: Option<String>,
+
+    /// 使用本地安装包安装, 支持文件路径及 file:// 地址, 不下载; 需要通过 --version 指定版本
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "version",
+        conflicts_with_all = ["only_download", "no_cache"]
+    )]
+    pub source: Option<String>,
+
+    /// 删除已安装的版本目录及缓存的安装包, 重新下载安装
+    #[arg(long)]
+    pub force: bool,
+
+    /// 安装后不切换当前版本, 只登记到已安装的版本中
+    #[arg(long)]
+    pub no_switch: bool,
+
+    /// 只下载安装包到缓存, 不解压也不切换版本
+    #[arg(long, conflicts_with = "no_switch")]
+    pub only_download: bool,
+
+    /// 不写入下载缓存, tar.gz 等格式边下载边解压, 不支持断点续传
+    #[arg(long, conflicts_with = "only_download")]
+    pub no_cache: bool,
+
+    /// 只列出指定发布通道的版本, lts 为长期支持版本, beta 包含测试版本
+    #[arg(long, value_enum)]
+    pub channel: Option<Channel>,
+
+    /// 下载其他操作系统的安装包(windows/linux/macos), 与当前平台不同时只下载不安装
+    #[arg(long, value_parser = install::parse_os)]
+    pub os: Option<String>,
+
+    /// 下载其他架构的安装包(x64/aarch64/armv7), 与当前平台不同时只下载不安装
+    #[arg(long, value_parser = install::parse_arch)]
+    pub arch: Option<String>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, PartialOrd, Ord, ValueEnum)]
@@ -97,6 +361,22 @@ impl ChooseEnvironment {
     }
 }
 
+/// 输出补全脚本, 环境名称按生成时的配置补全
+pub fn print_completions(shell: Shell, names: &[String]) {
+    let names = PossibleValuesParser::new(names.iter().cloned());
+    let mut cmd = EnvArgs::command();
+    for sub in [
+        "dev", "exec", "search", "current", "which", "shellenv", "choose", "alias", "verify",
+        "repair", "prune",
+    ] {
+        cmd = cmd.mut_subcommand(sub, |c| {
+            c.mut_arg("name", |a| a.value_parser(names.clone()))
+        });
+    }
+    let bin_name = cmd.get_name().to_string();
+    clap_complete::generate(shell, &mut cmd, bin_name, &mut io::stdout());
+}
+
 pub fn deduplicate<T: Eq + std::hash::Hash + Clone>(arr: &[T]) -> Vec<T> {
     let mut set = std::collections::HashSet::new();
     let mut result = Vec::new();
@@ -122,6 +402,26 @@ pub fn get_env_home_dir() -> PathBuf {
     PathBuf::from(get_home_dir()).join(".dev_env")
 }
 
+/// 获取配置文件目录, Linux 下遵循 XDG 规范使用 `$XDG_CONFIG_HOME/env`(默认 `~/.config/env`)
+pub fn get_env_config_dir() -> PathBuf {
+    if cfg!(target_os = "linux") {
+        if let Some(dir) = dirs::config_dir() {
+            return dir.join("env");
+        }
+    }
+    get_env_home_dir()
+}
+
+/// 获取默认安装目录, Linux 下遵循 XDG 规范使用 `$XDG_DATA_HOME/env`(默认 `~/.local/share/env`)
+pub fn get_env_data_dir() -> PathBuf {
+    if cfg!(target_os = "linux") {
+        if let Some(dir) = dirs::data_dir() {
+            return dir.join("env");
+        }
+    }
+    get_env_home_dir()
+}
+
 /// 获取系统临时目录路径
 pub fn get_temp_dir() -> PathBuf {
     if cfg!(windows) {
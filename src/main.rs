@@ -1,59 +1,383 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::Parser;
-use env::env_config::{flush_env_config, EnvConfig};
-use env::install::{choose_and_install, choose_and_install_from, choose_version};
-use env::{EnvArgs, EnvSubCommand};
+use clap_complete::Shell;
+use env::check::check_version;
+use env::download::clean_download_cache;
+use env::env_config::{flush_env_config, restore_env_config, EnvConfig};
+use env::environment::{exec_with_version, resolve_bin_dir, resolve_installed, shell_env_script};
+use env::error::{exit_code, EnvError};
+use env::i18n::set_lang;
+use env::install::{
+    choose_and_install, choose_and_install_from, choose_previous_version, choose_version_to,
+    export_config, import_config, install_default_environments, install_environment,
+    install_from_spec, list_versions, manage_alias, manage_versions, print_status, prune_versions,
+    read_install_spec, repair_installed, verify_package,
+};
+use env::interrupt::{cancel, cleanup_pending, install_handler};
+use env::options::{
+    is_dry_run, set_assume_yes, set_cache_dir, set_channel, set_dry_run, set_force,
+    set_keep_archive, set_no_cache, set_no_switch, set_offline, set_only_download, set_source,
+    set_system, set_target_arch, set_target_os,
+};
+use env::output::{emit_error, init_logger, is_human_output, set_output_format, set_verbosity};
+use env::path::check_system_privilege;
+use env::repository::update_index;
+use env::search::{known_versions, search_versions};
+use env::{print_completions, status, t, ConfigAction, EnvArgs, EnvSubCommand};
+use serde_json::{json, Value};
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = EnvArgs::parse();
+    // 设置 deadline 时命令在其他线程中运行, 参数在整个进程中有效
+    let args: &'static EnvArgs = Box::leak(Box::new(EnvArgs::parse()));
+    set_output_format(args.format);
+    set_dry_run(args.dry_run);
+    set_cache_dir(args.cache_dir.clone());
+    set_assume_yes(args.yes);
+    set_offline(args.offline);
+    set_keep_archive(args.keep_archive);
+    set_system(args.system);
+    set_verbosity(args.verbosity());
+    init_logger(args.verbosity());
 
-    if let Err(e) = handle_cmd(&args).await {
-        println!("env error: {}", e);
+    // 中断时清理未完成的安装
+    if let Err(e) = install_handler() {
+        log::warn!("注册 Ctrl-C 处理失败: {}", e);
+    }
+
+    let result = match args.deadline {
+        Some(secs) => run_with_deadline(args, secs).await,
+        None => handle_cmd(args).await,
+    };
+    // 命令只返回退出码, 在这里退出时安装锁、未完成目录的清理及配置的写入都已完成
+    match result {
+        Ok(0) => {}
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            // 错误输出到 stderr, `$(env current java)` 之类的命令替换不会把错误当作结果
+            if is_human_output() {
+                eprintln!("{}", t!("error.prefix", e));
+            } else {
+                emit_error("command", "", "", &e);
+            }
+            if matches!(e.downcast_ref(), Some(EnvError::DeadlineExceeded(_))) {
+                // 超时的命令可能仍阻塞在无法中途停止的操作中, 退出前再清理一次它留下的目录
+                cleanup_pending();
+            }
+            std::process::exit(exit_code(&e));
+        }
     }
 
     Ok(())
 }
 
-pub async fn handle_cmd(args: &EnvArgs) -> Result<()> {
-    let mut env_config = EnvConfig::load_deserialize()?;
+/// 超时后等待解压及下载停止的时间
+const CANCEL_GRACE: Duration = Duration::from_secs(5);
+
+/// 在阻塞线程中运行命令, 解压等阻塞操作不会占用驱动定时器的工作线程而让超时失效,
+/// 超时后通知命令停止, 等待其退出后删除未完成的安装目录
+async fn run_with_deadline(args: &'static EnvArgs, secs: u64) -> Result<i32> {
+    let handle = tokio::runtime::Handle::current();
+    let mut task = tokio::task::spawn_blocking(move || handle.block_on(handle_cmd(args)));
+    match tokio::time::timeout(Duration::from_secs(secs), &mut task).await {
+        Ok(result) => result?,
+        Err(_) => {
+            cancel();
+            let _ = tokio::time::timeout(CANCEL_GRACE, task).await;
+            cleanup_pending();
+            Err(EnvError::DeadlineExceeded(secs).into())
+        }
+    }
+}
+
+/// 执行命令, 返回进程的退出码, 命令本身的退出码(如 `env exec`)或未找到版本时不为 0
+pub async fn handle_cmd(args: &EnvArgs) -> Result<i32> {
+    if args.system && !args.dry_run {
+        check_system_privilege()?;
+    }
+
+    let env_config = EnvConfig::cached()?;
+    set_lang(env_config.lang);
 
     if let Some(command) = &args.command {
         match command {
             EnvSubCommand::Dev(args) => {
+                set_force(args.force);
+                set_no_switch(args.no_switch);
+                set_only_download(args.only_download);
+                set_no_cache(args.no_cache);
+                set_source(args.source.clone());
+                set_channel(args.channel);
+                set_target_os(args.os.clone());
+                set_target_arch(args.arch.clone());
                 let install_dir = PathBuf::from(&env_config.install_path);
 
-                if let Some(name) = &args.name {
-                    let name = name.get_name();
-                    let env = env_config.get_enviroment(name);
+                // 没有名为 all 的自定义环境时, `env dev all` 等同于 `--all`
+                let all = args.all
+                    || args.name.as_deref().is_some_and(|name| {
+                        name.eq_ignore_ascii_case("all")
+                            && env_config.find_environment(name).is_none()
+                    });
 
-                    if env.is_none() {
-                        println!("不支持的环境: {}", name);
-                        return Ok(());
+                if let Some(path) = &args.json_config {
+                    // 安装清单用于脚本, 跳过所有确认提示
+                    set_assume_yes(true);
+                    let spec = read_install_spec(path)?;
+                    install_from_spec(&spec, &install_dir).await?;
+                } else if all {
+                    install_default_environments(&install_dir).await?;
+                } else if let Some(name) = &args.name {
+                    let env = env_config
+                        .find_environment(name)
+                        .ok_or_else(|| anyhow!(t!("error.unsupported_environment", name)))?;
+
+                    // 未指定版本时使用项目固定的版本
+                    let version = args
+                        .version
+                        .clone()
+                        .or_else(|| env_config.get_pinned_version(&env.name));
+
+                    if let Some(version) = &version {
+                        let known = known_versions(&env_config, &env, version).await;
+                        let version = check_version(&env, version, &known)?;
+                        let install_args = json!({ "version": version });
+                        install_environment(&env, &install_args, &install_dir).await?;
+                    } else {
+                        choose_and_install_from(&env, &install_dir).await?;
+                    }
+                } else {
+                    let pinned = env_config.get_pinned_environments();
+                    if pinned.is_empty() {
+                        choose_and_install(&install_dir).await?;
                     }
 
-                    choose_and_install_from(&env.unwrap(), &install_dir).await?;
+                    // 项目固定了版本时直接安装, 不再交互选择
+                    for (env, version) in pinned {
+                        status!("{}", t!("env.pinned", env.name, version));
+                        let known = known_versions(&env_config, &env, &version).await;
+                        let version = check_version(&env, &version, &known)?;
+                        let install_args = json!({ "version": version });
+                        install_environment(&env, &install_args, &install_dir).await?;
+                    }
+                }
+            }
+            EnvSubCommand::Clean { older_than } => {
+                clean_download_cache(*older_than)?;
+            }
+            EnvSubCommand::Exec {
+                name,
+                version,
+                args,
+            } => {
+                let env = env_config
+                    .find_environment(name)
+                    .ok_or_else(|| anyhow!(t!("env.not_found", name)))?;
+
+                let version = match version {
+                    Some(version) => version.clone(),
+                    None => env_config
+                        .get_current_version(&env.name)
+                        .ok_or_else(|| anyhow!(t!("env.no_current", env.name)))?,
+                };
+
+                return exec_with_version(&env, &version, args);
+            }
+            EnvSubCommand::Verify { name, version } => {
+                verify_package(name, version)?;
+            }
+            EnvSubCommand::Repair { name } => {
+                let install_dir = PathBuf::from(&env_config.install_path);
+                repair_installed(name.as_deref(), &install_dir).await?;
+            }
+            EnvSubCommand::Prune { name, keep } => {
+                prune_versions(name, *keep)?;
+            }
+            EnvSubCommand::Export { path } => {
+                export_config(path)?;
+            }
+            EnvSubCommand::Import { path } => {
+                let install_dir = PathBuf::from(&env_config.install_path);
+                import_config(path, &install_dir).await?;
+            }
+            EnvSubCommand::UpdateIndex { url } => {
+                update_index(url.as_deref()).await?;
+            }
+            EnvSubCommand::Completions { shell } => {
+                let names: Vec<String> = env_config
+                    .environments
+                    .iter()
+                    .map(|env| env.name.clone())
+                    .collect();
+                print_completions(*shell, &names);
+            }
+            EnvSubCommand::Search { name } => {
+                let env = env_config
+                    .find_environment(name)
+                    .ok_or_else(|| anyhow!(t!("env.not_found", name)))?;
+                let versions = search_versions(&env.name).await?;
+
+                if is_human_output() {
+                    let installed = env_config.get_install_versions(&env.name);
+                    for version in &versions {
+                        if installed.contains(version) {
+                            println!("{}", t!("env.installed_mark", version));
+                        } else {
+                            println!("{}", version);
+                        }
+                    }
                 } else {
-                    choose_and_install(&install_dir).await?;
+                    println!("{}", json!({ "name": env.name, "versions": versions }));
+                }
+            }
+            EnvSubCommand::Current { name } => {
+                let env = env_config
+                    .find_environment(name)
+                    .ok_or_else(|| anyhow!(t!("env.not_found", name)))?;
+
+                match env_config.get_current_version(&env.name) {
+                    Some(version) if is_human_output() => println!("{}", version),
+                    Some(version) => {
+                        println!("{}", json!({ "name": env.name, "version": version }))
+                    }
+                    None => {
+                        eprintln!("{}", t!("env.no_current", env.name));
+                        return Ok(1);
+                    }
                 }
             }
-            EnvSubCommand::Choose { name } => {
-                choose_version(name)?;
+            EnvSubCommand::Which { name } => {
+                let env = env_config
+                    .find_environment(name)
+                    .ok_or_else(|| anyhow!(t!("env.not_found", name)))?;
+
+                let installed = env_config.get_installed(&env.name);
+                let Some(version) = installed.and_then(|i| i.current_version.clone()) else {
+                    eprintln!("{}", t!("env.not_installed", env.name));
+                    return Ok(1);
+                };
+
+                let home_dir = installed.and_then(|i| i.home_of(&version));
+                let bin_dir = resolve_bin_dir(&env, &version, home_dir)?;
+                if is_human_output() {
+                    println!("{}", bin_dir.display());
+                } else {
+                    println!(
+                        "{}",
+                        json!({ "name": env.name, "version": version, "path": bin_dir })
+                    );
+                }
+            }
+            EnvSubCommand::Status => {
+                print_status()?;
+            }
+            EnvSubCommand::Shellenv { name, shell } => {
+                let env = env_config
+                    .find_environment(name)
+                    .ok_or_else(|| anyhow!(t!("env.not_found", name)))?;
+
+                let installed = env_config.get_installed(&env.name);
+                let Some(version) = installed.and_then(|i| i.current_version.clone()) else {
+                    eprintln!("{}", t!("env.not_installed", env.name));
+                    return Ok(1);
+                };
+
+                let default_shell = if cfg!(windows) {
+                    Shell::PowerShell
+                } else {
+                    Shell::Bash
+                };
+                let shell = shell.or_else(Shell::from_env).unwrap_or(default_shell);
+                let home_dir = installed.and_then(|i| i.home_of(&version));
+                let (vars, bin_dir) = resolve_installed(&env, &version, home_dir)?;
+                println!("{}", shell_env_script(shell, &vars, &bin_dir));
+            }
+            EnvSubCommand::Choose {
+                name,
+                list_versions: true,
+                ..
+            } => {
+                list_versions(name)?;
+            }
+            EnvSubCommand::Choose {
+                name,
+                previous: true,
+                ..
+            } => {
+                choose_previous_version(name)?;
+            }
+            EnvSubCommand::Choose {
+                name,
+                version: Some(version),
+                ..
+            } => {
+                choose_version_to(name, version)?;
+            }
+            EnvSubCommand::Alias {
+                name,
+                alias,
+                version,
+                delete,
+            } => {
+                manage_alias(name, alias.as_deref(), version.as_deref(), *delete)?;
+            }
+            EnvSubCommand::Choose { name, .. } => {
+                let install_dir = PathBuf::from(&env_config.install_path);
+                manage_versions(name, &install_dir).await?;
             }
-            EnvSubCommand::Config { dir, flush } => {
+            EnvSubCommand::Config {
+                action: Some(ConfigAction::Get { key }),
+                ..
+            } => {
+                let value = EnvConfig::cached()?.get_value(key)?;
+                println!("{}", serde_json::to_string_pretty(&value)?);
+            }
+            EnvSubCommand::Config {
+                action: Some(ConfigAction::Set { key, value }),
+                ..
+            } => {
+                if is_dry_run() {
+                    let mut config = EnvConfig::cached()?;
+                    let value = config.set_value(key, value)?;
+                    let value = serde_json::to_string_pretty(&value)?;
+                    status!("{}", t!("dry_run.set_config", key, value));
+                } else {
+                    let mut written = Value::Null;
+                    EnvConfig::update(|config| {
+                        written = config.set_value(key, value)?;
+                        Ok(())
+                    })?;
+                    let written = serde_json::to_string_pretty(&written)?;
+                    status!("{}", t!("config.set", key, written));
+                }
+            }
+            EnvSubCommand::Config {
+                dir,
+                flush,
+                restore,
+                ..
+            } => {
                 if let Some(dir) = dir {
-                    env_config.install_path = dir.as_os_str().to_str().unwrap().to_string();
-                    EnvConfig::save(&env_config)?;
+                    let install_path = dir.as_os_str().to_str().unwrap().to_string();
+                    EnvConfig::update(|config| {
+                        config.install_path = install_path;
+                        Ok(())
+                    })?;
                 }
 
                 if *flush {
                     flush_env_config()?;
                 }
 
+                if *restore {
+                    restore_env_config()?;
+                }
+
+                let env_config = EnvConfig::cached()?;
                 println!("\n{}", serde_json::to_string_pretty(&env_config)?);
             }
         }
     }
-    Ok(())
+    Ok(0)
 }
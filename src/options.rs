@@ -0,0 +1,148 @@
+//! 全局运行选项, 由命令行参数在启动时设置
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use crate::env_config::Channel;
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+static FORCE: AtomicBool = AtomicBool::new(false);
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+static NO_SWITCH: AtomicBool = AtomicBool::new(false);
+static ONLY_DOWNLOAD: AtomicBool = AtomicBool::new(false);
+static SYSTEM: AtomicBool = AtomicBool::new(false);
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+static NO_CACHE: AtomicBool = AtomicBool::new(false);
+static KEEP_ARCHIVE: AtomicBool = AtomicBool::new(false);
+static CHANNEL: RwLock<Option<Channel>> = RwLock::new(None);
+static CACHE_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+static TARGET_OS: RwLock<Option<String>> = RwLock::new(None);
+static TARGET_ARCH: RwLock<Option<String>> = RwLock::new(None);
+static SOURCE: RwLock<Option<String>> = RwLock::new(None);
+
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+/// 是否只打印执行计划, 不下载、不解压、不修改环境变量
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+pub fn set_force(force: bool) {
+    FORCE.store(force, Ordering::Relaxed);
+}
+
+/// 是否强制重新下载并安装已存在的版本
+pub fn is_force() -> bool {
+    FORCE.load(Ordering::Relaxed)
+}
+
+pub fn set_system(system: bool) {
+    SYSTEM.store(system, Ordering::Relaxed);
+}
+
+/// 是否为所有用户设置环境变量, 写入 /etc/environment 而不是用户的 shell 配置
+pub fn is_system() -> bool {
+    SYSTEM.load(Ordering::Relaxed)
+}
+
+pub fn set_no_switch(no_switch: bool) {
+    NO_SWITCH.store(no_switch, Ordering::Relaxed);
+}
+
+/// 安装后是否保持当前版本不变, 只登记新安装的版本
+pub fn is_no_switch() -> bool {
+    NO_SWITCH.load(Ordering::Relaxed)
+}
+
+pub fn set_only_download(only_download: bool) {
+    ONLY_DOWNLOAD.store(only_download, Ordering::Relaxed);
+}
+
+/// 是否只下载安装包到缓存, 不解压也不切换版本
+pub fn is_only_download() -> bool {
+    ONLY_DOWNLOAD.load(Ordering::Relaxed)
+}
+
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// 是否只使用下载缓存中已有的安装包, 不访问网络
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+pub fn set_no_cache(no_cache: bool) {
+    NO_CACHE.store(no_cache, Ordering::Relaxed);
+}
+
+/// 是否不写入下载缓存, 支持的格式边下载边解压
+pub fn is_no_cache() -> bool {
+    NO_CACHE.load(Ordering::Relaxed)
+}
+
+pub fn set_keep_archive(keep_archive: bool) {
+    KEEP_ARCHIVE.store(keep_archive, Ordering::Relaxed);
+}
+
+/// 安装完成后是否保留缓存的安装包, 与配置中的 `keep_downloads` 作用相同
+pub fn is_keep_archive() -> bool {
+    KEEP_ARCHIVE.load(Ordering::Relaxed)
+}
+
+pub fn set_channel(channel: Option<Channel>) {
+    *CHANNEL.write().unwrap() = channel;
+}
+
+/// 选择版本时使用的发布通道, 未指定时列出全部版本
+pub fn get_channel() -> Option<Channel> {
+    *CHANNEL.read().unwrap()
+}
+
+pub fn set_cache_dir(cache_dir: Option<PathBuf>) {
+    *CACHE_DIR.write().unwrap() = cache_dir;
+}
+
+/// 命令行指定的下载缓存目录, 优先于配置中的 `cache_dir`
+pub fn get_cache_dir() -> Option<PathBuf> {
+    CACHE_DIR.read().unwrap().clone()
+}
+
+pub fn set_source(source: Option<String>) {
+    *SOURCE.write().unwrap() = source;
+}
+
+/// 命令行指定的本地安装包, 设置时不解析下载地址也不下载
+pub fn get_source() -> Option<String> {
+    SOURCE.read().unwrap().clone()
+}
+
+pub fn set_assume_yes(yes: bool) {
+    ASSUME_YES.store(yes, Ordering::Relaxed);
+}
+
+/// 是否跳过确认提示
+pub fn is_assume_yes() -> bool {
+    ASSUME_YES.load(Ordering::Relaxed)
+}
+
+pub fn set_target_os(os: Option<String>) {
+    *TARGET_OS.write().unwrap() = os;
+}
+
+/// 命令行指定的目标操作系统, 未指定时使用当前系统
+pub fn get_target_os() -> Option<String> {
+    TARGET_OS.read().unwrap().clone()
+}
+
+pub fn set_target_arch(arch: Option<String>) {
+    *TARGET_ARCH.write().unwrap() = arch;
+}
+
+/// 命令行指定的目标架构, 未指定时使用当前架构
+pub fn get_target_arch() -> Option<String> {
+    TARGET_ARCH.read().unwrap().clone()
+}
@@ -0,0 +1,236 @@
+use clap::ValueEnum;
+use indicatif::{HumanBytes, ProgressBar, ProgressDrawTarget};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::io::IsTerminal;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::error::error_code;
+
+/// 输出格式
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// 面向用户的文本输出
+    #[default]
+    Human,
+    /// 每行一个JSON事件, 便于脚本解析
+    Json,
+}
+
+/// 输出详细程度, 由 `--quiet` 等参数决定
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum Verbosity {
+    /// 只输出错误
+    Quiet,
+    #[default]
+    Normal,
+    /// 额外输出调试信息
+    Verbose,
+}
+
+lazy_static! {
+    static ref OUTPUT_FORMAT: RwLock<OutputFormat> = RwLock::new(OutputFormat::default());
+    static ref VERBOSITY: RwLock<Verbosity> = RwLock::new(Verbosity::default());
+}
+
+pub fn set_output_format(format: OutputFormat) {
+    *OUTPUT_FORMAT.write().unwrap() = format;
+}
+
+pub fn get_output_format() -> OutputFormat {
+    *OUTPUT_FORMAT.read().unwrap()
+}
+
+pub fn is_human_output() -> bool {
+    get_output_format() == OutputFormat::Human
+}
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    *VERBOSITY.write().unwrap() = verbosity;
+}
+
+/// 输出到标准错误的日志, `--verbose` 时输出 debug 级别日志
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// 按输出详细程度初始化日志
+pub fn init_logger(verbosity: Verbosity) {
+    let level = match verbosity {
+        Verbosity::Quiet => log::LevelFilter::Error,
+        Verbosity::Normal => log::LevelFilter::Warn,
+        Verbosity::Verbose => log::LevelFilter::Debug,
+    };
+    // 重复初始化时忽略
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level);
+}
+
+pub fn get_verbosity() -> Verbosity {
+    *VERBOSITY.read().unwrap()
+}
+
+/// 是否输出提示信息
+pub fn show_status() -> bool {
+    is_human_output() && get_verbosity() >= Verbosity::Normal
+}
+
+/// 是否显示进度条
+pub fn show_progress() -> bool {
+    get_verbosity() >= Verbosity::Normal
+}
+
+/// 标准错误不是终端时输出纯文本进度的间隔
+const PLAIN_PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 进度条是否绘制动画, 只在标准错误是终端时绘制, 重定向到文件或 CI 日志时不输出控制字符
+pub fn is_progress_terminal() -> bool {
+    std::io::stderr().is_terminal()
+}
+
+/// 标准错误不是终端时使用的进度条: 不绘制, 由后台线程定期输出一行百分比,
+/// 进度条结束或被释放后停止输出
+pub fn plain_progress_bar(total: u64, label: &'static str, bytes: bool) -> ProgressBar {
+    let pb = ProgressBar::with_draw_target(Some(total), ProgressDrawTarget::hidden());
+    let weak = pb.downgrade();
+    std::thread::spawn(move || {
+        let mut last = None;
+        loop {
+            std::thread::sleep(PLAIN_PROGRESS_INTERVAL);
+            let Some(pb) = weak.upgrade() else {
+                return;
+            };
+            if pb.is_finished() {
+                return;
+            }
+            let position = pb.position();
+            if last != Some(position) {
+                last = Some(position);
+                eprintln!(
+                    "{}",
+                    format_plain_progress(label, position, pb.length(), bytes)
+                );
+            }
+        }
+    });
+    pb
+}
+
+/// 格式化一行纯文本进度, 如 `下载进度: 45% (12.00 MiB/26.67 MiB)`
+fn format_plain_progress(label: &str, position: u64, total: Option<u64>, bytes: bool) -> String {
+    let amount = |n: u64| {
+        if bytes {
+            HumanBytes(n).to_string()
+        } else {
+            n.to_string()
+        }
+    };
+    match total.filter(|total| *total > 0) {
+        Some(total) => format!(
+            "{}: {}% ({}/{})",
+            label,
+            (position.min(total) * 100 / total),
+            amount(position),
+            amount(total)
+        ),
+        // 服务器没有返回大小时只输出已完成的量
+        None => format!("{}: {}", label, amount(position)),
+    }
+}
+
+/// 输出提示信息, JSON 模式及 `--quiet` 下不输出以保证标准输出可解析
+#[macro_export]
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::output::show_status() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// 安装过程中的结构化事件
+#[derive(Serialize, Debug)]
+pub struct Event<'a> {
+    pub stage: &'a str,
+
+    #[serde(skip_serializing_if = "str::is_empty")]
+    pub name: &'a str,
+
+    #[serde(skip_serializing_if = "str::is_empty")]
+    pub version: &'a str,
+
+    pub status: &'a str,
+
+    /// 错误码, 只有可识别的错误才有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// JSON 模式下输出一条事件
+pub fn emit_event(stage: &str, name: &str, version: &str, status: &str) {
+    emit(Event {
+        stage,
+        name,
+        version,
+        status,
+        code: None,
+        message: None,
+    });
+}
+
+/// JSON 模式下输出一条错误事件
+pub fn emit_error(stage: &str, name: &str, version: &str, err: &anyhow::Error) {
+    emit(Event {
+        stage,
+        name,
+        version,
+        status: "error",
+        code: error_code(err),
+        message: Some(err.to_string()),
+    });
+}
+
+fn emit(event: Event) {
+    if get_output_format() == OutputFormat::Json {
+        println!("{}", serde_json::to_string(&event).unwrap());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_plain_progress() {
+        assert_eq!(
+            format_plain_progress("下载进度", 512 * 1024, Some(2 * 1024 * 1024), true),
+            "下载进度: 25% (512.00 KiB/2.00 MiB)"
+        );
+        assert_eq!(
+            format_plain_progress("解压进度", 30, Some(30), false),
+            "解压进度: 100% (30/30)"
+        );
+        assert_eq!(
+            format_plain_progress("下载进度", 2048, None, true),
+            "下载进度: 2.00 KiB"
+        );
+    }
+}
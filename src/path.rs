@@ -1,25 +1,161 @@
+#[cfg(target_os = "windows")]
 use anyhow::Result;
 use std::fs::OpenOptions;
 use std::io::{BufRead, BufReader, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+#[cfg(target_os = "windows")]
 use std::process::Command;
 use std::{env, io};
 
+use crate::env_config::{EnvConfig, PathStrategy};
+use crate::options::is_system;
+
+/// 通过注册表 `HKCU\Environment\Path` 修改用户 PATH, 避免 setx 截断超过 1024 字符的 PATH;
+/// 引用变量时变量需要已经写入, 写入后检查条目展开后的目录是否存在
 #[cfg(target_os = "windows")]
 pub fn set_persistent_path(var: Option<&str>, new_path: &str) -> io::Result<()> {
-    let path = env::var("Path").unwrap();
-    let path = match var {
-        Some(v) => format!("%{v}%/{new_path};{path}"),
-        None => format!("{new_path};{path}"),
+    use winreg::enums::{HKEY_CURRENT_USER, KEY_READ, KEY_WRITE};
+    use winreg::RegKey;
+
+    let environment = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey_with_flags("Environment", KEY_READ | KEY_WRITE)?;
+    let lookup = |name: &str| {
+        environment
+            .get_value::<String, _>(name)
+            .ok()
+            .or_else(|| env::var(name).ok())
     };
-    Command::new("setx").args(&["Path", &path]).output()?;
 
+    let new_path = windows_path_entry(var, new_path);
+    if let Some(var) = var {
+        if lookup(var).is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("环境变量 {} 尚未设置, 无法添加 PATH: {}", var, new_path),
+            ));
+        }
+    }
+
+    // 用户 PATH 可能还不存在
+    let path: String = environment.get_value("Path").unwrap_or_default();
+
+    let updated = place_path_entry(
+        &path,
+        ';',
+        &new_path,
+        EnvConfig::current()
+            .map_err(io::Error::other)?
+            .path_strategy,
+    );
+    if updated != path {
+        set_expand_string(&environment, "Path", &updated)?;
+        broadcast_environment_change();
+    }
+
+    match expand_windows_vars(&new_path, lookup) {
+        Some(dir) if Path::new(&dir).is_dir() => {}
+        Some(dir) => log::warn!("PATH 条目 {} 展开后的目录不存在: {}", new_path, dir),
+        None => log::warn!("PATH 条目 {} 引用了未设置的环境变量", new_path),
+    }
     Ok(())
 }
 
+/// Windows PATH 条目, 引用变量时为 `%VAR%\bin`, 分隔符统一为反斜杠
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn windows_path_entry(var: Option<&str>, new_path: &str) -> String {
+    let new_path = new_path.replace('/', "\\");
+    match var {
+        Some(var) => format!("%{}%\\{}", var, new_path.trim_start_matches('\\')),
+        None => new_path,
+    }
+}
+
+/// 展开 `%VAR%` 形式的变量, 变量未设置时返回 None
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn expand_windows_vars(entry: &str, lookup: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let mut expanded = String::new();
+    let mut rest = entry;
+    while let Some(start) = rest.find('%') {
+        let Some(len) = rest[start + 1..].find('%') else {
+            break;
+        };
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&lookup(&rest[start + 1..start + 1 + len])?);
+        rest = &rest[start + len + 2..];
+    }
+    expanded.push_str(rest);
+    Some(expanded)
+}
+
+/// 按策略将路径放到 PATH 的最前面或最后面, 并去掉与其相同的已有条目,
+/// 重复安装时 PATH 不会无限增长; `;` 分隔的 Windows 路径不区分大小写及斜杠方向
+fn place_path_entry(path: &str, separator: char, new_path: &str, strategy: PathStrategy) -> String {
+    let normalize = |entry: &str| {
+        let entry = entry.trim_end_matches(['/', '\\']);
+        if separator == ';' {
+            entry.to_lowercase().replace('/', "\\")
+        } else {
+            entry.to_string()
+        }
+    };
+    let target = normalize(new_path);
+    let rest = path
+        .split(separator)
+        .filter(|entry| !entry.is_empty() && normalize(entry) != target);
+
+    let entries: Vec<&str> = match strategy {
+        PathStrategy::Prepend => std::iter::once(new_path).chain(rest).collect(),
+        PathStrategy::Append => rest.chain(std::iter::once(new_path)).collect(),
+    };
+    entries.join(&separator.to_string())
+}
+
+/// 以 REG_EXPAND_SZ 类型写入注册表, 使 PATH 中的 %VAR% 能被展开
+#[cfg(target_os = "windows")]
+fn set_expand_string(key: &winreg::RegKey, name: &str, value: &str) -> io::Result<()> {
+    let bytes = value
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(|c| c.to_le_bytes())
+        .collect();
+
+    key.set_raw_value(
+        name,
+        &winreg::RegValue {
+            bytes,
+            vtype: winreg::enums::REG_EXPAND_SZ,
+        },
+    )
+}
+
+/// 广播 WM_SETTINGCHANGE, 让新启动的进程读取到最新的环境变量
+#[cfg(target_os = "windows")]
+fn broadcast_environment_change() {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        SendMessageTimeoutW, HWND_BROADCAST, SMTO_ABORTIFHUNG, WM_SETTINGCHANGE,
+    };
+
+    let param: Vec<u16> = "Environment"
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut result = 0;
+    unsafe {
+        SendMessageTimeoutW(
+            HWND_BROADCAST,
+            WM_SETTINGCHANGE,
+            0,
+            param.as_ptr() as isize,
+            SMTO_ABORTIFHUNG,
+            5000,
+            &mut result,
+        );
+    }
+}
+
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 pub fn set_persistent_path(var: Option<&str>, new_path: &str) -> io::Result<()> {
-    set_persistent_path_unix(var, new_path, false)?;
+    set_persistent_path_unix(var, new_path, is_system())?;
 
     Ok(())
 }
@@ -29,110 +165,596 @@ pub fn set_persistent_path_unix(
     new_path: &str,
     system_level: bool,
 ) -> io::Result<()> {
+    let config_file = get_profile_file(system_level);
+    let mut block = ManagedBlock::parse(read_lines(&config_file)?, system_level);
+
     let new_path = match var {
+        // /etc/environment 不展开变量, 使用变量已写入的值
+        Some(v) if system_level => {
+            let value = block.get_env(v).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} 中未设置 {}", config_file.display(), v),
+                )
+            })?;
+            format!("{}/{}", value, new_path)
+        }
         Some(v) => format!("${}/{}", v, new_path),
         None => new_path.to_string(),
     };
-    let config_file = if system_level {
+    block.set_path(
+        &new_path,
+        EnvConfig::current()
+            .map_err(io::Error::other)?
+            .path_strategy,
+    );
+    write_lines(&config_file, block.into_lines())
+}
+
+/// env 写入配置文件的受管理区块起始标记
+pub const MANAGED_BLOCK_START: &str = "# >>> env managed >>>";
+/// env 写入配置文件的受管理区块结束标记
+pub const MANAGED_BLOCK_END: &str = "# <<< env managed <<<";
+
+/// /etc/environment 中没有 PATH 时使用的默认值
+const DEFAULT_SYSTEM_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// 配置文件中由 env 管理的区块, 每次只重写区块内的内容, 区块外的用户内容保持不变
+#[derive(Debug, Default)]
+struct ManagedBlock {
+    found: bool,
+    before: Vec<String>,
+    entries: Vec<String>,
+    after: Vec<String>,
+    /// /etc/environment 由 PAM 按 `KEY=value` 解析, 不支持 export 及变量展开
+    system: bool,
+}
+
+impl ManagedBlock {
+    fn parse(lines: Vec<String>, system: bool) -> ManagedBlock {
+        let start = lines.iter().position(|l| l.trim() == MANAGED_BLOCK_START);
+        let end = lines.iter().position(|l| l.trim() == MANAGED_BLOCK_END);
+
+        match (start, end) {
+            (Some(start), Some(end)) if start < end => {
+                let mut lines = lines;
+                let after = lines.split_off(end + 1);
+                let entries = lines[start + 1..end].to_vec();
+                lines.truncate(start);
+                ManagedBlock {
+                    found: true,
+                    before: lines,
+                    entries,
+                    after,
+                    system,
+                }
+            }
+            _ => ManagedBlock {
+                before: lines,
+                system,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn into_lines(self) -> Vec<String> {
+        let mut lines = self.before;
+        // 新建区块时与用户内容之间空一行
+        if !self.found && lines.last().is_some_and(|l| !l.trim().is_empty()) {
+            lines.push(String::new());
+        }
+        lines.push(MANAGED_BLOCK_START.to_string());
+        lines.extend(self.entries);
+        lines.push(MANAGED_BLOCK_END.to_string());
+        lines.extend(self.after);
+        lines
+    }
+
+    /// 变量定义的前缀, shell 配置使用 `export VAR=`, /etc/environment 使用 `VAR=`
+    fn prefix(&self, var_name: &str) -> String {
+        if self.system {
+            format!("{}=", var_name)
+        } else {
+            format!("export {}=", var_name)
+        }
+    }
+
+    fn path_index(&self) -> Option<usize> {
+        let prefix = self.prefix("PATH");
+        self.entries.iter().position(|l| l.starts_with(&prefix))
+    }
+
+    /// 区块中已设置的变量值
+    fn get_env(&self, var_name: &str) -> Option<String> {
+        let prefix = self.prefix(var_name);
+        self.entries
+            .iter()
+            .find_map(|l| l.strip_prefix(&prefix))
+            .map(String::from)
+    }
+
+    /// 区块之外系统原有的 PATH, 区块中的 PATH 需要包含它才不会覆盖掉系统路径
+    fn system_path(&self) -> String {
+        self.before
+            .iter()
+            .chain(&self.after)
+            .rev()
+            .find_map(|l| l.trim().strip_prefix("PATH="))
+            .map(|value| value.trim_matches('"').to_string())
+            .unwrap_or_else(|| DEFAULT_SYSTEM_PATH.to_string())
+    }
+
+    /// 设置环境变量, 已存在则覆盖, 保证变量定义在 PATH 之前
+    fn set_env(&mut self, var_name: &str, var_value: &str) {
+        let prefix = self.prefix(var_name);
+        self.entries.retain(|l| !l.starts_with(&prefix));
+
+        let entry = format!("{}{}", prefix, var_value);
+        match self.path_index() {
+            Some(index) => self.entries.insert(index, entry),
+            None => self.entries.push(entry),
+        }
+    }
+
+    /// 按策略将条目加到 PATH 的最前面或最后面, 已存在时移动到对应位置
+    fn set_path(&mut self, new_path: &str, strategy: PathStrategy) {
+        let prefix = self.prefix("PATH");
+        let index = self.path_index();
+        let path = match index {
+            Some(index) => self.entries[index][prefix.len()..].to_string(),
+            None if self.system => self.system_path(),
+            None => "$PATH".to_string(),
+        };
+
+        let entry = format!(
+            "{}{}",
+            prefix,
+            place_path_entry(&path, ':', new_path, strategy)
+        );
+        match index {
+            Some(index) => self.entries[index] = entry,
+            None => self.entries.push(entry),
+        }
+    }
+}
+
+pub fn get_profile_file(system_level: bool) -> PathBuf {
+    if system_level {
         PathBuf::from("/etc/environment")
     } else {
         let home = env::var("HOME").unwrap();
         PathBuf::from(home + "/.bashrc")
-    };
+    }
+}
 
+fn read_lines(config_file: &Path) -> io::Result<Vec<String>> {
     let mut lines = Vec::new();
     if config_file.exists() {
-        let file = OpenOptions::new().read(true).open(&config_file)?;
+        let file = OpenOptions::new().read(true).open(config_file)?;
         let reader = BufReader::new(file);
         for line in reader.lines() {
             lines.push(line?);
         }
     }
-    let mut found = false;
-    let mut updated_lines = Vec::new();
-    for line in lines {
-        if line.contains("PATH=") {
-            found = true;
-            let parts: Vec<&str> = line.split('=').collect();
-            let mut paths: Vec<&str> = parts[1].split(':').collect();
-            if !paths.contains(&new_path.as_str()) {
-                paths.push(&new_path);
-                let new_line = format!("export PATH={}\n", paths.join(":"));
-                updated_lines.push(new_line);
-            } else {
-                updated_lines.push(line);
-            }
-        } else {
-            updated_lines.push(line);
-        }
-    }
-
-    if !found {
-        updated_lines.push(format!("export PATH=$PATH:{}\n", new_path));
-    }
+    Ok(lines)
+}
 
+fn write_lines(config_file: &Path, lines: Vec<String>) -> io::Result<()> {
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
-        .open(&config_file)?;
-    file.write_all(updated_lines.join("\n").as_bytes())?;
+        .open(config_file)?;
+    file.write_all(lines.join("\n").as_bytes())?;
+    file.write_all(b"\n")?;
     Ok(())
 }
 
-#[cfg(target_os = "windows")]
-pub fn set_persistent_env(var_name: &str, var_value: &str) -> Result<()> {
-    Command::new("setx").args(&[var_name, var_value]).output()?;
+/// 启动新的非交互式登录 shell 读取 PATH, 用于检查写入的配置是否生效,
+/// shell 无法启动或超时(配置文件中有阻塞的命令)时返回 None
+///
+/// 交互式 shell 在脚本或没有终端时可能挂起, 而 ~/.bashrc 通常会在非交互时直接返回,
+/// 因此在登录 shell 的环境上执行配置文件中 env 管理的区块, 模拟新开终端后的 PATH
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn fresh_shell_path() -> Option<String> {
+    let shell = env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
+    let block = read_lines(&get_profile_file(false))
+        .map(|lines| ManagedBlock::parse(lines, false).entries.join("\n"))
+        .unwrap_or_default();
+    login_shell_path(&shell, &block)
+}
+
+/// 在登录 shell 中执行脚本后读取 PATH
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn login_shell_path(shell: &str, script: &str) -> Option<String> {
+    use std::io::Read;
+    use std::process::Stdio;
+    use std::time::{Duration, Instant};
 
+    // 配置文件可能输出其他内容, 只取标记之后的部分
+    const MARKER: &str = "__ENV_PATH__";
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    let command = format!(
+        "eval \"$ENV_PROFILE_BLOCK\" >/dev/null 2>&1; printf '{}%s' \"$PATH\"",
+        MARKER
+    );
+    let mut child = std::process::Command::new(shell)
+        .args(["-l", "-c", &command])
+        .env("ENV_PROFILE_BLOCK", script)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(50)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+
+    let mut output = String::new();
+    child.stdout.take()?.read_to_string(&mut output).ok()?;
+    output
+        .rsplit_once(MARKER)
+        .map(|(_, path)| path.trim_end().to_string())
+}
+
+/// `--system` 需要写入 /etc/environment, 提前检查是否有 root 权限
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn check_system_privilege() -> anyhow::Result<()> {
+    if unsafe { libc::geteuid() } != 0 {
+        return Err(anyhow::anyhow!(
+            "--system 需要 root 权限才能写入 /etc/environment, 请使用 sudo 运行"
+        ));
+    }
     Ok(())
 }
 
+#[cfg(target_os = "windows")]
+pub fn check_system_privilege() -> Result<()> {
+    Err(anyhow::anyhow!("--system 暂只支持 Linux 和 macOS"))
+}
+
+/// 移除配置文件中 env 管理的全部内容
 #[cfg(any(target_os = "linux", target_os = "macos"))]
-pub fn set_persistent_env(var_name: &str, var_value: &str, system_level: bool) -> io::Result<()> {
-    let config_file = if system_level {
-        PathBuf::from("/etc/environment")
-    } else {
-        let home = env::var("HOME").unwrap();
-        PathBuf::from(home + "/.bashrc")
-    };
+pub fn remove_managed_block(system_level: bool) -> io::Result<()> {
+    let config_file = get_profile_file(system_level);
+    if !config_file.exists() {
+        return Ok(());
+    }
 
-    let entry = format!("\nexport {}={}\n", var_name, var_value);
-    let mut lines = Vec::new();
-    if config_file.exists() {
-        let file = OpenOptions::new().read(true).open(&config_file)?;
-        let reader = BufReader::new(file);
-        for line in reader.lines() {
-            lines.push(line?);
+    let block = ManagedBlock::parse(read_lines(&config_file)?, system_level);
+    let mut lines = block.before;
+    lines.extend(block.after);
+    write_lines(&config_file, lines)
+}
+
+/// 修改前的环境变量配置快照, 切换版本中途失败时用于回滚
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub struct ProfileSnapshot {
+    config_file: PathBuf,
+    content: Option<Vec<u8>>,
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+impl ProfileSnapshot {
+    /// 记录 shell 配置文件的当前内容
+    pub fn take(_var_names: &[&str]) -> io::Result<ProfileSnapshot> {
+        ProfileSnapshot::of(get_profile_file(is_system()))
+    }
+
+    fn of(config_file: PathBuf) -> io::Result<ProfileSnapshot> {
+        let content = if config_file.exists() {
+            Some(std::fs::read(&config_file)?)
+        } else {
+            None
+        };
+        Ok(ProfileSnapshot {
+            config_file,
+            content,
+        })
+    }
+
+    /// 恢复配置文件, 修改前不存在的文件直接删除
+    pub fn restore(self) -> io::Result<()> {
+        match self.content {
+            Some(content) => std::fs::write(&self.config_file, content),
+            None if self.config_file.exists() => std::fs::remove_file(&self.config_file),
+            None => Ok(()),
         }
     }
+}
 
-    let var_like = &format!("export {}", var_name);
+/// 修改前的环境变量配置快照, 切换版本中途失败时用于回滚
+#[cfg(target_os = "windows")]
+pub struct ProfileSnapshot {
+    values: Vec<(String, Option<winreg::RegValue>)>,
+}
+
+#[cfg(target_os = "windows")]
+impl ProfileSnapshot {
+    /// 记录注册表中相关变量及 PATH 的当前值
+    pub fn take(var_names: &[&str]) -> io::Result<ProfileSnapshot> {
+        use winreg::enums::{HKEY_CURRENT_USER, KEY_READ};
+        use winreg::RegKey;
+
+        let environment =
+            RegKey::predef(HKEY_CURRENT_USER).open_subkey_with_flags("Environment", KEY_READ)?;
+        let values = var_names
+            .iter()
+            .copied()
+            .chain(std::iter::once("Path"))
+            .map(|name| (name.to_string(), environment.get_raw_value(name).ok()))
+            .collect();
+        Ok(ProfileSnapshot { values })
+    }
+
+    /// 恢复注册表中的值, 修改前不存在的变量直接删除
+    pub fn restore(self) -> io::Result<()> {
+        use winreg::enums::{HKEY_CURRENT_USER, KEY_READ, KEY_WRITE};
+        use winreg::RegKey;
 
-    let mut indexs = Vec::new();
-    for (index, line) in lines.iter().enumerate() {
-        if line.contains(var_like) {
-            indexs.push(index);
+        let environment = RegKey::predef(HKEY_CURRENT_USER)
+            .open_subkey_with_flags("Environment", KEY_READ | KEY_WRITE)?;
+        for (name, value) in &self.values {
+            match value {
+                Some(value) => environment.set_raw_value(name, value)?,
+                None => {
+                    let _ = environment.delete_value(name);
+                }
+            }
         }
+        broadcast_environment_change();
+        Ok(())
     }
-    // 移除重复元素
-    indexs.iter().for_each(|i| {
-        lines.remove(*i);
-    });
-    lines.push(entry);
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(&config_file)?;
+}
 
-    file.write_all(lines.join("\n").as_bytes())?;
+#[cfg(target_os = "windows")]
+pub fn set_persistent_env(var_name: &str, var_value: &str) -> Result<()> {
+    let output = Command::new("setx").args([var_name, var_value]).output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "setx {} 执行失败: {}",
+            var_name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
 
     Ok(())
 }
 
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn set_persistent_env(var_name: &str, var_value: &str) -> io::Result<()> {
+    set_persistent_env_unix(var_name, var_value, is_system())
+}
+
+pub fn set_persistent_env_unix(
+    var_name: &str,
+    var_value: &str,
+    system_level: bool,
+) -> io::Result<()> {
+    let config_file = get_profile_file(system_level);
+
+    let mut block = ManagedBlock::parse(read_lines(&config_file)?, system_level);
+    block.set_env(var_name, var_value);
+    write_lines(&config_file, block.into_lines())
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::path::{set_persistent_env, set_persistent_path};
+    use crate::env_config::PathStrategy;
+    use crate::path::{
+        expand_windows_vars, place_path_entry, set_persistent_env, set_persistent_path,
+        windows_path_entry, write_lines, ManagedBlock, ProfileSnapshot, DEFAULT_SYSTEM_PATH,
+    };
+
+    fn lines(content: &str) -> Vec<String> {
+        content.lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_managed_block_is_idempotent() {
+        let mut block = ManagedBlock::parse(lines("alias ll='ls -l'"), false);
+        block.set_env("JAVA_HOME", "/opt/java");
+        block.set_path("$JAVA_HOME/bin", PathStrategy::Append);
+        let first = block.into_lines();
+
+        let mut block = ManagedBlock::parse(first.clone(), false);
+        block.set_env("JAVA_HOME", "/opt/java");
+        block.set_path("$JAVA_HOME/bin", PathStrategy::Append);
+        assert_eq!(block.into_lines(), first);
+
+        assert_eq!(
+            first,
+            lines(
+                "alias ll='ls -l'\n\n# >>> env managed >>>\nexport JAVA_HOME=/opt/java\nexport PATH=$PATH:$JAVA_HOME/bin\n# <<< env managed <<<"
+            )
+        );
+    }
+
+    #[test]
+    fn test_managed_block_preserves_user_content() {
+        let content = "export A=1\n# >>> env managed >>>\nexport PATH=$PATH:/old\n# <<< env managed <<<\nexport B=2";
+        let mut block = ManagedBlock::parse(lines(content), false);
+        block.set_env("GOROOT", "/opt/go");
+        block.set_path("/opt/go/bin", PathStrategy::Append);
+
+        assert_eq!(
+            block.into_lines(),
+            lines(
+                "export A=1\n# >>> env managed >>>\nexport GOROOT=/opt/go\nexport PATH=$PATH:/old:/opt/go/bin\n# <<< env managed <<<\nexport B=2"
+            )
+        );
+    }
+
+    #[test]
+    fn test_managed_block_system_syntax() {
+        let content = "PATH=\"/usr/local/bin:/usr/bin:/bin\"\nLANG=en_US.UTF-8";
+        let mut block = ManagedBlock::parse(lines(content), true);
+        block.set_path("/opt/java/bin", PathStrategy::Append);
+        block.set_env("JAVA_HOME", "/opt/java");
+        assert_eq!(block.get_env("JAVA_HOME"), Some("/opt/java".to_string()));
+
+        assert_eq!(
+            block.into_lines(),
+            lines(
+                "PATH=\"/usr/local/bin:/usr/bin:/bin\"\nLANG=en_US.UTF-8\n\n# >>> env managed >>>\nJAVA_HOME=/opt/java\nPATH=/usr/local/bin:/usr/bin:/bin:/opt/java/bin\n# <<< env managed <<<"
+            )
+        );
+
+        // 没有 PATH 时使用默认的系统路径
+        let mut block = ManagedBlock::parse(vec![], true);
+        block.set_path("/opt/go/bin", PathStrategy::Append);
+        assert_eq!(
+            block.entries,
+            vec![format!("PATH={}:/opt/go/bin", DEFAULT_SYSTEM_PATH)]
+        );
+    }
+
+    #[test]
+    fn test_prepend_path_entry() {
+        let prepend = |path, new_path| place_path_entry(path, ';', new_path, PathStrategy::Prepend);
+        let path = r"C:\Windows;%JAVA_HOME%/bin;C:\Tools;%java_home%/BIN\;";
+        assert_eq!(
+            prepend(path, "%JAVA_HOME%/bin"),
+            r"%JAVA_HOME%/bin;C:\Windows;C:\Tools"
+        );
+        assert_eq!(prepend("", r"C:\Go\bin"), r"C:\Go\bin");
+
+        assert_eq!(
+            place_path_entry(path, ';', "%JAVA_HOME%/bin", PathStrategy::Append),
+            r"C:\Windows;C:\Tools;%JAVA_HOME%/bin"
+        );
+        // 旧版本写入的正斜杠条目被替换
+        assert_eq!(
+            prepend(path, r"%JAVA_HOME%\bin"),
+            r"%JAVA_HOME%\bin;C:\Windows;C:\Tools"
+        );
+    }
+
+    #[test]
+    fn test_windows_path_entry() {
+        assert_eq!(
+            windows_path_entry(Some("JAVA_HOME"), "/bin"),
+            r"%JAVA_HOME%\bin"
+        );
+        assert_eq!(windows_path_entry(None, "E:/dev/go/bin"), r"E:\dev\go\bin");
+
+        let lookup = |name: &str| (name == "JAVA_HOME").then(|| r"E:\dev\java".to_string());
+        assert_eq!(
+            expand_windows_vars(r"%JAVA_HOME%\bin", lookup).as_deref(),
+            Some(r"E:\dev\java\bin")
+        );
+        assert_eq!(expand_windows_vars(r"%GOROOT%\bin", lookup), None);
+        assert_eq!(
+            expand_windows_vars("C:\\100%", lookup).as_deref(),
+            Some("C:\\100%")
+        );
+    }
+
+    /// 写入用户环境变量及引用它的 PATH 条目后读回, 结束时恢复注册表
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_windows_path_with_var() {
+        use winreg::enums::{HKEY_CURRENT_USER, KEY_READ};
+        use winreg::RegKey;
+
+        let home = std::env::temp_dir().join("env_test_java_home");
+        std::fs::create_dir_all(home.join("bin")).unwrap();
+        let snapshot = ProfileSnapshot::take(&["ENV_TEST_JAVA_HOME"]).unwrap();
+
+        assert!(set_persistent_path(Some("ENV_TEST_JAVA_HOME"), "bin").is_err());
+        set_persistent_env("ENV_TEST_JAVA_HOME", home.to_str().unwrap()).unwrap();
+        set_persistent_path(Some("ENV_TEST_JAVA_HOME"), "bin").unwrap();
+
+        let environment = RegKey::predef(HKEY_CURRENT_USER)
+            .open_subkey_with_flags("Environment", KEY_READ)
+            .unwrap();
+        let value: String = environment.get_value("ENV_TEST_JAVA_HOME").unwrap();
+        let path: String = environment.get_value("Path").unwrap();
+        snapshot.restore().unwrap();
+
+        assert_eq!(value, home.to_str().unwrap());
+        let entry = path
+            .split(';')
+            .find(|entry| entry.starts_with("%ENV_TEST_JAVA_HOME%"))
+            .unwrap();
+        assert_eq!(entry, r"%ENV_TEST_JAVA_HOME%\bin");
+        let expanded = expand_windows_vars(entry, |_| Some(value.clone())).unwrap();
+        assert!(std::path::Path::new(&expanded).is_dir());
+
+        std::fs::remove_dir_all(home).unwrap();
+    }
+
+    #[test]
+    fn test_managed_block_prepend() {
+        let content = "# >>> env managed >>>\nexport PATH=$PATH:/old\n# <<< env managed <<<";
+        let mut block = ManagedBlock::parse(lines(content), false);
+        block.set_path("$JAVA_HOME/bin", PathStrategy::Prepend);
+        assert_eq!(block.entries, vec!["export PATH=$JAVA_HOME/bin:$PATH:/old"]);
+
+        // 已存在的条目移动到最前面
+        block.set_path("/old", PathStrategy::Prepend);
+        assert_eq!(block.entries, vec!["export PATH=/old:$JAVA_HOME/bin:$PATH"]);
+
+        // 系统级配置放在系统 PATH 之前
+        let mut block = ManagedBlock::parse(lines("PATH=\"/usr/bin:/bin\""), true);
+        block.set_path("/opt/java/bin", PathStrategy::Prepend);
+        assert_eq!(block.entries, vec!["PATH=/opt/java/bin:/usr/bin:/bin"]);
+    }
+
+    #[test]
+    fn test_profile_snapshot_restore() {
+        let dir = std::env::temp_dir().join("env_test_profile_snapshot");
+        std::fs::create_dir_all(&dir).unwrap();
+        let profile = dir.join(".bashrc");
+        std::fs::write(&profile, "export A=1\n").unwrap();
+
+        let snapshot = ProfileSnapshot::of(profile.clone()).unwrap();
+        let mut block = ManagedBlock::parse(lines("export A=1"), false);
+        block.set_env("JAVA_HOME", "/opt/java");
+        write_lines(&profile, block.into_lines()).unwrap();
+        snapshot.restore().unwrap();
+        assert_eq!(std::fs::read_to_string(&profile).unwrap(), "export A=1\n");
+
+        // 修改前不存在的文件回滚时删除
+        let created = dir.join(".profile");
+        let snapshot = ProfileSnapshot::of(created.clone()).unwrap();
+        std::fs::write(&created, "export B=2\n").unwrap();
+        snapshot.restore().unwrap();
+        assert!(!created.exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_login_shell_path() {
+        use crate::path::login_shell_path;
+
+        let block = ManagedBlock {
+            entries: vec![
+                "export JAVA_HOME=/opt/env-test/java".to_string(),
+                "export PATH=$JAVA_HOME/bin:$PATH".to_string(),
+            ],
+            ..Default::default()
+        };
+        let path = login_shell_path("/bin/sh", &block.entries.join("\n")).unwrap();
+        assert!(path.starts_with("/opt/env-test/java/bin:"));
+
+        // 没有区块时返回登录 shell 原有的 PATH
+        let path = login_shell_path("/bin/sh", "").unwrap();
+        assert!(!path.is_empty() && !path.contains("/opt/env-test"));
+    }
 
     #[test]
     fn test_set_persistent_path() {
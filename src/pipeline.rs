@@ -0,0 +1,169 @@
+//! 安装流程依赖的下载、解压及切换版本的实现, 测试时可替换为不访问网络和用户配置的实现
+
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use crate::check::is_install_complete;
+use crate::download::{check_package_url, download_cache_dir, download_packages, stream_extract};
+use crate::env_config::{EnvConfig, Environment};
+use crate::environment::switch_version;
+use crate::options::is_dry_run;
+use crate::zip::auto_unzip;
+use crate::{status, t};
+
+/// 下载安装包
+pub trait Downloader: Send + Sync {
+    /// 检查安装包地址是否存在
+    fn exists(&self, url: &str) -> impl Future<Output = Result<bool>> + Send;
+
+    /// 下载安装包, 返回本地文件路径
+    fn download(&self, url: &str) -> impl Future<Output = Result<String>> + Send;
+
+    /// 边下载边解压到指定目录, 不经过下载缓存
+    fn stream_extract(
+        &self,
+        url: &str,
+        output_dir: &Path,
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// 下载得到的安装包
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Package {
+    /// 下载缓存中的安装包文件
+    Archive(String),
+    /// 边下载边解压得到的临时目录, 内容尚未移动到版本目录
+    Extracted(PathBuf),
+}
+
+/// 解压安装包
+pub trait Extractor: Send + Sync {
+    fn extract(&self, filename: &str, output_dir: &Path) -> Result<()>;
+}
+
+/// 安装完成后切换到新版本并记录到配置
+pub trait Activator: Send + Sync {
+    fn activate(&self, env: &Environment, version: &str, version_dir: &Path) -> Result<()>;
+
+    /// 只登记已安装的版本, 不修改当前版本
+    fn register(&self, env: &Environment, version: &str, version_dir: &Path) -> Result<()>;
+}
+
+/// 通过 HTTP 下载到下载缓存目录
+#[derive(Copy, Clone, Debug, Default)]
+pub struct HttpDownloader;
+
+impl Downloader for HttpDownloader {
+    fn exists(&self, url: &str) -> impl Future<Output = Result<bool>> + Send {
+        check_package_url(url)
+    }
+
+    fn download(&self, url: &str) -> impl Future<Output = Result<String>> + Send {
+        download_packages(url)
+    }
+
+    fn stream_extract(
+        &self,
+        url: &str,
+        output_dir: &Path,
+    ) -> impl Future<Output = Result<()>> + Send {
+        stream_extract(url, output_dir)
+    }
+}
+
+/// 按扩展名解压 zip/tar.gz/7z 等格式
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ArchiveExtractor;
+
+impl Extractor for ArchiveExtractor {
+    fn extract(&self, filename: &str, output_dir: &Path) -> Result<()> {
+        auto_unzip(filename, output_dir)
+    }
+}
+
+/// 修改用户的环境变量及 PATH, 并更新全局配置
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ProfileActivator;
+
+impl Activator for ProfileActivator {
+    fn activate(&self, env: &Environment, version: &str, _version_dir: &Path) -> Result<()> {
+        switch_version(env, version)
+    }
+
+    fn register(&self, env: &Environment, version: &str, _version_dir: &Path) -> Result<()> {
+        if is_dry_run() {
+            status!("{}", t!("dry_run.register", env.name, version));
+            return Ok(());
+        }
+        EnvConfig::add_installed_version(&env.name, version)?;
+        status!("{}", t!("version.registered", env.name, version));
+        Ok(())
+    }
+}
+
+/// 一次安装使用的安装目录及各步骤的实现
+#[derive(Clone, Debug)]
+pub struct InstallContext<D = HttpDownloader, E = ArchiveExtractor, A = ProfileActivator> {
+    pub install_dir: PathBuf,
+    /// 安装过程中使用的配置, 版本目录布局、下载缓存目录等都从这里读取
+    pub config: EnvConfig,
+    pub downloader: D,
+    pub extractor: E,
+    pub activator: A,
+}
+
+impl InstallContext {
+    /// 使用真实下载、解压及切换版本实现的安装上下文
+    pub fn new(install_dir: &Path) -> Result<InstallContext> {
+        Ok(InstallContext::with_config(
+            install_dir,
+            EnvConfig::current()?,
+        ))
+    }
+
+    /// 使用指定配置的安装上下文, 不读取用户的全局配置
+    pub fn with_config(install_dir: &Path, config: EnvConfig) -> InstallContext {
+        InstallContext {
+            install_dir: install_dir.to_path_buf(),
+            config,
+            downloader: HttpDownloader,
+            extractor: ArchiveExtractor,
+            activator: ProfileActivator,
+        }
+    }
+}
+
+impl<D, E, A> InstallContext<D, E, A> {
+    /// 环境的版本目录
+    pub fn version_dir(&self, name: &str, version: &str) -> PathBuf {
+        self.config
+            .layout
+            .version_dir(&self.install_dir, name, version)
+    }
+
+    /// 下载缓存目录
+    pub fn cache_dir(&self) -> PathBuf {
+        download_cache_dir(&self.config)
+    }
+
+    /// 同一版本安装锁的文件位置
+    pub fn lock_path(&self, name: &str, version: &str) -> PathBuf {
+        self.config
+            .layout
+            .lock_path(&self.install_dir, name, version)
+    }
+
+    /// 版本的临时解压目录
+    pub fn temp_dir(&self, name: &str, version: &str) -> PathBuf {
+        self.config
+            .layout
+            .temp_dir(&self.install_dir, name, version)
+    }
+
+    /// 版本目录中是否为完整的安装
+    pub fn is_installed(&self, env: &Environment, version: &str) -> bool {
+        is_install_complete(env, version, &self.version_dir(&env.name, version))
+    }
+}
@@ -0,0 +1,299 @@
+//! 安装包下载地址的仓库索引, 内置索引随程序编译,
+//! 通过 `env update-index` 下载的索引保存在配置目录中并优先使用
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+
+use crate::download::{http_client, request_error};
+use crate::env_config::EnvConfig;
+use crate::options::is_dry_run;
+use crate::{get_env_config_dir, status};
+
+/// 环境名称 -> 平台 -> 版本 -> 下载地址
+pub type RepositoryIndex = HashMap<String, HashMap<String, HashMap<String, String>>>;
+
+/// 未配置 `index_url` 时下载索引的地址
+pub const DEFAULT_INDEX_URL: &str =
+    "https://raw.githubusercontent.com/wengchengjian/env/main/.env.repository.json";
+
+/// 下载的索引在配置目录中的文件名
+const INDEX_FILE_NAME: &str = ".env.repository.json";
+
+/// 外部解析命令的超时时间
+const RESOLVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    static ref BUILTIN_INDEX: RepositoryIndex =
+        serde_json::from_str(include_str!("../.env.repository.json")).unwrap();
+    static ref UPDATED_INDEX: Option<RepositoryIndex> = read_updated_index();
+}
+
+/// 下载的索引文件位置
+pub fn get_index_path() -> PathBuf {
+    get_env_config_dir().join(INDEX_FILE_NAME)
+}
+
+/// 读取下载的索引, 文件损坏时忽略并使用内置索引
+fn read_updated_index() -> Option<RepositoryIndex> {
+    let path = get_index_path();
+    let content = fs::read_to_string(&path).ok()?;
+    serde_json::from_str(&content)
+        .inspect_err(|e| log::warn!("仓库索引 {} 无法解析, 使用内置索引: {}", path.display(), e))
+        .ok()
+}
+
+fn lookup_in<'a>(
+    index: &'a RepositoryIndex,
+    name: &str,
+    platform: &str,
+    version: &str,
+) -> Option<&'a str> {
+    index
+        .get(name)?
+        .get(platform)?
+        .get(version)
+        .map(String::as_str)
+}
+
+/// 查询安装包的下载地址, 下载的索引优先于内置索引
+pub fn lookup_package(name: &str, platform: &str, version: &str) -> Option<String> {
+    UPDATED_INDEX
+        .as_ref()
+        .and_then(|index| lookup_in(index, name, platform, version))
+        .or_else(|| lookup_in(&BUILTIN_INDEX, name, platform, version))
+        .map(String::from)
+}
+
+/// 索引中收录的环境版本, 包括所有平台, 下载的索引与内置索引合并
+pub fn indexed_versions(name: &str) -> Vec<String> {
+    let mut versions: Vec<String> = UPDATED_INDEX
+        .iter()
+        .chain([&*BUILTIN_INDEX])
+        .filter_map(|index| index.get(name))
+        .flat_map(|platforms| platforms.values())
+        .flat_map(|packages| packages.keys().cloned())
+        .collect();
+    versions.sort();
+    versions.dedup();
+    versions
+}
+
+/// 调用配置中的 `resolver_command` 解析下载地址, 未配置或命令没有输出时返回 None
+pub fn resolve_with_command(
+    config: &EnvConfig,
+    name: &str,
+    version: &str,
+    os: &str,
+    arch: &str,
+) -> Result<Option<String>> {
+    let Some(command) = &config.resolver_command else {
+        return Ok(None);
+    };
+    run_resolver(command, &[name, version, os, arch], RESOLVER_TIMEOUT)
+}
+
+/// 运行解析命令, 命令按空白分隔为程序及参数, 之后追加解析参数,
+/// 退出码非零、超时或输出的不是下载地址时返回错误
+fn run_resolver(command: &str, args: &[&str], timeout: Duration) -> Result<Option<String>> {
+    let mut parts = command.split_whitespace();
+    let program = parts
+        .next()
+        .ok_or_else(|| anyhow!("resolver_command 不能为空"))?;
+
+    let mut child = Command::new(program)
+        .args(parts)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("无法运行解析命令 {}: {}", command, e))?;
+
+    // 输出超过管道缓冲区时命令会阻塞在写入上, 需要在等待退出的同时读取
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!(
+                "解析命令 {} 超过 {} 秒没有完成",
+                command,
+                timeout.as_secs_f32()
+            ));
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        return Err(anyhow!(
+            "解析命令 {} 执行失败({}): {}",
+            command,
+            status,
+            stderr.trim()
+        ));
+    }
+
+    // 只使用第一行非空输出
+    let Some(url) = stdout.lines().map(str::trim).find(|line| !line.is_empty()) else {
+        return Ok(None);
+    };
+    if !url.contains("://") {
+        return Err(anyhow!("解析命令 {} 输出的不是下载地址: {}", command, url));
+    }
+    Ok(Some(url.to_string()))
+}
+
+/// 在后台线程中读取子进程的输出直到管道关闭
+fn read_in_background<R: Read + Send + 'static>(reader: Option<R>) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut output = vec![];
+        if let Some(mut reader) = reader {
+            let _ = reader.read_to_end(&mut output);
+        }
+        String::from_utf8_lossy(&output).into_owned()
+    })
+}
+
+/// 下载最新的仓库索引, 地址依次使用参数、配置中的 `index_url` 及默认地址
+pub async fn update_index(url: Option<&str>) -> Result<()> {
+    let url = match url {
+        Some(url) => url.to_string(),
+        None => EnvConfig::current()?
+            .index_url
+            .unwrap_or_else(|| DEFAULT_INDEX_URL.to_string()),
+    };
+    let path = get_index_path();
+
+    if is_dry_run() {
+        status!("[dry-run] 下载仓库索引 {} 到 {}", url, path.display());
+        return Ok(());
+    }
+
+    status!("正在下载仓库索引: {}", url);
+    let content = http_client()?
+        .get(&url)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| request_error(&url, e))?
+        .text()
+        .await
+        .map_err(|e| request_error(&url, e))?;
+
+    // 校验格式后再替换, 避免写入无法使用的索引
+    let index: RepositoryIndex =
+        serde_json::from_str(&content).map_err(|e| anyhow!("仓库索引格式错误 {}: {}", url, e))?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, &content)?;
+    fs::rename(&tmp_path, &path)?;
+
+    status!(
+        "已更新仓库索引: {} 个环境, {} 个下载地址, 保存到 {}",
+        index.len(),
+        count_packages(&index),
+        path.display()
+    );
+    Ok(())
+}
+
+fn count_packages(index: &RepositoryIndex) -> usize {
+    index
+        .values()
+        .flat_map(|platforms| platforms.values())
+        .map(HashMap::len)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_resolver() {
+        let dir = std::env::temp_dir().join("env-resolver-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let script = |name: &str, body: &str| {
+            // 通过 sh 运行, 避免刚写入的文件在并行测试中执行时报 Text file busy
+            let path = dir.join(name);
+            fs::write(&path, body).unwrap();
+            format!("sh {}", path.display())
+        };
+        let args = ["tool", "1.2.0", "linux", "x64"];
+        let timeout = Duration::from_secs(5);
+
+        let resolver = script(
+            "resolve.sh",
+            "echo\necho \"https://artifacts.example.com/$1/$2/$1-$3-$4.tar.gz\"",
+        );
+        assert_eq!(
+            run_resolver(&resolver, &args, timeout).unwrap().as_deref(),
+            Some("https://artifacts.example.com/tool/1.2.0/tool-linux-x64.tar.gz")
+        );
+
+        // 输出超过管道缓冲区时不会阻塞
+        let verbose = script(
+            "verbose.sh",
+            "echo https://artifacts.example.com/tool.tar.gz\nhead -c 1000000 /dev/zero | tr '\\0' x",
+        );
+        assert_eq!(
+            run_resolver(&verbose, &args, timeout).unwrap().as_deref(),
+            Some("https://artifacts.example.com/tool.tar.gz")
+        );
+
+        // 没有输出时使用内置的解析方式
+        let silent = script("silent.sh", "exit 0");
+        assert_eq!(run_resolver(&silent, &args, timeout).unwrap(), None);
+
+        let failing = script("fail.sh", "echo 'unknown tool' >&2\nexit 2");
+        let err = run_resolver(&failing, &args, timeout).unwrap_err();
+        assert!(err.to_string().contains("unknown tool"));
+
+        let invalid = script("invalid.sh", "echo not-a-url");
+        assert!(run_resolver(&invalid, &args, timeout).is_err());
+
+        let slow = script("slow.sh", "sleep 5");
+        let err = run_resolver(&slow, &args, Duration::from_millis(200)).unwrap_err();
+        assert!(err.to_string().contains("没有完成"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_lookup_in() {
+        let index: RepositoryIndex = serde_json::from_str(
+            r#"{"java": {"linux-x64": {"25": "https://example.com/jdk-25.tar.gz"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            lookup_in(&index, "java", "linux-x64", "25"),
+            Some("https://example.com/jdk-25.tar.gz")
+        );
+        assert_eq!(lookup_in(&index, "java", "linux-x64", "21"), None);
+        assert_eq!(count_packages(&index), 1);
+
+        // 内置索引可以正常解析
+        assert!(lookup_in(&BUILTIN_INDEX, "java", "linux-x64", "21").is_some());
+    }
+}
@@ -0,0 +1,266 @@
+//! 查询上游发布的可用版本, 结果在下载缓存目录中缓存一段时间
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use crate::check::normalize_version;
+use crate::download::{get_download_cache_dir, http_client, request_error};
+use crate::env_config::{Channel, EnvConfig, Environment};
+use crate::options::{get_channel, is_offline};
+use crate::repository::indexed_versions;
+use crate::{deduplicate, status, version};
+
+/// 远程版本列表的缓存时间
+const VERSION_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// 表示最新稳定版的版本关键字
+pub const LATEST_VERSION: &str = "latest";
+
+/// 版本索引的格式
+enum VersionIndex {
+    /// nodejs.org/dist/index.json
+    Node,
+    /// golang 下载页的 JSON 接口
+    Go,
+    /// services.gradle.org/versions/all
+    Gradle,
+    /// Apache/Python 风格的目录列表, 每个版本一个子目录
+    Directory,
+}
+
+/// 获取环境的版本索引地址
+fn get_version_index(name: &str) -> Option<(&'static str, VersionIndex)> {
+    match name.to_lowercase().as_str() {
+        "node" | "node.js" => Some(("https://nodejs.org/dist/index.json", VersionIndex::Node)),
+        "go" => Some((
+            "https://golang.google.cn/dl/?mode=json&include=all",
+            VersionIndex::Go,
+        )),
+        "gradle" => Some((
+            "https://services.gradle.org/versions/all",
+            VersionIndex::Gradle,
+        )),
+        "maven" => Some((
+            "https://dlcdn.apache.org/maven/maven-3/",
+            VersionIndex::Directory,
+        )),
+        "python" => Some((
+            "https://www.python.org/ftp/python/",
+            VersionIndex::Directory,
+        )),
+        _ => None,
+    }
+}
+
+fn get_version_cache_path(name: &str) -> Result<PathBuf> {
+    Ok(get_download_cache_dir()?.join(format!("versions-{}.json", name.to_lowercase())))
+}
+
+/// 读取未过期的版本缓存, 离线模式下过期的缓存也可以使用
+fn read_version_cache(name: &str) -> Option<Vec<String>> {
+    let path = get_version_cache_path(name).ok()?;
+    let modified = path.metadata().ok()?.modified().ok()?;
+    let age = SystemTime::now()
+        .duration_since(modified)
+        .unwrap_or(Duration::ZERO);
+    if age > VERSION_CACHE_TTL && !is_offline() {
+        return None;
+    }
+
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn write_version_cache(name: &str, versions: &[String]) -> Result<()> {
+    let path = get_version_cache_path(name)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string(versions)?)?;
+    Ok(())
+}
+
+/// 查询远程可用版本, 按版本号从新到旧排序
+pub async fn search_versions(name: &str) -> Result<Vec<String>> {
+    let (url, index) =
+        get_version_index(name).ok_or_else(|| anyhow!("暂不支持查询 {} 的远程版本", name))?;
+
+    if let Some(versions) = read_version_cache(name) {
+        return Ok(versions);
+    }
+
+    status!("正在查询 {} 的可用版本: {}", name, url);
+    let content = http_client()?
+        .get(url)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| request_error(url, e))?
+        .text()
+        .await
+        .map_err(|e| request_error(url, e))?;
+
+    let mut versions = match index {
+        VersionIndex::Node => parse_json_versions(&content, "version", |v| {
+            v.strip_prefix('v').map(String::from)
+        })?,
+        VersionIndex::Go => parse_json_versions(&content, "version", |v| {
+            v.strip_prefix("go").map(String::from)
+        })?,
+        VersionIndex::Gradle => parse_json_versions(&content, "version", |v| Some(v.to_string()))?,
+        VersionIndex::Directory => parse_directory_listing(&content),
+    };
+
+    // 只保留正式发布的版本
+    versions.retain(|v| is_release_version(v));
+    version::sort_newest_first(&mut versions);
+    versions.dedup();
+
+    write_version_cache(name, &versions)?;
+    Ok(versions)
+}
+
+/// 环境已知的版本: 配置中列出的版本、仓库索引中的版本, 指定的版本不在其中时再查询远程版本列表
+/// (查询失败时忽略), 有已知版本时再加上已安装的版本; 返回空列表表示无法判断, 只校验格式,
+/// 配置了 `resolver_command` 时版本由解析命令决定, 同样返回空列表
+pub async fn known_versions(config: &EnvConfig, env: &Environment, version: &str) -> Vec<String> {
+    if config.resolver_command.is_some() {
+        return vec![];
+    }
+
+    let version = normalize_version(version);
+    let mut versions = env.get_version_options();
+    versions.extend(indexed_versions(&env.name));
+    if !versions.contains(&version) && get_version_index(&env.name).is_some() {
+        match search_versions(&env.name).await {
+            Ok(remote) => versions.extend(remote),
+            Err(e) => log::debug!("查询 {} 的远程版本失败: {}", env.name, e),
+        }
+    }
+    if !versions.is_empty() {
+        versions.extend(config.get_install_versions(&env.name));
+    }
+    deduplicate(&versions)
+}
+
+/// 解析 `latest` 关键字为最新的正式版本, 优先查询远程版本索引,
+/// 不支持或查询失败时使用配置中列出的版本
+pub async fn resolve_latest(env: &Environment) -> Result<String> {
+    // 远程版本索引不区分通道, 指定 lts 时从配置中标注的版本里选择
+    if get_channel() == Some(Channel::Lts) {
+        let lts: Vec<String> = env
+            .args
+            .iter()
+            .find(|arg| arg.name == "version")
+            .map(|arg| {
+                arg.options
+                    .iter()
+                    .filter(|v| arg.get_channel(v) == Channel::Lts)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        return latest_of(&lts).ok_or_else(|| anyhow!("{} 没有 lts 通道的版本", env.name));
+    }
+
+    if get_version_index(&env.name).is_some() {
+        match search_versions(&env.name).await {
+            Ok(versions) if !versions.is_empty() => return Ok(versions[0].clone()),
+            Ok(_) => {}
+            Err(e) => log::warn!("查询 {} 的远程版本失败, 使用配置中的版本: {}", env.name, e),
+        }
+    }
+
+    latest_of(&env.get_version_options()).ok_or_else(|| anyhow!("无法确定 {} 的最新版本", env.name))
+}
+
+/// 在版本列表中选出最新的正式版本, 没有数字版本时使用 stable 通道
+fn latest_of(options: &[String]) -> Option<String> {
+    version::max(options.iter().filter(|v| is_release_version(v)))
+        .or_else(|| options.iter().find(|v| *v == "stable"))
+        .cloned()
+}
+
+/// 从 JSON 数组中读取每一项的版本字段
+fn parse_json_versions(
+    content: &str,
+    field: &str,
+    map: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<String>> {
+    let value: Value = serde_json::from_str(content)?;
+    let items = value
+        .as_array()
+        .ok_or_else(|| anyhow!("无法解析版本列表"))?;
+
+    Ok(items
+        .iter()
+        .filter_map(|item| item.get(field)?.as_str())
+        .filter_map(map)
+        .collect())
+}
+
+/// 从目录列表页面中读取形如 `href="3.9.9/"` 的版本目录
+fn parse_directory_listing(content: &str) -> Vec<String> {
+    content
+        .split("href=\"")
+        .skip(1)
+        .filter_map(|part| part.split('"').next()?.strip_suffix('/'))
+        .map(String::from)
+        .collect()
+}
+
+fn is_release_version(version: &str) -> bool {
+    !version.is_empty()
+        && version
+            .split('.')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn test_parse_version_index() {
+        let listing = r#"<a href="../">../</a>
+<a href="3.8.8/">3.8.8/</a>
+<a href="3.9.9/">3.9.9/</a>
+<a href="3.9.10-rc1/">3.9.10-rc1/</a>"#;
+        let mut versions = parse_directory_listing(listing);
+        versions.retain(|v| is_release_version(v));
+        version::sort_newest_first(&mut versions);
+        assert_eq!(versions, vec!["3.9.9", "3.8.8"]);
+
+        let node = r#"[{"version":"v22.12.0","lts":"Jod"},{"version":"v20.18.1","lts":"Iron"}]"#;
+        let versions =
+            parse_json_versions(node, "version", |v| v.strip_prefix('v').map(String::from))
+                .unwrap();
+        assert_eq!(versions, vec!["22.12.0", "20.18.1"]);
+
+        assert_eq!(version::compare("1.10.0", "1.9.3"), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_latest_of() {
+        let options = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+
+        assert_eq!(
+            latest_of(&options(&["1.22rc1", "1.21.6", "1.23.4", "1.9"])),
+            Some("1.23.4".to_string())
+        );
+        assert_eq!(
+            latest_of(&options(&["21", "17", "11", "8"])),
+            Some("21".to_string())
+        );
+        assert_eq!(
+            latest_of(&options(&["nightly", "stable", "beta"])),
+            Some("stable".to_string())
+        );
+        assert_eq!(latest_of(&options(&["nightly"])), None);
+    }
+}
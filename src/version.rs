@@ -0,0 +1,220 @@
+//! 版本号比较, 支持配置及上游索引中使用的点分版本号,
+//! 以及 `1.22.0-beta1`、`1.22rc1` 这类预发布版本
+
+use std::cmp::Ordering;
+
+/// 解析后的版本号, 数字部分缺省的段按 0 处理, 如 `1.24` 与 `1.24.0` 相等
+#[derive(Debug, Clone)]
+pub struct Version {
+    parts: Vec<u64>,
+    pre: Option<String>,
+}
+
+impl Version {
+    /// 解析版本号, 允许 `v` 前缀, 不以数字开头(如 stable、nightly)时返回 None
+    pub fn parse(version: &str) -> Option<Version> {
+        let version = version.trim();
+        let version = version.strip_prefix(['v', 'V']).unwrap_or(version);
+        if !version.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+
+        // 第一个既不是数字也不是点的位置开始为预发布标识
+        let split = version
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(version.len());
+        let (numbers, pre) = version.split_at(split);
+        let parts = numbers
+            .split('.')
+            .filter(|p| !p.is_empty())
+            .map(|p| p.parse().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        let pre = pre.trim_start_matches(['-', '.', '+']);
+
+        Some(Version {
+            parts,
+            pre: (!pre.is_empty()).then(|| pre.to_string()),
+        })
+    }
+
+    /// 是否为预发布版本
+    pub fn is_prerelease(&self) -> bool {
+        self.pre.is_some()
+    }
+
+    fn part(&self, index: usize) -> u64 {
+        self.parts.get(index).copied().unwrap_or(0)
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.parts.len().max(other.parts.len());
+        (0..len)
+            .map(|i| self.part(i).cmp(&other.part(i)))
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                // 同一版本号的预发布版本早于正式版本
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(a), Some(b)) => compare_prerelease(a, b),
+            })
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// 预发布标识先比较字母部分, 再按数字比较, 使 `beta10` 晚于 `beta2`
+fn compare_prerelease(a: &str, b: &str) -> Ordering {
+    let split = |s: &str| {
+        let index = s.find(|c: char| c.is_ascii_digit()).unwrap_or(s.len());
+        let (name, number) = s.split_at(index);
+        (name.to_lowercase(), number.parse::<u64>().unwrap_or(0))
+    };
+    split(a).cmp(&split(b))
+}
+
+/// 比较两个版本号, 无法解析的版本(如 stable)排在所有数字版本之前, 之间按字符串比较
+pub fn compare(a: &str, b: &str) -> Ordering {
+    match (Version::parse(a), Version::parse(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => a.cmp(b),
+    }
+}
+
+/// 选出最新的版本
+pub fn max<'a, I>(versions: I) -> Option<&'a String>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    versions.into_iter().max_by(|a, b| compare(a, b))
+}
+
+/// 按从新到旧排序
+pub fn sort_newest_first(versions: &mut [String]) {
+    versions.sort_by(|a, b| compare(b, a));
+}
+
+/// 判断版本是否满足范围, 多个条件以逗号或空格分隔且需同时满足, 支持:
+/// `*`、`>=1.20`、`>1.20`、`<=2`、`<2`、`=1.2.3`、`^1.2`(主版本相同)、
+/// `~1.2`(主次版本相同) 以及 `1.2` 这样的前缀匹配
+pub fn satisfies(version: &str, range: &str) -> bool {
+    let Some(version) = Version::parse(version) else {
+        return range.trim() == "*" || range.trim() == version;
+    };
+
+    comparators(range).all(|comparator| satisfies_one(&version, &comparator))
+}
+
+/// 拆分范围中的条件, 只有运算符的部分与后面的版本号合并, 如 `>= 1.20` 为一个条件
+fn comparators(range: &str) -> impl Iterator<Item = String> + '_ {
+    let mut op = String::new();
+    range
+        .split([',', ' '])
+        .filter(|c| !c.is_empty())
+        .filter_map(move |part| {
+            if part.chars().all(|c| "<>=^~".contains(c)) {
+                op.push_str(part);
+                return None;
+            }
+            Some(std::mem::take(&mut op) + part)
+        })
+}
+
+fn satisfies_one(version: &Version, comparator: &str) -> bool {
+    if comparator == "*" {
+        return true;
+    }
+
+    let ops = [">=", "<=", ">", "<", "=", "^", "~"];
+    let (op, rest) = ops
+        .iter()
+        .find_map(|op| comparator.strip_prefix(op).map(|rest| (*op, rest)))
+        .unwrap_or(("", comparator));
+    let Some(bound) = Version::parse(rest) else {
+        return false;
+    };
+
+    match op {
+        ">=" => version >= &bound,
+        "<=" => version <= &bound,
+        ">" => version > &bound,
+        "<" => version < &bound,
+        "=" => version == &bound,
+        "^" => version >= &bound && version.part(0) == bound.part(0),
+        "~" => {
+            version >= &bound
+                && version.part(0) == bound.part(0)
+                && version.part(1) == bound.part(1)
+        }
+        // 只写出的段需要相同, 如 `17` 匹配 `17.0.12`
+        _ => bound
+            .parts
+            .iter()
+            .enumerate()
+            .all(|(i, part)| version.part(i) == *part),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(compare("9.0.0", "10.0.0"), Ordering::Less);
+        assert_eq!(compare("1.10.0", "1.9.3"), Ordering::Greater);
+        assert_eq!(compare("1.24", "1.24.0"), Ordering::Equal);
+        assert_eq!(compare("v22.12.0", "22.12.0"), Ordering::Equal);
+        assert_eq!(compare("1.22.0-beta1", "1.22.0"), Ordering::Less);
+        assert_eq!(compare("1.22rc1", "1.21.6"), Ordering::Greater);
+        assert_eq!(compare("1.22.0-beta10", "1.22.0-beta2"), Ordering::Greater);
+        assert_eq!(compare("1.22.0-beta1", "1.22.0-rc1"), Ordering::Less);
+        assert_eq!(compare("stable", "1.0"), Ordering::Less);
+
+        let versions: Vec<String> = ["8", "21", "17.0.12", "11"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(max(&versions), Some(&"21".to_string()));
+
+        let mut sorted = versions.clone();
+        sort_newest_first(&mut sorted);
+        assert_eq!(sorted, vec!["21", "17.0.12", "11", "8"]);
+    }
+
+    #[test]
+    fn test_satisfies() {
+        assert!(satisfies("1.23.4", ">=1.20, <2"));
+        assert!(!satisfies("2.0.0", ">=1.20 <2"));
+        // 运算符与版本号之间可以有空格
+        assert!(satisfies("1.23.4", ">= 1.20, < 2"));
+        assert!(!satisfies("1.19.0", ">= 1.20, < 2"));
+        assert!(!satisfies("2.0.0", ">= 1.20, < 2"));
+        assert!(satisfies("17.0.12", "17"));
+        assert!(!satisfies("17.0.12", "17.1"));
+        assert!(satisfies("1.9.3", "^1.2"));
+        assert!(!satisfies("2.0.0", "^1.2"));
+        assert!(satisfies("1.2.9", "~1.2"));
+        assert!(!satisfies("1.3.0", "~1.2"));
+        assert!(satisfies("3.9.9", "=3.9.9"));
+        assert!(satisfies("stable", "*"));
+        assert!(!satisfies("stable", ">=1"));
+    }
+}
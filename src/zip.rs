@@ -1,19 +1,25 @@
 use anyhow::{anyhow, Result};
 use bzip2::read::BzDecoder;
 use flate2::read::GzDecoder;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use lazy_static::lazy_static;
 use serde_json::{json, Value};
 use sevenz_rust::decompress_file;
+use std::collections::HashSet;
 use std::fs::{self, File};
 use std::io::BufReader;
-use std::io::{copy, Read};
-use std::path::Path;
-use tar::Archive;
+use std::io::{copy, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use tar::{Archive, Entry};
 use xz2::read::XzDecoder;
 use zip::ZipArchive;
 
-use crate::download::create_pbr;
+use crate::error::EnvError;
+use crate::interrupt::check_cancelled;
+use crate::output::{is_progress_terminal, plain_progress_bar, show_progress};
+use crate::status;
 
 lazy_static! {
     pub static ref DEFAULT_FORMAT: Value = json!({
@@ -23,9 +29,19 @@ lazy_static! {
     });
 }
 
-pub fn auto_unzip(filename: &str, output: &str) -> Result<()> {
+/// 按文件类型解压, 不会删除源文件, 由调用方在解压成功后决定是否保留
+pub fn auto_unzip(filename: &str, output_dir: &Path) -> Result<()> {
+    unpack(filename, output_dir).map_err(|e| {
+        EnvError::ExtractFailed {
+            file: filename.to_string(),
+            reason: format!("{:#}", e),
+        }
+        .into()
+    })
+}
+
+fn unpack(filename: &str, output_dir: &Path) -> Result<()> {
     let file_path = Path::new(filename);
-    let output_dir = Path::new(output);
 
     if let Err(e) = fs::create_dir_all(output_dir) {
         return Err(anyhow!("无法创建输出目录: {}", e));
@@ -40,15 +56,53 @@ pub fn auto_unzip(filename: &str, output: &str) -> Result<()> {
             FileType::XZ => unxz_file(file_path, output_dir)?,
             FileType::SZ => un7z_file(file_path, output_dir)?,
             FileType::TARGZ => untargz_file(file_path, output_dir)?,
-            FileType::UNKNOWN => return Err(anyhow!("不支持的压缩格式")),
         }
     } else {
         return Err(anyhow!("无法识别文件类型"));
     }
-    fs::remove_file(filename)?;
     Ok(())
 }
 
+/// 估算解压后占用的空间, zip 读取中央目录, gzip 读取文件末尾记录的原始大小,
+/// 其他格式无法在解压前得知大小, 返回 None
+pub fn estimate_unpacked_size(filename: &str) -> Option<u64> {
+    let file_path = Path::new(filename);
+    match get_file_type(file_path)? {
+        FileType::ZIP => zip_unpacked_size(file_path).ok(),
+        FileType::TAR => fs::metadata(file_path).ok().map(|m| m.len()),
+        FileType::GZ | FileType::TARGZ => gzip_unpacked_size(file_path).ok(),
+        _ => None,
+    }
+}
+
+fn zip_unpacked_size(file_path: &Path) -> Result<u64> {
+    let mut archive = ZipArchive::new(File::open(file_path)?)?;
+    let mut total = 0;
+    for i in 0..archive.len() {
+        total += archive.by_index_raw(i)?.size();
+    }
+    Ok(total)
+}
+
+/// gzip 末尾 4 字节是原始大小对 2^32 取模后的值, 超过 4GB 时按压缩后的大小补足
+fn gzip_unpacked_size(file_path: &Path) -> Result<u64> {
+    let mut file = File::open(file_path)?;
+    let len = file.metadata()?.len();
+    if len < 18 {
+        return Err(anyhow!("不是有效的 gzip 文件: {}", file_path.display()));
+    }
+
+    let mut buffer = [0; 4];
+    file.seek(SeekFrom::End(-4))?;
+    file.read_exact(&mut buffer)?;
+
+    let mut size = u32::from_le_bytes(buffer) as u64;
+    while size < len {
+        size += 1 << 32;
+    }
+    Ok(size)
+}
+
 fn get_file_type(file_path: &Path) -> Option<FileType> {
     let filename = file_path.file_name().unwrap().to_str().unwrap();
     if filename.contains(".tar.gz") {
@@ -88,44 +142,122 @@ fn get_file_type(file_path: &Path) -> Option<FileType> {
 }
 
 pub fn create_unzip_progress_bar(total: usize) -> ProgressBar {
-    let pb = ProgressBar::new(total as u64);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({eta})")
-        .unwrap());
+    if !show_progress() {
+        return ProgressBar::hidden();
+    }
+    if !is_progress_terminal() {
+        return plain_progress_bar(total as u64, "解压进度", false);
+    }
+    let pb = ProgressBar::with_draw_target(Some(total as u64), ProgressDrawTarget::stderr());
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({eta})",
+            )
+            .unwrap(),
+    );
     pb
 }
 
+/// 解压 zip 使用的最大线程数
+const MAX_UNZIP_THREADS: usize = 8;
+
+/// 先按顺序创建所有目录, 再由多个线程各自打开压缩包并发解压文件,
+/// 线程之间不会同时创建同一个目录
 fn unzip_file(file_path: &Path, output_dir: &Path) -> Result<()> {
+    let threads = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .clamp(1, MAX_UNZIP_THREADS);
+    unzip_with_threads(file_path, output_dir, threads)
+}
+
+/// 使用指定数量的线程解压 zip
+fn unzip_with_threads(file_path: &Path, output_dir: &Path, threads: usize) -> Result<()> {
     let file = File::open(file_path).map_err(|e| anyhow!("无法打开 zip 文件: {}", e))?;
 
     let mut archive = ZipArchive::new(file).map_err(|e| anyhow!("无法打开 zip 存档: {}", e))?;
     let total_files = archive.len();
     let pb = create_unzip_progress_bar(total_files);
+
+    let mut files = vec![];
+    let mut dirs = HashSet::new();
     for i in 0..total_files {
-        let mut entry = archive
-            .by_index(i)
+        let entry = archive
+            .by_index_raw(i)
             .map_err(|e| anyhow!("无法读取 zip 条目 {}: {}", i, e))?;
 
-        let entry_path = output_dir.join(entry.mangled_name());
+        let name = entry
+            .enclosed_name()
+            .ok_or_else(|| anyhow!("压缩包条目路径超出解压目录: {}", entry.name()))?;
+        let entry_path = safe_join(output_dir, &name)?;
         if entry.is_dir() {
-            fs::create_dir_all(&entry_path)
-                .map_err(|e| anyhow!("无法创建目录 {}: {}", entry_path.display(), e))?;
+            dirs.insert(entry_path);
+            pb.inc(1);
         } else {
             if let Some(parent) = entry_path.parent() {
-                fs::create_dir_all(parent)?;
+                dirs.insert(parent.to_path_buf());
             }
-            let mut file = File::create(&entry_path)?;
-
-            copy(&mut entry, &mut file)
-                .map_err(|e| anyhow!("无法解压文件 {}: {}", entry_path.display(), e))?;
+            files.push((i, entry_path));
         }
-        pb.inc(1);
     }
+    for dir in &dirs {
+        fs::create_dir_all(dir).map_err(|e| anyhow!("无法创建目录 {}: {}", dir.display(), e))?;
+    }
+
+    let threads = threads.clamp(1, files.len().max(1));
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| -> Result<()> {
+                    let mut archive = ZipArchive::new(File::open(file_path)?)?;
+                    while !failed.load(Ordering::Relaxed) {
+                        let Some((i, entry_path)) = files.get(next.fetch_add(1, Ordering::Relaxed))
+                        else {
+                            break;
+                        };
+                        extract_zip_entry(&mut archive, *i, entry_path).inspect_err(|_| {
+                            failed.store(true, Ordering::Relaxed);
+                        })?;
+                        pb.inc(1);
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow!("解压线程异常退出")))
+            })
+            .collect::<Result<Vec<()>>>()
+    })?;
     pb.finish_with_message("解压完成");
 
     Ok(())
 }
 
+fn extract_zip_entry(
+    archive: &mut ZipArchive<File>,
+    index: usize,
+    entry_path: &Path,
+) -> Result<()> {
+    check_cancelled()?;
+    let mut entry = archive
+        .by_index(index)
+        .map_err(|e| anyhow!("无法读取 zip 条目 {}: {}", index, e))?;
+    let mut file = File::create(entry_path)?;
+    copy(&mut entry, &mut file)
+        .map_err(|e| anyhow!("无法解压文件 {}: {}", entry_path.display(), e))?;
+    Ok(())
+}
+
 fn ungzip_file(file_path: &Path, output_dir: &Path) -> Result<()> {
     let file = File::open(file_path)?;
     let decoder = GzDecoder::new(file);
@@ -138,9 +270,12 @@ fn ungzip_file(file_path: &Path, output_dir: &Path) -> Result<()> {
 }
 
 fn untar_file(file_path: &Path, output_dir: &Path) -> Result<()> {
+    fs::create_dir_all(output_dir)?;
     let file = File::open(file_path)?;
     let mut archive = Archive::new(file);
-    archive.unpack(output_dir)?;
+    for entry in archive.entries()? {
+        unpack_tar_entry(&mut entry?, output_dir)?;
+    }
     Ok(())
 }
 
@@ -171,44 +306,33 @@ fn un7z_file(file_path: &Path, output_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+fn open_targz(file_path: &Path) -> Result<Archive<BufReader<GzDecoder<File>>>> {
+    let file = File::open(file_path)?;
+    Ok(Archive::new(BufReader::new(GzDecoder::new(file))))
+}
+
 fn untargz_file(file_path: &Path, output_dir: &Path) -> Result<()> {
     if let Err(e) = fs::create_dir_all(output_dir) {
         return Err(anyhow!("无法创建输出目录: {}", e));
     }
 
-    let file = File::open(file_path)?;
-    let decoder = GzDecoder::new(file);
-    let reader = BufReader::new(decoder);
-    let mut archive = Archive::new(reader);
-    let total_files = archive.entries()?.count();
+    // tar 条目只能顺序读取一次, 统计数量与解压分别打开
+    let total_files = open_targz(file_path)?.entries()?.count();
     let pb = create_unzip_progress_bar(total_files);
+    let mut archive = open_targz(file_path)?;
     for file in archive.entries()? {
         let mut file = file?;
-        let path = file.path()?;
-
-        if path.as_os_str().is_empty() {
-            return Err(anyhow!("无效的 tar 条目路径"));
-        }
-
-        let output_path = output_dir.join(&path);
-        if file.header().entry_type().is_dir() {
-            fs::create_dir_all(&output_path)?;
-        } else {
-            if let Some(parent) = output_path.parent() {
-                fs::create_dir_all(parent)?;
-            }
-
-            let mut out_file = File::create(&output_path)?;
-            let filepath = file.header().path()?;
-            let filename = filepath.file_name().unwrap().to_str().unwrap();
-
-            println!(
+        let output_path = unpack_tar_entry(&mut file, output_dir)?;
+        if file.header().entry_type().is_file() {
+            status!(
                 "File {} extracted to \"{}\" ({} bytes)",
-                filename,
+                output_path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy(),
                 output_path.display(),
                 file.size()
             );
-            copy(&mut file, &mut out_file)?;
         }
         pb.inc(1);
     }
@@ -218,6 +342,37 @@ fn untargz_file(file_path: &Path, output_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+/// 将 tar 条目解压到 `output_dir` 下并返回目标路径。
+/// `unpack_in` 会校验规范化后的父目录, 防止条目借助前面解压出的符号链接写到解压目录之外
+fn unpack_tar_entry<R: Read>(entry: &mut Entry<R>, output_dir: &Path) -> Result<PathBuf> {
+    check_cancelled()?;
+    let path = entry.path()?.into_owned();
+    if path.as_os_str().is_empty() {
+        return Err(anyhow!("无效的 tar 条目路径"));
+    }
+    let output_path = safe_join(output_dir, &path)?;
+    entry
+        .unpack_in(output_dir)
+        .map_err(|e| anyhow!("无法解压 tar 条目 {}: {}", path.display(), e))?;
+    Ok(output_path)
+}
+
+/// 将压缩包中的条目路径拼接到解压目录,
+/// 拒绝绝对路径及通过 `..` 跳出解压目录的条目(zip-slip)
+fn safe_join(output_dir: &Path, entry: &Path) -> Result<PathBuf> {
+    let mut depth = 0usize;
+    for component in entry.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return Err(anyhow!("压缩包条目路径超出解压目录: {}", entry.display())),
+        }
+    }
+    Ok(output_dir.join(entry))
+}
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Eq)]
 enum FileType {
     ZIP,
@@ -226,26 +381,209 @@ enum FileType {
     BZ2,
     XZ,
     SZ,
-    UNKNOWN,
     TARGZ,
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::zip::auto_unzip;
+    use crate::zip::{
+        auto_unzip, estimate_unpacked_size, safe_join, unzip_with_threads, MAX_UNZIP_THREADS,
+    };
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_safe_join() {
+        let output = Path::new("/tmp/out");
+        assert_eq!(
+            safe_join(output, Path::new("jdk/bin/../lib")).unwrap(),
+            output.join("jdk/bin/../lib")
+        );
+        assert!(safe_join(output, Path::new("../etc/passwd")).is_err());
+        assert!(safe_join(output, Path::new("jdk/../../etc")).is_err());
+        assert!(safe_join(output, Path::new("/etc/passwd")).is_err());
+    }
+
+    #[test]
+    fn test_reject_tar_slip() {
+        let dir = std::env::temp_dir().join("env_tar_slip_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        // tar::Builder 会拒绝 `..` 路径, 直接写入头部的文件名
+        let archive = dir.join("evil.tar.gz");
+        let file = fs::File::create(&archive).unwrap();
+        let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let content = b"evil";
+        let mut header = tar::Header::new_old();
+        let name = b"../evil.txt";
+        header.as_old_mut().name[..name.len()].copy_from_slice(name);
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, &content[..]).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let output = dir.join("out");
+        let err = auto_unzip(archive.to_str().unwrap(), &output).unwrap_err();
+        assert!(err.to_string().contains("超出解压目录"));
+        assert!(!dir.join("evil.txt").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// 写入一个指向 `target` 的符号链接 `pkg/link`, 以及通过该链接写入的 `pkg/link/evil.txt`
+    fn append_symlink_escape<W: std::io::Write>(builder: &mut tar::Builder<W>, target: &Path) {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        header.set_mode(0o777);
+        builder
+            .append_link(&mut header, "pkg/link", target)
+            .unwrap();
+        let content = b"evil";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "pkg/link/evil.txt", &content[..])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_reject_tar_symlink_escape() {
+        let dir = std::env::temp_dir().join("env_tar_symlink_test");
+        let _ = fs::remove_dir_all(&dir);
+        let outside = dir.join("outside");
+        fs::create_dir_all(&outside).unwrap();
+
+        let tar = dir.join("evil.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&tar).unwrap());
+        append_symlink_escape(&mut builder, &outside);
+        builder.into_inner().unwrap();
+
+        let targz = dir.join("evil.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            fs::File::create(&targz).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        append_symlink_escape(&mut builder, &outside);
+        builder.into_inner().unwrap().finish().unwrap();
+
+        for archive in [tar, targz] {
+            let output = dir.join("out");
+            let _ = fs::remove_dir_all(&output);
+            assert!(auto_unzip(archive.to_str().unwrap(), &output).is_err());
+            assert!(!outside.join("evil.txt").exists());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unzip_many_files() {
+        let dir = std::env::temp_dir().join("env_unzip_many_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let archive = dir.join("pkg.zip");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        writer.add_directory("pkg/empty/", options).unwrap();
+        for i in 0..500 {
+            writer
+                .start_file(format!("pkg/lib/{}/file-{}.txt", i % 7, i), options)
+                .unwrap();
+            std::io::Write::write_all(&mut writer, i.to_string().as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let output = dir.join("out");
+        auto_unzip(archive.to_str().unwrap(), &output).unwrap();
+        assert!(output.join("pkg/empty").is_dir());
+        for i in [0, 123, 499] {
+            let path = output.join(format!("pkg/lib/{}/file-{}.txt", i % 7, i));
+            assert_eq!(fs::read_to_string(path).unwrap(), i.to_string());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// 对比单线程与并发解压真实安装包的耗时, 需要通过 `ENV_BENCH_ZIP` 指定 zip 文件:
+    /// `ENV_BENCH_ZIP=jdk-17.zip cargo test --release bench_unzip -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_unzip() {
+        let archive = std::env::var("ENV_BENCH_ZIP").expect("未设置 ENV_BENCH_ZIP");
+        let output = std::env::temp_dir().join("env_unzip_bench");
+
+        for threads in [1, 4, MAX_UNZIP_THREADS] {
+            let _ = fs::remove_dir_all(&output);
+            let start = std::time::Instant::now();
+            unzip_with_threads(Path::new(&archive), &output, threads).unwrap();
+            println!("{} 个线程: {:?}", threads, start.elapsed());
+        }
+
+        fs::remove_dir_all(&output).unwrap();
+    }
+
+    #[test]
+    fn test_estimate_unpacked_size() {
+        let dir = std::env::temp_dir().join("env_unpacked_size_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let content = vec![b'a'; 64 * 1024];
+
+        let archive = dir.join("pkg.zip");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+        writer
+            .start_file("pkg/bin/tool", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(&mut writer, &content).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            estimate_unpacked_size(archive.to_str().unwrap()),
+            Some(content.len() as u64)
+        );
+
+        let archive = dir.join("pkg.tar.gz");
+        let file = fs::File::create(&archive).unwrap();
+        let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "pkg/bin/tool", &content[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+        // tar 有 512 字节的头部及结尾的空块
+        let size = estimate_unpacked_size(archive.to_str().unwrap()).unwrap();
+        assert!(size > content.len() as u64 && size < content.len() as u64 + 16 * 1024);
+
+        assert_eq!(
+            estimate_unpacked_size(dir.join("pkg.7z").to_str().unwrap()),
+            None
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 
     #[test]
     fn test_auto_unzip() {
         //zip
         auto_unzip(
             "E:\\wengchengjian\\下载\\jdk-17.0.12_windows-x64_bin.zip",
-            "E:\\project\\rust-project\\env\\test\\java",
+            Path::new("E:\\project\\rust-project\\env\\test\\java"),
         )
         .unwrap();
         //tar.gz
         auto_unzip(
             "E:\\wengchengjian\\下载\\jdk-17.0.12_linux-aarch64_bin.tar.gz",
-            "E:\\project\\rust-project\\env\\test\\java2",
+            Path::new("E:\\project\\rust-project\\env\\test\\java2"),
         )
         .unwrap();
     }